@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+
+use crate::{issue_paths, relative_link, write_file_with_retry, Config, Issue};
+
+pub const ASSETS_DIR: &str = "assets";
+const MANIFEST_FILE: &str = "attachments.json";
+
+// Markdown images hosted on GitHub's attachment storage
+const IMAGE_PATTERN: &str = r"!\[([^\]]*)\]\((https://(?:user-images\.githubusercontent\.com/|github\.com/[^\s)]*assets/)[^\s)]+)\)";
+
+// Markdown images and links, and HTML images, with absolute URLs
+const REFERENCE_PATTERN: &str = r#"(!?)\[[^\]]*\]\((https?://[^\s)]+)\)|<img\s[^>]*src="(https?://[^"]+)""#;
+// Where GitHub stores files attached to issues
+const ATTACHMENT_HOST_PATTERN: &str = r"^https://(?:user-images\.githubusercontent\.com/|github\.com/[^\s)]*/(?:assets|files)/)";
+
+/// Maps downloaded files in `assets/` back to the URLs they came from.
+type Manifest = BTreeMap<String, String>;
+
+/// Downloads the images referenced in the issue bodies into `assets/` and
+/// points the markdown at the local copies. Files that were already
+/// downloaded are reused; a failed download leaves the remote link in place.
+/// Links are relative to each issue's file, so they also work from files
+/// in subdirectories.
+pub async fn localize(config: &Config, issues: &mut [Issue]) -> Result<()> {
+    let paths = issue_paths(config, issues)?;
+    let assets_dir = config.issues_dir.join(ASSETS_DIR);
+    localize_with(config, &config.issues_dir, issues, |number| match paths.get(&number) {
+        Some(path) => relative_link(config, path, &assets_dir),
+        None => ASSETS_DIR.to_string(),
+    })
+    .await
+}
+
+/// Like [`localize`], but keeps `assets/` and its manifest under `dir`
+/// instead of the issues directory, with the issue files next to it.
+pub async fn localize_into(config: &Config, dir: &Path, issues: &mut [Issue]) -> Result<()> {
+    localize_with(config, dir, issues, |_| ASSETS_DIR.to_string()).await
+}
+
+// `assets_link` gives the link to the assets folder from an issue's file
+async fn localize_with(config: &Config, dir: &Path, issues: &mut [Issue], assets_link: impl Fn(i64) -> String) -> Result<()> {
+    let assets_dir = dir.join(ASSETS_DIR);
+    let mut manifest = load_manifest(dir)?;
+    let http = reqwest::Client::new();
+    let pattern = Regex::new(IMAGE_PATTERN).unwrap();
+
+    for issue in issues.iter_mut() {
+        let Some(body) = &issue.body else { continue };
+
+        let mut local_names = BTreeMap::new();
+        for captures in pattern.captures_iter(body) {
+            let url = captures[2].to_string();
+            let name = local_file_name(issue.number, &url);
+            let path = assets_dir.join(&name);
+
+            if !path.exists() && !config.dry_run {
+                if let Err(e) = download(&http, &url, &path, config.io_retries).await {
+                    eprintln!(
+                        "{}",
+                        config.colors.warning(&format!("Warning: failed to download {} for issue #{}: {:#}", url, issue.number, e))
+                    );
+                    continue;
+                }
+                println!("Downloaded {} to {}", url, path.display());
+            }
+
+            manifest.insert(name.clone(), url.clone());
+            local_names.insert(url, name);
+        }
+
+        if !local_names.is_empty() {
+            let assets_link = assets_link(issue.number);
+            issue.body = Some(rewrite_images(body, |url| {
+                local_names.get(url).map(|name| format!("{}/{}", assets_link, name))
+            }));
+        }
+    }
+
+    if !config.dry_run {
+        save_manifest(dir, &manifest, config.io_retries)?;
+    }
+    Ok(())
+}
+
+/// Sets `attachments` on each issue to the URLs of the images in its body,
+/// and of links to files attached on GitHub, in order of appearance and
+/// without duplicates. Nothing is downloaded, so it costs no requests.
+pub fn inventory(issues: &mut [Issue]) {
+    let references = Regex::new(REFERENCE_PATTERN).unwrap();
+    let attachment_host = Regex::new(ATTACHMENT_HOST_PATTERN).unwrap();
+
+    for issue in issues.iter_mut() {
+        let mut urls: Vec<String> = Vec::new();
+        for captures in references.captures_iter(issue.body.as_deref().unwrap_or_default()) {
+            let url = match (captures.get(2), captures.get(3)) {
+                (Some(url), _) if &captures[1] == "!" || attachment_host.is_match(url.as_str()) => url.as_str(),
+                (_, Some(url)) => url.as_str(),
+                _ => continue,
+            };
+            if !urls.iter().any(|seen| seen == url) {
+                urls.push(url.to_string());
+            }
+        }
+        issue.attachments = Some(urls);
+    }
+}
+
+/// Points links to downloaded assets back at their original remote URLs
+/// before a push. Links to local files that weren't downloaded by retasks
+/// are left alone.
+pub fn restore_remote_urls(config: &Config, body: &str) -> Result<String> {
+    let manifest = load_manifest(&config.issues_dir)?;
+    if manifest.is_empty() {
+        return Ok(body.to_string());
+    }
+
+    // `assets/name`, or `../assets/name` from a file in a subdirectory
+    let pattern = Regex::new(&format!(r"!\[([^\]]*)\]\((?:\.\./)*{}/([^\s)]+)\)", regex::escape(ASSETS_DIR))).unwrap();
+    Ok(pattern
+        .replace_all(body, |captures: &Captures| match manifest.get(&captures[2]) {
+            Some(url) => format!("![{}]({})", &captures[1], url),
+            None => captures[0].to_string(),
+        })
+        .into_owned())
+}
+
+fn rewrite_images(body: &str, mut local_path: impl FnMut(&str) -> Option<String>) -> String {
+    let pattern = Regex::new(IMAGE_PATTERN).unwrap();
+    pattern
+        .replace_all(body, |captures: &Captures| match local_path(&captures[2]) {
+            Some(path) => format!("![{}]({})", &captures[1], path),
+            None => captures[0].to_string(),
+        })
+        .into_owned()
+}
+
+// `issue-12-<last URL segment>`, so the same attachment always maps to the same file
+fn local_file_name(number: i64, url: &str) -> String {
+    let segment = url
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(url)
+        .rsplit('/')
+        .find(|s| !s.is_empty())
+        .unwrap_or("attachment");
+    let safe: String = segment
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+        .collect();
+    format!("issue-{}-{}", number, safe)
+}
+
+async fn download(http: &reqwest::Client, url: &str, path: &Path, retries: u32) -> Result<()> {
+    let response = http.get(url).send().await?.error_for_status()?;
+    let bytes = response.bytes().await?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context(format!("Failed to create {}", parent.display()))?;
+    }
+    crate::retry_io(retries, || crate::write_atomically(path, &bytes)).context(format!("Failed to write {}", path.display()))
+}
+
+fn load_manifest(issues_dir: &Path) -> Result<Manifest> {
+    let path = issues_dir.join(ASSETS_DIR).join(MANIFEST_FILE);
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).context(format!("Failed to parse {}", path.display())),
+        Err(_) => Ok(Manifest::new()),
+    }
+}
+
+fn save_manifest(issues_dir: &Path, manifest: &Manifest, retries: u32) -> Result<()> {
+    if manifest.is_empty() {
+        return Ok(());
+    }
+
+    let dir = issues_dir.join(ASSETS_DIR);
+    fs::create_dir_all(&dir).context(format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(MANIFEST_FILE);
+    write_file_with_retry(&path, &serde_json::to_string_pretty(manifest)?, retries)
+        .context(format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inventory_lists_images_and_attached_files() {
+        let body = "![shot](https://user-images.githubusercontent.com/1/abc.png) \
+                    [log](https://github.com/user-attachments/files/7/crash.log) \
+                    [docs](https://example.com/docs) ![logo](https://example.com/logo.png) \
+                    <img width=\"200\" src=\"https://github.com/user-attachments/assets/f00\"> \
+                    ![again](https://example.com/logo.png) ![local](assets/issue-1-x.png)";
+        let mut issues: Vec<Issue> = vec![serde_json::from_value(serde_json::json!({
+            "number": 1, "title": "Issue", "body": body, "state": "open", "labels": [], "html_url": ""
+        }))
+        .unwrap()];
+
+        inventory(&mut issues);
+        assert_eq!(
+            issues[0].attachments.as_deref().unwrap(),
+            [
+                "https://user-images.githubusercontent.com/1/abc.png",
+                "https://github.com/user-attachments/files/7/crash.log",
+                "https://example.com/logo.png",
+                "https://github.com/user-attachments/assets/f00",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rewrite_images_only_touches_github_attachments() {
+        let body = "See ![screenshot](https://user-images.githubusercontent.com/1/abc.png) \
+                    and ![logo](https://example.com/logo.png)";
+
+        let rewritten = rewrite_images(body, |url| Some(format!("assets/{}", local_file_name(5, url))));
+
+        assert_eq!(
+            rewritten,
+            "See ![screenshot](assets/issue-5-abc.png) and ![logo](https://example.com/logo.png)"
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use octorust::types;
+
+use crate::patch::Filter;
+use crate::{
+    fingerprint, fingerprints_match, include, issue_from_file, issue_from_listing, issue_from_remote, parse_markdown_file, read_issue_text, read_local_file,
+    scan_issue_files, set_issue_state, Config, LocalFile,
+};
+
+/// How many changed files a push may send before `--confirm-bulk` is
+/// needed, unless `--bulk-max-files` says otherwise.
+pub const DEFAULT_MAX_FILES: usize = 50;
+
+/// The circuit breaker against bulk mistakes, such as a bad `sed` run over
+/// the issues directory: errors, listing the files, when more issue files
+/// differ from the last sync than `--bulk-max-files`, or than
+/// `--bulk-max-percent` of all issue files. `--confirm-bulk` lets them through.
+pub fn check(config: &Config) -> Result<()> {
+    if config.confirm_bulk {
+        return Ok(());
+    }
+
+    let files: Vec<LocalFile> = scan_issue_files(config)?.into_iter().filter(|file| file.number.is_some()).collect();
+    let total = files.len();
+    let changed = changed_files(config, files).into_iter().map(|(path, _)| path).collect();
+    refuse_bulk(config, changed, total)
+}
+
+/// The issue files whose content differs from the last sync, by their
+/// fingerprint, with their issue numbers, sorted by path.
+pub fn changed(config: &Config) -> Result<Vec<(PathBuf, i64)>> {
+    Ok(changed_files(config, scan_issue_files(config)?))
+}
+
+/// Like [`check`], but only counts `paths`, the files of one batch of
+/// watcher events. A refused batch then doesn't hold up later edits, and
+/// the files aren't all parsed again for every save.
+pub fn check_files(config: &Config, paths: &[PathBuf]) -> Result<()> {
+    if config.confirm_bulk {
+        return Ok(());
+    }
+
+    let total = scan_issue_files(config)?.iter().filter(|file| file.number.is_some()).count();
+    let files = paths.iter().filter_map(|path| read_local_file(config, path.clone()));
+    let changed = changed_files(config, files).into_iter().map(|(path, _)| path).collect();
+    refuse_bulk(config, changed, total)
+}
+
+fn refuse_bulk(config: &Config, changed: Vec<PathBuf>, total: usize) -> Result<()> {
+    let too_many_files = changed.len() > config.bulk_max_files;
+    let too_large_share = config.bulk_max_percent.is_some_and(|percent| changed.len() * 100 > total * percent as usize);
+    if !too_many_files && !too_large_share {
+        return Ok(());
+    }
+
+    for path in &changed {
+        eprintln!("{}: changed since the last sync", path.display());
+    }
+    let limit = if too_many_files {
+        format!("more than --bulk-max-files ({})", config.bulk_max_files)
+    } else {
+        format!("more than --bulk-max-percent ({}%)", config.bulk_max_percent.unwrap_or_default())
+    };
+    Err(anyhow::anyhow!(
+        "{} of {} issue file(s) changed since the last sync, {}; check them, then pass --confirm-bulk to push them anyway",
+        changed.len(),
+        total,
+        limit
+    ))
+}
+
+// Those of `files` a push would send, sorted. Files that can't be read or
+// parsed are left to the push to report.
+fn changed_files(config: &Config, files: impl IntoIterator<Item = LocalFile>) -> Vec<(PathBuf, i64)> {
+    let fingerprints = config.state.get().fingerprints;
+    let mut changed = Vec::new();
+    for file in files {
+        let Some(number) = file.number else { continue };
+        let Ok(content) = read_issue_text(config, &file.path) else { continue };
+        let Ok((mut frontmatter, body)) = parse_markdown_file(&content) else { continue };
+        let Ok(()) = config.state_map.apply(&mut frontmatter) else { continue };
+        let Ok(body) = include::expand(config, &file.path, &body) else { continue };
+        let Ok(issue) = issue_from_file(number, &frontmatter, body) else { continue };
+        let current = fingerprint(config, &issue);
+        if !fingerprints.get(&number).is_some_and(|stored| fingerprints_match(config, stored, &current)) {
+            changed.push((file.path, number));
+        }
+    }
+    changed.sort();
+    changed.dedup();
+    changed
+}
+
+/// What `bulk close` closes: the open issues on GitHub matching all
+/// `filters`, at most `limit` of them.
+#[derive(Debug, Default)]
+pub struct CloseOptions {
+    pub filters: Vec<Filter>,
+    pub limit: Option<usize>,
+    /// Close them; without it, only list them.
+    pub yes: bool,
+}
+
+/// Closes the matching open issues on GitHub, lowest number first, without
+/// reading or writing local files; the next pull brings the new states
+/// down. Pull requests never match. Returns the numbers of the closed issues.
+pub async fn close(config: &Config, options: &CloseOptions) -> Result<Vec<i64>> {
+    if options.filters.is_empty() {
+        return Err(anyhow::anyhow!("bulk close needs at least one --filter, so it never closes every open issue by mistake"));
+    }
+
+    let mut matching = list_open(config, &options.filters).await?;
+    let total = matching.len();
+    matching.truncate(options.limit.unwrap_or(total));
+    if matching.is_empty() {
+        println!("No open issues match the filters");
+        return Ok(Vec::new());
+    }
+    if total > matching.len() {
+        println!("{} open issue(s) match; only the first {} (--limit) are closed", total, matching.len());
+    }
+
+    if !options.yes && !config.dry_run {
+        for (number, title) in &matching {
+            println!("#{} {}", number, title);
+        }
+        return Err(anyhow::anyhow!(
+            "bulk close would close the {} issue(s) above; pass --yes to close them",
+            matching.len()
+        ));
+    }
+
+    let mut closed = Vec::new();
+    for (number, _) in matching {
+        set_issue_state(config, number, types::State::Closed).await?;
+        if !config.dry_run {
+            closed.push(number);
+        }
+    }
+    Ok(closed)
+}
+
+// The number and title of each open issue matching `filters`. Labels
+// narrow the listing on GitHub; every filter is checked here as well
+async fn list_open(config: &Config, filters: &[Filter]) -> Result<Vec<(i64, String)>> {
+    // GitHub splits the labels parameter at commas
+    let labels = filters
+        .iter()
+        .filter_map(|filter| match filter {
+            Filter::Label(label) if !label.contains(',') => Some(label.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let response = config
+        .github
+        .with_rotation(|client| {
+            let labels = &labels;
+            async move {
+                client
+                    .issues()
+                    .list_all_for_repo(
+                        &config.repo_owner,
+                        &config.repo_name,
+                        "",
+                        types::IssuesListState::Open,
+                        "",
+                        "",
+                        "",
+                        labels,
+                        types::IssuesListSort::Created,
+                        types::Order::Asc,
+                        None,
+                    )
+                    .await
+            }
+        })
+        .await
+        .context("Failed to list issues from GitHub")?;
+
+    let mut matching = Vec::new();
+    for listed in response.body.into_iter().filter(|listed| listed.pull_request.is_none()) {
+        let issue = issue_from_remote(config, issue_from_listing(listed)?);
+        if filters.iter().all(|filter| filter.matches(&issue)) {
+            matching.push((issue.number, issue.title));
+        }
+    }
+    matching.sort();
+    Ok(matching)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use crate::{push_all_files, sync_github_to_local};
+    use std::fs;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_bulk_changes_need_confirmation_before_pushing() {
+        let server = MockServer::start().await;
+        let issues: Vec<serde_json::Value> =
+            (1..=4).map(|n| crate::tests::issue_json(n, "Issue", "open", &[], "Body")).collect();
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issues))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        sync_github_to_local(&config, None).await.unwrap();
+        // A bad search-and-replace over every file
+        for n in 1..=3 {
+            let path = dir.path().join(format!("issue-{}.md", n));
+            fs::write(&path, fs::read_to_string(&path).unwrap().replace("Body", "Mangled")).unwrap();
+        }
+
+        config.bulk_max_files = 2;
+        let error = push_all_files(&config).await.unwrap_err();
+        assert!(error.to_string().starts_with("3 of 4 issue file(s) changed since the last sync, more than --bulk-max-files (2)"));
+        assert!(server.received_requests().await.unwrap().iter().all(|request| request.method.as_str() == "GET"));
+
+        config.bulk_max_files = DEFAULT_MAX_FILES;
+        config.bulk_max_percent = Some(50);
+        assert!(check(&config).unwrap_err().to_string().contains("more than --bulk-max-percent (50%)"));
+
+        config.confirm_bulk = true;
+        assert!(check(&config).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bulk_close_needs_yes_and_respects_the_limit() {
+        let server = MockServer::start().await;
+        let mut pull_request = crate::tests::issue_json(4, "Stale PR", "open", &["stale"], "Body");
+        pull_request["pull_request"] = serde_json::json!({ "url": "https://api.github.com/repos/owner/repo/pulls/4" });
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("state", "open"))
+            .and(query_param("labels", "stale"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                crate::tests::issue_json(3, "Old idea", "open", &["stale"], "Body"),
+                crate::tests::issue_json(1, "Ancient bug", "open", &["stale", "bug"], "Body"),
+                crate::tests::issue_json(2, "Question", "open", &["stale", "question"], "Body"),
+                pull_request,
+            ])))
+            .mount(&server)
+            .await;
+        for (number, expected) in [(1, 1), (2, 1), (3, 0), (4, 0)] {
+            Mock::given(method("PATCH"))
+                .and(path(format!("/repos/owner/repo/issues/{}", number)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(crate::tests::issue_json(number, "Closed", "closed", &[], "Body")))
+                .expect(expected)
+                .mount(&server)
+                .await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        let mut options = CloseOptions {
+            filters: vec![Filter::parse("label=stale").unwrap(), Filter::parse("state=open").unwrap()],
+            limit: None,
+            yes: false,
+        };
+        let error = close(&config, &options).await.unwrap_err();
+        assert_eq!(error.to_string(), "bulk close would close the 3 issue(s) above; pass --yes to close them");
+
+        config.dry_run = true;
+        assert!(close(&config, &options).await.unwrap().is_empty());
+
+        // Lowest numbers first, and never the pull request
+        config.dry_run = false;
+        options.yes = true;
+        options.limit = Some(2);
+        assert_eq!(close(&config, &options).await.unwrap(), vec![1, 2]);
+
+        assert!(close(&config, &CloseOptions { filters: Vec::new(), ..options }).await.is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::SyncError;
+use crate::{push_local_file, write_atomically, Config, Issue};
+
+/// The issues of the last successful pull, kept in the issues directory for
+/// `--offline-fallback`.
+pub const CACHE_FILE: &str = ".retasks-cache.json";
+
+#[derive(Serialize, Deserialize, Default)]
+struct Cache {
+    pulled_at: Option<DateTime<Utc>>,
+    issues: BTreeMap<i64, Issue>,
+}
+
+/// Whether `error` means GitHub couldn't be reached (no connection, a
+/// timeout, or GitHub itself down), as opposed to GitHub rejecting the
+/// request.
+pub fn is_unreachable(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.downcast_ref::<SyncError>().is_some_and(SyncError::is_unreachable))
+}
+
+/// Records what a pull fetched. A `complete` pull replaces the cache;
+/// a partial one (`--only`, a catch-up pull) is merged into it.
+pub fn save(config: &Config, issues: &[Issue], complete: bool) -> Result<()> {
+    let mut cache = if complete { Cache::default() } else { read(config)?.unwrap_or_default() };
+    cache.pulled_at = Some(Utc::now());
+    cache.issues.extend(issues.iter().map(|issue| (issue.number, issue.clone())));
+
+    let path = config.issues_dir.join(CACHE_FILE);
+    write_atomically(&path, serde_json::to_string(&cache)?.as_bytes()).context(format!("Failed to write {}", path.display()))
+}
+
+/// The cached issues and when they were pulled, or None before the first pull.
+pub fn load(config: &Config) -> Result<Option<(DateTime<Utc>, Vec<Issue>)>> {
+    Ok(read(config)?.and_then(|cache| Some((cache.pulled_at?, cache.issues.into_values().collect()))))
+}
+
+fn read(config: &Config) -> Result<Option<Cache>> {
+    let path = config.issues_dir.join(CACHE_FILE);
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(Some(serde_json::from_str(&content).context(format!("Failed to parse {}", path.display()))?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Retries the pushes queued while GitHub was unreachable. Returns the
+/// numbers of the issues pushed; a push that still can't reach GitHub
+/// stays queued.
+pub async fn push_queued(config: &Config) -> Vec<i64> {
+    let queued = config.state.get().queued_pushes;
+    if queued.is_empty() {
+        return Vec::new();
+    }
+
+    println!("Retrying {} push(es) queued while GitHub was unreachable...", queued.len());
+    let mut pushed = Vec::new();
+    for (number, path) in queued {
+        // push_local_file queues it again if GitHub is still unreachable
+        if let Err(e) = config.state.update(|state| {
+            state.queued_pushes.remove(&number);
+        }) {
+            eprintln!("{}", config.colors.error(&format!("{:#}", e)));
+            continue;
+        }
+        match push_local_file(config, &path, None).await {
+            Ok(Some(number)) => pushed.push(number),
+            Ok(None) => {}
+            Err(e) => eprintln!("{}", config.colors.error(&format!("Error syncing to GitHub: {:#}", e))),
+        }
+    }
+    pushed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use crate::{sync_github_to_local, sync_local_to_github};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_unreachable_github_falls_back_to_cache_and_queues_pushes() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Issue", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.offline_fallback = true;
+        sync_github_to_local(&config, None).await.unwrap();
+        let last_pull_at = config.state.get().last_pull_at;
+
+        // GitHub goes away; the deleted file comes back from the cache
+        let closed_port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let unreachable = format!("http://127.0.0.1:{}", closed_port);
+        config.github = crate::github::GitHub::new(vec!["test-token".to_string()], Some(&unreachable), Default::default()).unwrap();
+        config.github.set_server_error_retries(0);
+        let file_path = dir.path().join("issue-1.md");
+        let content = fs::read_to_string(&file_path).unwrap();
+        fs::remove_file(&file_path).unwrap();
+        sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), content);
+        assert_eq!(config.state.get().last_pull_at, last_pull_at);
+
+        // An edit is queued rather than failing
+        fs::write(&file_path, content.replace("Body", "Edited offline")).unwrap();
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), None);
+        assert_eq!(config.state.get().queued_pushes.get(&1), Some(&file_path));
+
+        // Once GitHub is back, the queued push goes through
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Issue", "open", &[], "Edited offline")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut config = test_config(&server, dir.path());
+        config.offline_fallback = true;
+        assert_eq!(push_queued(&config).await, vec![1]);
+        assert!(config.state.get().queued_pushes.is_empty());
+    }
+}
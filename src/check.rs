@@ -0,0 +1,124 @@
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+
+use crate::Config;
+
+/// Verifies each token and the repository with a few cheap requests, and
+/// reports what a sync would run into: who each token authenticates as,
+/// how much of its rate limit is left, and whether it can push to the
+/// repository. Fails if any token or the repository isn't usable.
+pub async fn check(config: &Config) -> Result<()> {
+    let mut problems = Vec::new();
+
+    for (index, client) in config.github.clients().into_iter().enumerate() {
+        let label = format!("Token {}", index + 1);
+        let login = match client.users().get_authenticated_public_user().await {
+            Ok(response) => response.body.login,
+            Err(e) => {
+                eprintln!("{}", config.colors.error(&format!("{}: not accepted by GitHub: {}", label, e)));
+                problems.push(format!("{} is invalid", label.to_lowercase()));
+                continue;
+            }
+        };
+
+        match client.rate_limit().get().await {
+            Ok(response) => {
+                let core = response.body.resources.core;
+                let reset = Utc.timestamp_opt(core.reset, 0).single().map(|at| at.to_rfc3339()).unwrap_or_default();
+                let message = format!(
+                    "{}: authenticated as {}, {} of {} requests left (resets at {})",
+                    label, login, core.remaining, core.limit, reset
+                );
+                if core.remaining == 0 {
+                    eprintln!("{}", config.colors.warning(&message));
+                } else {
+                    println!("{}", config.colors.success(&message));
+                }
+            }
+            Err(e) => println!("{}: authenticated as {} (rate limit unknown: {})", label, login, e),
+        }
+    }
+
+    let repo = config
+        .github
+        .with_rotation(|client| async move { client.repos().get(&config.repo_owner, &config.repo_name).await })
+        .await;
+    let full_name = format!("{}/{}", config.repo_owner, config.repo_name);
+    match repo {
+        Ok(response) => {
+            let repo = response.body;
+            if !repo.has_issues {
+                eprintln!("{}", config.colors.error(&format!("Repository {}: issues are disabled", full_name)));
+                problems.push("issues are disabled".to_string());
+            } else if repo.permissions.is_some_and(|permissions| !permissions.push) {
+                eprintln!(
+                    "{}",
+                    config.colors.warning(&format!("Repository {}: read-only access; pulls work but pushes will fail", full_name))
+                );
+            } else {
+                println!("{}", config.colors.success(&format!("Repository {}: accessible", full_name)));
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", config.colors.error(&format!("Repository {}: not accessible: {}", full_name, e)));
+            problems.push("the repository is not accessible".to_string());
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(anyhow::anyhow!("Check failed: {}", problems.join(", ")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_check_passes_then_reports_a_rejected_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "login": "octocat" })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rate_limit"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "rate": { "limit": 5000, "remaining": 4999, "reset": 1700000000, "used": 1 },
+                "resources": {
+                    "core": { "limit": 5000, "remaining": 4999, "reset": 1700000000, "used": 1 },
+                    "search": { "limit": 30, "remaining": 30, "reset": 1700000000, "used": 0 }
+                }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "name": "repo",
+                "has_issues": true,
+                "permissions": { "admin": false, "pull": true, "push": true }
+            })))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        check(&config).await.unwrap();
+
+        // A rejected token fails the check
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({ "message": "Bad credentials" })))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        let error = check(&config).await.unwrap_err().to_string();
+        assert!(error.contains("token 1 is invalid"), "{}", error);
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use octorust::types;
+use reqwest::header::{HeaderMap, LINK};
+use serde::{Deserialize, Serialize};
+
+use crate::{graphql, write_atomically, Config};
+
+/// Where the pages of a pull in progress are kept, in the issues directory.
+/// Hidden, so scans and the watcher leave it alone.
+pub const CHECKPOINT_DIR: &str = ".retasks-checkpoint";
+/// Issues per page, GitHub's maximum.
+const PAGE_SIZE: i64 = 100;
+
+/// How old an interrupted pull may be and still be resumed. Older pages
+/// are likely stale enough that fetching them again is the safer choice.
+fn max_age() -> Duration {
+    Duration::hours(1)
+}
+
+#[derive(Serialize, Deserialize)]
+struct Progress {
+    /// What was being listed; a pull listing something else starts over.
+    key: String,
+    started_at: DateTime<Utc>,
+    /// Pages saved so far, numbered from 1.
+    pages: i64,
+    /// Whether the last page has been saved.
+    complete: bool,
+    /// How many pages there are, from the first page's `Link` header.
+    #[serde(default)]
+    last_page: Option<i64>,
+    /// With `--api graphql`, where the next page starts.
+    #[serde(default)]
+    cursor: Option<String>,
+}
+
+/// One page of the listing as GitHub returned it, and whether there are
+/// more: from the `Link` header over REST, or the cursor over GraphQL.
+struct Page {
+    issues: Vec<serde_json::Value>,
+    has_next: bool,
+    last_page: Option<i64>,
+    cursor: Option<String>,
+}
+
+/// Lists every issue of the repository page by page, over REST or with
+/// `--api graphql`, saving each page as it arrives. If a pull with the same
+/// `since` was interrupted within the last hour, its saved pages are reused
+/// and listing carries on after them.
+///
+/// Returns the issues, each as GitHub listed it by number (the REST JSON,
+/// which has fields octorust drops such as `state_reason`, or the GraphQL
+/// node), and when the listing started. For a resumed pull that is when the
+/// interrupted one started, so the sync cursor doesn't skip updates made in
+/// between.
+pub async fn fetch_all(
+    config: &Config,
+    since: Option<DateTime<Utc>>,
+) -> Result<(Vec<types::Issue>, HashMap<i64, serde_json::Value>, DateTime<Utc>)> {
+    let key = key(config, since);
+    // Issues move between pages as they are updated or commented on, so only
+    // a listing by creation date can carry on where it left off
+    let resumable = matches!(config.sort, None | Some(types::IssuesListSort::Created));
+    let mut progress = match read_progress(config) {
+        Some(progress) if resumable && progress.key == key && Utc::now() - progress.started_at < max_age() => progress,
+        _ => {
+            if !config.dry_run {
+                clear(config)?;
+            }
+            Progress { key, started_at: Utc::now(), pages: 0, complete: false, last_page: None, cursor: None }
+        }
+    };
+
+    let mut issues = Vec::new();
+    for page in 1..=progress.pages {
+        issues.extend(read_page(config, page)?);
+    }
+    if progress.pages > 0 {
+        println!("Resuming the pull interrupted at {}, after page {} ({} issues)", progress.started_at, progress.pages, issues.len());
+    }
+
+    // Listing goes on for as long as GitHub links a next page, which unlike
+    // counting issues per page holds however GitHub sizes its pages
+    while !progress.complete {
+        let page = progress.pages + 1;
+        let batch = match config.api {
+            graphql::Api::Rest => fetch_page(config, since, page).await?,
+            graphql::Api::Graphql => {
+                let (issues, cursor) = graphql::fetch_page(config, since, progress.cursor.take()).await?;
+                Page { issues, has_next: cursor.is_some(), last_page: None, cursor }
+            }
+        };
+        progress.complete = !batch.has_next;
+        progress.pages = page;
+        progress.last_page = progress.last_page.or(batch.last_page);
+        progress.cursor = batch.cursor;
+        if !config.dry_run {
+            save_page(config, page, &batch.issues, &progress)?;
+        }
+        issues.extend(batch.issues);
+        if let Some(last_page) = progress.last_page.filter(|&last_page| last_page > 1) {
+            println!("Listed page {} of {} ({} issues so far)", page, last_page, issues.len());
+        }
+    }
+
+    let fetched = match config.api {
+        graphql::Api::Rest => issues
+            .iter()
+            .map(|issue| serde_json::from_value(issue.clone()).context("Failed to read an issue from GitHub's response"))
+            .collect::<Result<Vec<types::Issue>>>()?,
+        graphql::Api::Graphql => graphql::issues_from_nodes(config, &issues).await?,
+    };
+    let listed = fetched.iter().map(|issue| issue.number).zip(issues).collect();
+    Ok((fetched, listed, progress.started_at))
+}
+
+/// Removes the saved pages, once the pull they belong to has been written.
+pub fn clear(config: &Config) -> Result<()> {
+    let dir = dir(config);
+    match fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).context(format!("Failed to remove {}", dir.display())),
+        _ => Ok(()),
+    }
+}
+
+// Oldest first unless --sort or --order say otherwise, so issues created
+// while a pull is interrupted land on later pages instead of shifting the
+// ones already saved
+async fn fetch_page(config: &Config, since: Option<DateTime<Utc>>, page: i64) -> Result<Page> {
+    // The same filters as fetch_issues
+    let mut query = vec![("state", "all".to_string())];
+    for (key, value) in [("creator", &config.creator), ("mentioned", &config.mentioned)] {
+        if let Some(value) = value.as_ref().filter(|value| !value.is_empty()) {
+            query.push((key, value.clone()));
+        }
+    }
+    query.push(("sort", config.sort.clone().unwrap_or(types::IssuesListSort::Created).to_string()));
+    query.push(("direction", config.order.clone().unwrap_or(types::Order::Asc).to_string()));
+    if let Some(since) = since {
+        query.push(("since", since.to_rfc3339_opts(SecondsFormat::Secs, true)));
+    }
+    query.push(("per_page", PAGE_SIZE.to_string()));
+    query.push(("page", page.to_string()));
+
+    let (listed, headers) = config
+        .github
+        .list_issues_page(&config.repo_owner, &config.repo_name, &query)
+        .await
+        .context(format!("Failed to list issues from GitHub (page {})", page))?;
+
+    let (has_next, last_page) = parse_links(&headers);
+    // GitHub lists pull requests as issues too
+    let issues = listed.into_iter().filter(|listed| listed["pull_request"].is_null()).collect();
+    Ok(Page { issues, has_next, last_page, cursor: None })
+}
+
+// Reads a header like `<https://...&page=2>; rel="next", <https://...&page=5>; rel="last"`
+// into whether there is a next page and the number of the last one. The
+// last page has no `last` link, since it is the last.
+fn parse_links(headers: &HeaderMap) -> (bool, Option<i64>) {
+    let Some(header) = headers.get(LINK).and_then(|value| value.to_str().ok()) else {
+        return (false, None);
+    };
+
+    let mut has_next = false;
+    let mut last_page = None;
+    for link in header.split(',') {
+        let Some((url, params)) = link.trim().strip_prefix('<').and_then(|link| link.split_once('>')) else {
+            continue;
+        };
+        let rels: Vec<&str> = params
+            .split(';')
+            .filter_map(|param| param.trim().strip_prefix("rel="))
+            .flat_map(|rel| rel.trim_matches('"').split_whitespace())
+            .collect();
+        if rels.contains(&"next") {
+            has_next = true;
+        }
+        if rels.contains(&"last") {
+            last_page = reqwest::Url::parse(url)
+                .ok()
+                .and_then(|url| url.query_pairs().find(|(key, _)| key == "page").and_then(|(_, page)| page.parse().ok()));
+        }
+    }
+    (has_next, last_page)
+}
+
+fn key(config: &Config, since: Option<DateTime<Utc>>) -> String {
+    let since = since.map(|since| since.to_rfc3339_opts(SecondsFormat::Secs, true)).unwrap_or_else(|| "all".to_string());
+    let key = format!(
+        "{}/{} since={} creator={} mentioned={} order={}",
+        config.repo_owner,
+        config.repo_name,
+        since,
+        config.creator.as_deref().unwrap_or_default(),
+        config.mentioned.as_deref().unwrap_or_default(),
+        config.order.as_ref().map(ToString::to_string).unwrap_or_default()
+    );
+    // Pages of the two APIs don't mix; REST keys stay as they were
+    match config.api {
+        graphql::Api::Rest => key,
+        graphql::Api::Graphql => format!("{} api=graphql", key),
+    }
+}
+
+fn dir(config: &Config) -> PathBuf {
+    config.issues_dir.join(CHECKPOINT_DIR)
+}
+
+// An unreadable checkpoint only costs a fresh start
+fn read_progress(config: &Config) -> Option<Progress> {
+    let content = fs::read_to_string(dir(config).join("progress.json")).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn read_page(config: &Config, page: i64) -> Result<Vec<serde_json::Value>> {
+    let path = dir(config).join(format!("page-{}.json", page));
+    let content = fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).context(format!("Failed to parse {}", path.display()))
+}
+
+// The page goes first, so the progress never counts a page that isn't there
+fn save_page(config: &Config, page: i64, issues: &[serde_json::Value], progress: &Progress) -> Result<()> {
+    let dir = dir(config);
+    fs::create_dir_all(&dir).context(format!("Failed to create {}", dir.display()))?;
+    let page_path = dir.join(format!("page-{}.json", page));
+    write_atomically(&page_path, serde_json::to_string(issues)?.as_bytes()).context(format!("Failed to write {}", page_path.display()))?;
+    let progress_path = dir.join("progress.json");
+    write_atomically(&progress_path, serde_json::to_string(progress)?.as_bytes())
+        .context(format!("Failed to write {}", progress_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn links(server: &MockServer, next: i64, last: i64) -> String {
+        let url = |page| format!("<{}/repos/owner/repo/issues?state=all&per_page={}&page={}>", server.uri(), PAGE_SIZE, page);
+        format!("{}; rel=\"next\", {}; rel=\"last\"", url(next), url(last))
+    }
+
+    #[tokio::test]
+    async fn test_sort_and_order_set_the_listing_and_writing_order() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("sort", "updated"))
+            .and(query_param("direction", "desc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(5, "Updated last", "open", &[], "Body"),
+                issue_json(2, "Updated before", "open", &[], "Body"),
+                issue_json(9, "Updated first", "open", &[], "Body"),
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.sort = Some(types::IssuesListSort::Updated);
+        config.order = Some(types::Order::Desc);
+        let summary = crate::sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![5, 2, 9]);
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_pull_resumes_after_the_saved_pages() {
+        let server = MockServer::start().await;
+        let first_page: Vec<serde_json::Value> = (1..=PAGE_SIZE).map(|n| issue_json(n, "Issue", "open", &[], "Body")).collect();
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("page", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(first_page).insert_header("Link", links(&server, 2, 2)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({ "message": "Interrupted" })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(PAGE_SIZE + 1, "Last", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        assert!(fetch_all(&config, None).await.is_err());
+        let started_at = read_progress(&config).unwrap().started_at;
+
+        let (issues, _, resumed_started_at) = fetch_all(&config, None).await.unwrap();
+        assert_eq!(issues.len() as i64, PAGE_SIZE + 1);
+        assert_eq!(resumed_started_at, started_at);
+
+        clear(&config).unwrap();
+        assert!(!dir.path().join(CHECKPOINT_DIR).exists());
+    }
+
+    #[tokio::test]
+    async fn test_pages_are_followed_while_link_header_has_next() {
+        let server = MockServer::start().await;
+        // Short pages, as with filters, don't end the listing while there is a next link
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("page", "1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!([issue_json(1, "First", "open", &[], "Body")]))
+                    .insert_header("Link", links(&server, 2, 2)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(2, "Second", "open", &[], "Body"),
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        let (issues, _, _) = fetch_all(&config, None).await.unwrap();
+        assert_eq!(issues.iter().map(|issue| issue.number).collect::<Vec<_>>(), vec![1, 2]);
+
+        let progress = read_progress(&config).unwrap();
+        assert_eq!((progress.pages, progress.last_page, progress.complete), (2, Some(2), true));
+    }
+}
@@ -0,0 +1,73 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// Value of the `--color` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(anyhow::anyhow!("Invalid --color value: {} (expected auto, always or never)", value)),
+        }
+    }
+}
+
+/// Resolved color settings for stdout and stderr.
+///
+/// Under `auto`, a stream is colored only when it is a terminal and `NO_COLOR`
+/// is unset, so output piped to files or CI logs stays free of ANSI codes.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Colors {
+    stdout: bool,
+    stderr: bool,
+}
+
+impl Colors {
+    pub fn new(choice: ColorChoice) -> Self {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let auto = |is_tty: bool| is_tty && !no_color;
+
+        match choice {
+            ColorChoice::Always => Colors { stdout: true, stderr: true },
+            ColorChoice::Never => Colors { stdout: false, stderr: false },
+            ColorChoice::Auto => Colors {
+                stdout: auto(io::stdout().is_terminal()),
+                stderr: auto(io::stderr().is_terminal()),
+            },
+        }
+    }
+
+    /// Green, for stdout.
+    pub fn success(&self, text: &str) -> String {
+        paint(self.stdout, "32", text)
+    }
+
+    /// Yellow, for stderr.
+    pub fn warning(&self, text: &str) -> String {
+        paint(self.stderr, "33", text)
+    }
+
+    /// Red, for stderr.
+    pub fn error(&self, text: &str) -> String {
+        paint(self.stderr, "31", text)
+    }
+}
+
+fn paint(enabled: bool, code: &str, text: &str) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::{fetch_issues, in_number_range, issue_from_remote, lists_every_issue, prune_local_files, scan_issue_files, write_issue_files, Config, Issue};
+
+/// Everything `doctor` found wrong with the issues directory.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Files whose issue doesn't exist on GitHub.
+    pub orphaned: Vec<(i64, PathBuf)>,
+    /// Issue numbers claimed by more than one file.
+    pub duplicates: BTreeMap<i64, Vec<PathBuf>>,
+    /// Markdown files without a `number` key.
+    pub unnumbered: Vec<PathBuf>,
+    /// Issues on GitHub without a local file.
+    pub missing: Vec<Issue>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.orphaned.is_empty() && self.duplicates.is_empty() && self.unnumbered.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Cross-checks every local file against the full list of issues on
+/// GitHub. Nothing is changed unless `fix` is set, in which case orphaned
+/// files are pruned and missing issues are pulled. Duplicates and files
+/// without a number need a human to decide, so they are only reported.
+pub async fn doctor(config: &Config, fix: bool) -> Result<Report> {
+    if config.only.is_some() || !lists_every_issue(config) || config.format.data_file_name().is_some() {
+        return Err(anyhow::anyhow!(
+            "doctor checks every issue file, so it can't be combined with --only, --creator, --mentioned or --format json/yaml"
+        ));
+    }
+
+    let remote: BTreeMap<i64, Issue> = fetch_issues(config, None)
+        .await?
+        .into_iter()
+        .map(|issue| (issue.number, issue_from_remote(config, issue)))
+        .collect();
+
+    let mut report = Report::default();
+    let mut local: BTreeMap<i64, Vec<PathBuf>> = BTreeMap::new();
+    for file in scan_issue_files(config)? {
+        match file.number {
+            Some(number) => local.entry(number).or_default().push(file.path),
+            None => report.unnumbered.push(file.path),
+        }
+    }
+
+    for (number, paths) in &local {
+        if !remote.contains_key(number) {
+            report.orphaned.extend(paths.iter().map(|path| (*number, path.clone())));
+        }
+        if paths.len() > 1 {
+            report.duplicates.insert(*number, paths.clone());
+        }
+    }
+    // Issues outside --number-min and --number-max aren't pulled, so they aren't missing
+    report.missing = remote
+        .values()
+        .filter(|issue| !local.contains_key(&issue.number) && in_number_range(config, issue.number))
+        .cloned()
+        .collect();
+
+    print_report(config, &report, fix);
+
+    if fix && !report.is_clean() {
+        if !report.orphaned.is_empty() {
+            let remote_numbers: HashSet<i64> = remote.keys().copied().collect();
+            prune_local_files(config, &remote_numbers)?;
+        }
+        if !report.missing.is_empty() {
+            let (_, failures) = write_issue_files(config, &report.missing, None)?;
+            if !failures.is_empty() {
+                return Err(anyhow::anyhow!("Failed to write {} missing issue(s): {:?}", failures.len(), failures));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn print_report(config: &Config, report: &Report, fix: bool) {
+    if report.is_clean() {
+        println!("{}", config.colors.success("No problems found"));
+        return;
+    }
+
+    for (number, path) in &report.orphaned {
+        eprintln!("{}", config.colors.warning(&format!("Issue #{} does not exist on GitHub: {}", number, path.display())));
+    }
+    for (number, paths) in &report.duplicates {
+        let paths: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+        eprintln!("{}", config.colors.warning(&format!("Issue #{} is in {} files: {}", number, paths.len(), paths.join(", "))));
+    }
+    for path in &report.unnumbered {
+        eprintln!("{}", config.colors.warning(&format!("No `number` key in {}", path.display())));
+    }
+    for issue in &report.missing {
+        eprintln!("{}", config.colors.warning(&format!("Issue #{} has no local file", issue.number)));
+    }
+
+    if !fix {
+        if !report.orphaned.is_empty() {
+            println!("Run `doctor --fix` or pull with --prune to delete the files of issues that no longer exist.");
+        }
+        if !report.missing.is_empty() {
+            println!("Run `doctor --fix` or a pull with --full-resync to write the missing issues.");
+        }
+    }
+    if !report.duplicates.is_empty() {
+        println!("Delete or renumber the duplicate files by hand; doctor does not guess which one is right.");
+    }
+    if !report.unnumbered.is_empty() {
+        println!("Files without a `number` key are not synced; move them out of the issues directory if they aren't issues.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use std::fs;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_doctor_reports_and_fixes() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Kept", "open", &[], "Body"),
+                issue_json(2, "Missing", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("issue-1.md"), "---\nnumber: 1\n---\n\nBody").unwrap();
+        fs::write(dir.path().join("copy-of-1.md"), "---\nnumber: 1\n---\n\nBody").unwrap();
+        fs::write(dir.path().join("issue-5.md"), "---\nnumber: 5\n---\n\nGone").unwrap();
+        fs::write(dir.path().join("notes.md"), "# Notes").unwrap();
+        let mut config = test_config(&server, dir.path());
+
+        // Out of range, #2 is not expected locally
+        config.number_max = Some(1);
+        assert!(doctor(&config, false).await.unwrap().missing.is_empty());
+        config.number_max = None;
+
+        let report = doctor(&config, false).await.unwrap();
+        assert_eq!(report.orphaned.iter().map(|(n, _)| *n).collect::<Vec<_>>(), vec![5]);
+        assert_eq!(report.duplicates.keys().copied().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(report.unnumbered, vec![dir.path().join("notes.md")]);
+        assert_eq!(report.missing.iter().map(|i| i.number).collect::<Vec<_>>(), vec![2]);
+        assert!(dir.path().join("issue-5.md").exists());
+        assert!(!dir.path().join("issue-2.md").exists());
+
+        doctor(&config, true).await.unwrap();
+        assert!(!dir.path().join("issue-5.md").exists());
+        assert!(dir.path().join("issue-2.md").exists());
+        assert!(dir.path().join("copy-of-1.md").exists());
+        assert!(dir.path().join("notes.md").exists());
+    }
+}
@@ -0,0 +1,115 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use octorust::ClientError;
+
+/// How long to wait when GitHub says it is rate limiting without saying
+/// for how long, e.g. after the secondary rate limit retries run out.
+const DEFAULT_RATE_LIMIT_WAIT: u64 = 60;
+
+/// The kinds of failure a caller may want to branch on. Errors still travel
+/// as `anyhow::Error` with context added along the way; a `SyncError` in
+/// the chain is found with `error.chain().find_map(|e| e.downcast_ref())`
+/// or `anyhow::Error::downcast_ref` when it is the root cause.
+#[derive(Debug, thiserror::Error)]
+pub enum SyncError {
+    /// The token was rejected or lacks access (HTTP 401 or 403).
+    #[error("GitHub rejected the token (HTTP {status}): {message}")]
+    Auth { status: u16, message: String },
+    /// Every token is rate limited.
+    #[error("GitHub rate limit exceeded; retry in {retry_after_secs} seconds")]
+    RateLimited { retry_after_secs: u64 },
+    /// Any other error response from GitHub.
+    #[error("GitHub returned HTTP {status}: {message}")]
+    Http { status: u16, message: String },
+    /// GitHub couldn't be reached: no connection, or a timeout.
+    #[error("Could not reach GitHub: {message}")]
+    Network { message: String },
+    /// A response from GitHub that couldn't be read.
+    #[error("Unexpected response from GitHub: {message}")]
+    InvalidResponse { message: String },
+    /// A local issue file that can't be read as one.
+    #[error("Invalid frontmatter in {}: {message}", path.display())]
+    Parse { path: PathBuf, message: String },
+    /// A local file that couldn't be read or written.
+    #[error("Failed to access {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl SyncError {
+    pub fn parse(path: &Path, error: anyhow::Error) -> Self {
+        SyncError::Parse { path: path.to_path_buf(), message: format!("{:#}", error) }
+    }
+
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        SyncError::Io { path: path.to_path_buf(), source }
+    }
+
+    /// The error for an unsuccessful HTTP response.
+    pub fn from_status(status: u16, message: String) -> Self {
+        match status {
+            401 | 403 if !message.to_lowercase().contains("rate limit") => SyncError::Auth { status, message },
+            403 | 429 => SyncError::RateLimited { retry_after_secs: DEFAULT_RATE_LIMIT_WAIT },
+            _ => SyncError::Http { status, message },
+        }
+    }
+
+    /// Whether GitHub itself couldn't be reached, as opposed to GitHub
+    /// rejecting the request. Gateway errors count, since they mean GitHub
+    /// is down rather than that the request was wrong.
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            SyncError::Network { .. } => true,
+            SyncError::Http { status, .. } => matches!(status, 502..=504),
+            _ => false,
+        }
+    }
+}
+
+impl From<ClientError> for SyncError {
+    fn from(error: ClientError) -> Self {
+        match error {
+            ClientError::RateLimited { duration } => SyncError::RateLimited { retry_after_secs: duration },
+            ClientError::HttpError { status, error, .. } => SyncError::from_status(status.as_u16(), error),
+            ClientError::ReqwestError(error) => SyncError::from(error),
+            // What's left after the retry middleware gave up
+            error @ ClientError::ReqwestMiddleWareError(_) => {
+                SyncError::Network { message: format!("{:#}", anyhow::Error::from(error)) }
+            }
+            error => SyncError::InvalidResponse { message: error.to_string() },
+        }
+    }
+}
+
+impl From<reqwest::Error> for SyncError {
+    fn from(error: reqwest::Error) -> Self {
+        if let Some(status) = error.status() {
+            SyncError::from_status(status.as_u16(), error.to_string())
+        } else if error.is_decode() || error.is_body() {
+            SyncError::InvalidResponse { message: error.to_string() }
+        } else {
+            SyncError::Network { message: format!("{:#}", anyhow::Error::from(error)) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_statuses_map_to_error_kinds() {
+        assert!(matches!(SyncError::from_status(401, "Bad credentials".to_string()), SyncError::Auth { status: 401, .. }));
+        assert!(matches!(
+            SyncError::from_status(403, "API rate limit exceeded".to_string()),
+            SyncError::RateLimited { .. }
+        ));
+        assert!(matches!(SyncError::from_status(404, "Not Found".to_string()), SyncError::Http { status: 404, .. }));
+        assert!(SyncError::from_status(503, String::new()).is_unreachable());
+        assert!(!SyncError::from_status(500, String::new()).is_unreachable());
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::{attachments, fetch_issues, issue_from_remote, render_issue_file, source, Config, Issue};
+
+/// Which issues go into an export, and what else is bundled with them.
+pub struct ExportOptions {
+    pub output: PathBuf,
+    /// `open`, `closed`, or None for both.
+    pub state: Option<String>,
+    /// Only issues carrying all of these labels.
+    pub labels: Vec<String>,
+    pub include_assets: bool,
+}
+
+/// Pulls the matching issues into a temporary directory and zips it, as a
+/// one-shot snapshot that doesn't touch the issues directory or the sync state.
+pub async fn export(config: &Config, options: &ExportOptions) -> Result<usize> {
+    let fetched_at = chrono::Utc::now();
+    let mut issues: Vec<Issue> = fetch_issues(config, None)
+        .await?
+        .into_iter()
+        .map(|issue| issue_from_remote(config, issue))
+        .filter(|issue| matches(issue, options))
+        .collect();
+
+    let staging = std::env::temp_dir().join(format!(
+        "retasks-export-{}-{}",
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    ));
+    fs::create_dir_all(&staging).context(format!("Failed to create {}", staging.display()))?;
+    let result = stage_and_zip(config, options, &staging, &mut issues, fetched_at).await;
+    let _ = fs::remove_dir_all(&staging);
+    result?;
+
+    println!("Exported {} issue(s) to {}", issues.len(), options.output.display());
+    Ok(issues.len())
+}
+
+fn matches(issue: &Issue, options: &ExportOptions) -> bool {
+    options.state.as_ref().is_none_or(|state| issue.state.eq_ignore_ascii_case(state))
+        && options.labels.iter().all(|label| issue.labels.contains(label))
+}
+
+async fn stage_and_zip(
+    config: &Config,
+    options: &ExportOptions,
+    staging: &Path,
+    issues: &mut [Issue],
+    fetched_at: chrono::DateTime<chrono::Utc>,
+) -> Result<()> {
+    if options.include_assets {
+        attachments::localize_into(config, staging, issues).await?;
+    }
+
+    for issue in issues.iter() {
+        let path = staging.join(format!("issue-{}.md", issue.number));
+        fs::write(&path, render_issue_file(issue)).context(format!("Failed to write {}", path.display()))?;
+    }
+    // With the export's own filters, which a pull doesn't apply
+    let mut description = source::describe(config, fetched_at);
+    description["export"] = serde_json::json!({ "state": options.state, "labels": options.labels });
+    source::write(staging, &description)?;
+
+    let file = File::create(&options.output).context(format!("Failed to create {}", options.output.display()))?;
+    let mut zip = ZipWriter::new(file);
+    add_dir(&mut zip, staging, staging)?;
+    zip.finish().context(format!("Failed to write {}", options.output.display()))?;
+    Ok(())
+}
+
+// Adds every file under `dir` with its path relative to `root`, in sorted order
+fn add_dir(zip: &mut ZipWriter<File>, root: &Path, dir: &Path) -> Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .context(format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            add_dir(zip, root, &path)?;
+            continue;
+        }
+        let name = path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/");
+        zip.start_file(name, SimpleFileOptions::default())?;
+        zip.write_all(&fs::read(&path).context(format!("Failed to read {}", path.display()))?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use std::io::Read;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_export_zips_only_matching_issues() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Open bug", "open", &["bug"], "One"),
+                issue_json(2, "Closed bug", "closed", &["bug"], "Two"),
+                issue_json(3, "Open feature", "open", &["feature"], "Three"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, &dir.path().join("issues"));
+        let options = ExportOptions {
+            output: dir.path().join("issues.zip"),
+            state: Some("open".to_string()),
+            labels: vec!["bug".to_string()],
+            include_assets: false,
+        };
+        assert_eq!(export(&config, &options).await.unwrap(), 1);
+
+        let mut archive = zip::ZipArchive::new(File::open(&options.output).unwrap()).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut content = String::new();
+        archive.by_name("issue-1.md").unwrap().read_to_string(&mut content).unwrap();
+        assert!(content.contains("title: Open bug\n"));
+        let mut source = String::new();
+        archive.by_name(source::SOURCE_FILE).unwrap().read_to_string(&mut source).unwrap();
+        let source: serde_json::Value = serde_json::from_str(&source).unwrap();
+        assert_eq!(source["repo"], "owner/repo");
+        assert_eq!(source["export"], serde_json::json!({ "state": "open", "labels": ["bug"] }));
+        assert!(!config.issues_dir.exists());
+    }
+}
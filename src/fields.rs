@@ -0,0 +1,104 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use octorust::types;
+use serde::Serialize;
+
+/// The fields retasks can push. Any other frontmatter key is local-only
+/// and never sent to GitHub.
+pub const MANAGED: [&str; 5] = ["title", "body", "state", "labels", "assignees"];
+
+/// Which managed fields go into update requests, from `--push-fields` or
+/// `--no-push-fields`. All of them by default.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PushFields(BTreeSet<&'static str>);
+
+impl Default for PushFields {
+    fn default() -> Self {
+        PushFields(MANAGED.into_iter().collect())
+    }
+}
+
+impl PushFields {
+    /// `only` lists the fields to push, `except` the fields not to push.
+    /// At most one of them is given.
+    pub fn parse(only: Option<&str>, except: Option<&str>) -> Result<Self> {
+        let fields = match (only, except) {
+            (Some(only), _) => PushFields(parse_names(only)?),
+            (None, Some(except)) => {
+                let except = parse_names(except)?;
+                PushFields(MANAGED.into_iter().filter(|field| !except.contains(field)).collect())
+            }
+            (None, None) => PushFields::default(),
+        };
+        if fields.0.is_empty() {
+            return Err(anyhow::anyhow!("At least one field must be pushed; to push nothing, don't watch or push"));
+        }
+        Ok(fields)
+    }
+
+    pub fn includes(&self, field: &str) -> bool {
+        self.0.contains(field)
+    }
+
+    /// Leaves the excluded fields out of `update`. Absent fields aren't
+    /// serialized, so GitHub keeps its current values for them.
+    pub fn apply(&self, update: &mut types::IssuesUpdateRequest) {
+        if !self.includes("title") {
+            update.title = None;
+        }
+        if !self.includes("body") {
+            update.body = String::new();
+        }
+        if !self.includes("state") {
+            update.state = None;
+        }
+        if !self.includes("labels") {
+            update.labels.clear();
+        }
+        if !self.includes("assignees") {
+            update.assignees.clear();
+        }
+    }
+}
+
+fn parse_names(list: &str) -> Result<BTreeSet<&'static str>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            MANAGED
+                .into_iter()
+                .find(|field| field.eq_ignore_ascii_case(name))
+                .ok_or_else(|| anyhow::anyhow!("Unknown field {:?} (expected one of {})", name, MANAGED.join(", ")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excluded_fields_are_left_out_of_the_update() {
+        let mut update = types::IssuesUpdateRequest {
+            title: Some(types::TitleOneOf::String("Title".to_string())),
+            body: "Body".to_string(),
+            state: Some(types::State::Closed),
+            assignee: String::new(),
+            assignees: vec!["octocat".to_string()],
+            milestone: None,
+            labels: vec![types::IssuesCreateRequestLabelsOneOf::String("bug".to_string())],
+        };
+
+        PushFields::parse(None, Some("labels, Body")).unwrap().apply(&mut update);
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            serde_json::json!({ "title": "Title", "state": "closed", "assignees": ["octocat"] })
+        );
+
+        let only_state = PushFields::parse(Some("state"), None).unwrap();
+        assert!(only_state.includes("state") && !only_state.includes("title"));
+        assert!(PushFields::parse(Some("priority"), None).unwrap_err().to_string().contains("priority"));
+    }
+}
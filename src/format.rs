@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    check_state_reason, fetch_issues, issue_from_remote, issues_differ, labels, update_request_for_issue,
+    write_file_with_retry, Config, Issue, SyncSummary,
+};
+
+/// How issues are stored locally.
+///
+/// `Markdown` is one frontmatter+body file per issue, meant for editing by
+/// hand. `Json` and `Yaml` keep every issue in a single `issues.json` /
+/// `issues.yaml` array for data pipelines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    Markdown,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "markdown" => Ok(Format::Markdown),
+            "json" => Ok(Format::Json),
+            "yaml" => Ok(Format::Yaml),
+            _ => Err(anyhow::anyhow!("Invalid --format value: {} (expected markdown, json or yaml)", value)),
+        }
+    }
+
+    /// The single data file for `Json` and `Yaml`, None for `Markdown`.
+    pub fn data_file_name(&self) -> Option<&'static str> {
+        match self {
+            Format::Markdown => None,
+            Format::Json => Some("issues.json"),
+            Format::Yaml => Some("issues.yaml"),
+        }
+    }
+}
+
+fn data_file_path(config: &Config) -> PathBuf {
+    config.issues_dir.join(config.format.data_file_name().expect("data file format"))
+}
+
+fn read_data_file(config: &Config) -> Result<Vec<Issue>> {
+    let path = data_file_path(config);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let issues = match config.format {
+        Format::Json => serde_json::from_str(&content).map_err(anyhow::Error::from),
+        _ => serde_yaml::from_str(&content).map_err(anyhow::Error::from),
+    };
+    issues.context(format!("Failed to parse {}", path.display()))
+}
+
+/// Merges pulled issues into the data file, keeping entries that weren't
+/// fetched (e.g. on a catch-up pull). With `--prune`, entries for issues
+/// missing from `remote_numbers`, every issue on GitHub when the fetch was
+/// complete, are dropped.
+pub fn write_data_file(config: &Config, issues: &[Issue], remote_numbers: Option<&HashSet<i64>>) -> Result<SyncSummary> {
+    let mut merged: BTreeMap<i64, Issue> = read_data_file(config)?
+        .into_iter()
+        .map(|issue| (issue.number, issue))
+        .collect();
+    let mut summary = SyncSummary::default();
+
+    for issue in issues {
+        match merged.insert(issue.number, issue.clone()) {
+            None => summary.created.push(issue.number),
+            Some(previous) if &previous != issue => summary.updated.push(issue.number),
+            Some(_) => {}
+        }
+    }
+
+    if let Some(remote_numbers) = remote_numbers.filter(|_| config.prune) {
+        merged.retain(|number, _| {
+            let keep = remote_numbers.contains(number);
+            if !keep {
+                summary.pruned.push(*number);
+            }
+            keep
+        });
+    }
+
+    if summary.created.is_empty() && summary.updated.is_empty() && summary.pruned.is_empty() {
+        return Ok(summary);
+    }
+
+    let path = data_file_path(config);
+    if config.dry_run {
+        println!(
+            "Would write {} ({} created, {} updated, {} pruned)",
+            path.display(),
+            summary.created.len(),
+            summary.updated.len(),
+            summary.pruned.len()
+        );
+        return Ok(SyncSummary::default());
+    }
+
+    let all: Vec<&Issue> = merged.values().collect();
+    let content = match config.format {
+        Format::Json => serde_json::to_string_pretty(&all)? + "\n",
+        _ => serde_yaml::to_string(&all)?,
+    };
+    write_file_with_retry(&path, &content, config.io_retries)
+        .context(format!("Failed to write {}", path.display()))?;
+    println!("Synced {} issue(s) to {}", merged.len(), path.display());
+
+    Ok(summary)
+}
+
+/// Pushes the entries of the data file that differ from GitHub. Returns the
+/// numbers of the updated issues.
+pub async fn push_data_file(config: &Config) -> Result<Vec<i64>> {
+    let mut local = read_data_file(config)?;
+    for issue in &mut local {
+        if let Some(body) = &issue.body {
+            issue.body = Some(crate::attachments::restore_remote_urls(config, body)?);
+        }
+    }
+    let remote: HashMap<i64, Issue> = fetch_issues(config, None)
+        .await?
+        .into_iter()
+        .map(|issue| (issue.number, issue_from_remote(config, issue)))
+        .collect();
+
+    let mut updated = Vec::new();
+    let mut failures = Vec::new();
+
+    for issue in &local {
+        if config.only.is_some_and(|only| only != issue.number) {
+            continue;
+        }
+
+        let Some(remote_issue) = remote.get(&issue.number) else {
+            eprintln!(
+                "{}",
+                config.colors.warning(&format!("Warning: issue #{} does not exist on GitHub, skipping", issue.number))
+            );
+            continue;
+        };
+        if !issues_differ(config, issue, remote_issue) {
+            continue;
+        }
+
+        let mut update = update_request_for_issue(issue)?;
+        config.push_fields.apply(&mut update);
+        labels::prepare(config, issue.number, &mut update).await;
+        let state_reason = check_state_reason(issue.state_reason.as_deref().unwrap_or_default(), Some(&issue.state))
+            .context(format!("Issue #{}", issue.number))?
+            .filter(|_| config.push_fields.includes("state"));
+        if config.dry_run {
+            println!("Would update issue #{} with {}", issue.number, serde_json::to_string(&update)?);
+            if let Some(reason) = state_reason {
+                println!("Would set state_reason of issue #{} to {}", issue.number, reason);
+            }
+            continue;
+        }
+
+        let number = issue.number;
+        let update = &update;
+        let result = config.github.with_rotation(|client| async move {
+            client.issues().update(&config.repo_owner, &config.repo_name, number, update).await
+        }).await.map_err(anyhow::Error::from);
+        let result = match (result, state_reason, &update.state) {
+            (Ok(_), Some(reason), Some(state)) => config
+                .github
+                .update_state_reason(&config.repo_owner, &config.repo_name, number, &state.to_string(), reason)
+                .await,
+            (result, _, _) => result.map(|_| ()),
+        };
+
+        match result {
+            Ok(_) => {
+                println!("{}", config.colors.success(&format!("Updated issue #{} on GitHub", number)));
+                updated.push(number);
+            }
+            Err(e) => {
+                eprintln!("{}", config.colors.error(&format!("Failed to update issue #{} on GitHub: {}", number, e)));
+                failures.push(number);
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        let numbers: Vec<String> = failures.iter().map(|n| format!("#{}", n)).collect();
+        return Err(anyhow::anyhow!("Failed to push {} issue(s): {}", failures.len(), numbers.join(", ")));
+    }
+
+    Ok(updated)
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use octorust::types;
+use serde::de::DeserializeOwned;
+
+use crate::{parse_list, parse_state, unquote_scalar};
+
+/// The frontmatter keys retasks reads, each parsed into its type. A key
+/// that is absent is None, which on push means "leave that field alone".
+/// Keys retasks doesn't know are ignored here and kept in the file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Frontmatter {
+    pub number: Option<i64>,
+    pub title: Option<String>,
+    pub state: Option<types::State>,
+    pub state_reason: Option<String>,
+    pub labels: Option<Vec<String>>,
+    pub assignees: Option<Vec<String>>,
+    pub locked: Option<bool>,
+    pub created_at: Option<DateTime<FixedOffset>>,
+    pub updated_at: Option<DateTime<FixedOffset>>,
+    pub schema_version: Option<u32>,
+}
+
+impl Frontmatter {
+    /// Parses the raw `key: value` pairs of a file. A value of the wrong
+    /// type is an error naming the key, rather than being skipped.
+    pub fn parse(raw: &HashMap<String, String>) -> Result<Self> {
+        Ok(Frontmatter {
+            number: typed(raw, "number")?,
+            title: raw.get("title").map(|title| unquote_scalar(title)),
+            state: raw
+                .get("state")
+                .map(|state| parse_state(&unquote_scalar(state)))
+                .transpose()
+                .context("Invalid `state` in frontmatter")?,
+            state_reason: raw.get("state_reason").map(|reason| unquote_scalar(reason)),
+            labels: list(raw, "labels")?,
+            assignees: list(raw, "assignees")?,
+            locked: boolean(raw, "locked")?,
+            created_at: timestamp(raw, "created_at")?,
+            updated_at: timestamp(raw, "updated_at")?,
+            schema_version: typed(raw, "schema_version")?,
+        })
+    }
+}
+
+/// Finds the frontmatter block: the file must open with a `---` line, and
+/// the block ends at the next line that is exactly `---`. Returns the lines
+/// in between and the byte offset of the closing fence. Only this first
+/// block is frontmatter, so a `---` inside a value, or a body that itself
+/// looks like frontmatter, is never taken for the end.
+pub fn block(content: &str) -> Option<(&str, usize)> {
+    let mut offset = 0;
+    let mut start = None;
+    for line in content.split_inclusive('\n') {
+        let is_fence = line.trim_end() == "---";
+        match start {
+            None if is_fence => start = Some(offset + line.len()),
+            None => return None,
+            Some(start) if is_fence => return Some((&content[start..offset], offset)),
+            Some(_) => {}
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Reads a boolean the way people write them: `true`/`false`, and also
+/// `yes`/`no` and `on`/`off`, in any case. Files are always written with
+/// `true`/`false`, so the other forms are normalized on the next pull.
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match unquote_scalar(value).to_lowercase().as_str() {
+        "true" | "yes" | "on" => Some(true),
+        "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn boolean(raw: &HashMap<String, String>, key: &str) -> Result<Option<bool>> {
+    raw.get(key)
+        .map(|value| {
+            parse_bool(value).ok_or_else(|| {
+                anyhow::anyhow!("Invalid `{}` in frontmatter: {:?} (expected true/false, yes/no or on/off)", key, value)
+            })
+        })
+        .transpose()
+}
+
+// RFC 3339, with whatever offset --timezone wrote it in
+fn timestamp(raw: &HashMap<String, String>, key: &str) -> Result<Option<DateTime<FixedOffset>>> {
+    raw.get(key)
+        .map(|value| {
+            DateTime::parse_from_rfc3339(&unquote_scalar(value))
+                .context(format!("Invalid `{}` in frontmatter: {:?}", key, value))
+        })
+        .transpose()
+}
+
+fn typed<T: DeserializeOwned>(raw: &HashMap<String, String>, key: &str) -> Result<Option<T>> {
+    raw.get(key)
+        .map(|value| serde_yaml::from_str(value).context(format!("Invalid `{}` in frontmatter: {:?}", key, value)))
+        .transpose()
+}
+
+fn list(raw: &HashMap<String, String>, key: &str) -> Result<Option<Vec<String>>> {
+    raw.get(key)
+        .map(|value| parse_list(value).context(format!("Invalid `{}` in frontmatter", key)))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_markdown_file;
+
+    #[test]
+    fn test_fields_are_typed_with_per_field_errors() {
+        let (raw, _) =
+            parse_markdown_file("---\nnumber: 7\ntitle: \"Fix: crash\"\nstate: Closed\nlabels: [bug]\ncustom: x\n---\n\nBody")
+                .unwrap();
+        let frontmatter = Frontmatter::parse(&raw).unwrap();
+        assert_eq!(frontmatter.number, Some(7));
+        assert_eq!(frontmatter.title.as_deref(), Some("Fix: crash"));
+        assert_eq!(frontmatter.state, Some(types::State::Closed));
+        assert_eq!(frontmatter.labels, Some(vec!["bug".to_string()]));
+        assert_eq!(frontmatter.assignees, None);
+
+        let (raw, _) = parse_markdown_file("---\nnumber: seven\n---\n\nBody").unwrap();
+        let error = format!("{:#}", Frontmatter::parse(&raw).unwrap_err());
+        assert!(error.starts_with("Invalid `number` in frontmatter: \"seven\""), "{}", error);
+
+        let (raw, _) = parse_markdown_file("---\nnumber: 7\nstate: done\n---\n\nBody").unwrap();
+        assert!(Frontmatter::parse(&raw).unwrap_err().to_string().contains("`state`"));
+    }
+
+    #[test]
+    fn test_booleans_accept_common_variants() {
+        for (value, expected) in [("true", true), ("Yes", true), ("on", true), ("\"false\"", false), ("NO", false), ("off", false)] {
+            let (raw, _) = parse_markdown_file(&format!("---\nnumber: 1\nlocked: {}\n---\n\nBody", value)).unwrap();
+            assert_eq!(Frontmatter::parse(&raw).unwrap().locked, Some(expected), "{}", value);
+        }
+
+        // Sidecars store the canonical form
+        let (body, json) = crate::sidecar::split("---\nnumber: 1\nlocked: yes\n---\n\nBody");
+        assert!(json.as_deref().unwrap().contains("\"locked\": true"));
+        assert_eq!(crate::sidecar::join(&body, &json.unwrap()).unwrap(), "---\nnumber: 1\nlocked: true\n---\n\nBody");
+
+        let (raw, _) = parse_markdown_file("---\nnumber: 1\nlocked: maybe\n---\n\nBody").unwrap();
+        let error = Frontmatter::parse(&raw).unwrap_err().to_string();
+        assert!(error.starts_with("Invalid `locked` in frontmatter: \"maybe\""), "{}", error);
+    }
+}
@@ -0,0 +1,233 @@
+use std::process::Command;
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::{cache, checkpoint, raw, service, source, state, Config, SyncSummary};
+
+pub const DEFAULT_MESSAGE: &str = "Sync {count} issue(s) from {repo}";
+
+/// Paths in the issues directory that `--git-commit` leaves out: the sync
+/// state, response cache, checkpoint, pause file and raw dumps are
+/// machine-local, and the source file changes on every pull.
+const LOCAL_PATHS: [&str; 6] = [
+    state::STATE_FILE,
+    source::SOURCE_FILE,
+    cache::CACHE_FILE,
+    checkpoint::CHECKPOINT_DIR,
+    service::PAUSE_FILE,
+    raw::RAW_DIR,
+];
+
+/// Whether `--git-commit` makes one commit per pull or one per changed issue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Granularity {
+    #[default]
+    PerSync,
+    PerIssue,
+}
+
+impl Granularity {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "per-sync" => Ok(Granularity::PerSync),
+            "per-issue" => Ok(Granularity::PerIssue),
+            _ => Err(anyhow::anyhow!("Invalid --git-commit-granularity value: {} (expected per-issue or per-sync)", value)),
+        }
+    }
+}
+
+/// How `--git-commit` commits pulled changes to the repository that holds
+/// the issues directory.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GitCommit {
+    /// Template with `{count}`, `{repo}` and `{date}` tokens.
+    pub message: String,
+    /// Author and committer name; git's `user.name` when None.
+    pub author: Option<String>,
+    /// Author and committer email; git's `user.email` when None.
+    pub email: Option<String>,
+    pub granularity: Granularity,
+}
+
+impl GitCommit {
+    fn render_message(&self, count: usize, repo: &str) -> String {
+        self.message
+            .replace("{count}", &count.to_string())
+            .replace("{repo}", repo)
+            .replace("{date}", &Utc::now().format("%Y-%m-%d").to_string())
+    }
+}
+
+/// Commits the issues directory after a pull that changed something, if
+/// `--git-commit` is set. The files retasks keeps for itself in the issues
+/// directory are never committed: see [`LOCAL_PATHS`].
+///
+/// Like the post-sync hook, failures are only reported.
+///
+/// Per issue, each created, updated and pruned issue's files are committed
+/// on their own first; whatever else the pull changed (downloaded
+/// attachments, a data file) then goes into one commit with the usual message.
+pub fn commit_pulled_changes(config: &Config, summary: &SyncSummary) {
+    let Some(git) = &config.git_commit else {
+        return;
+    };
+    let count = summary.created.len() + summary.updated.len() + summary.pruned.len();
+    if config.dry_run || count == 0 {
+        return;
+    }
+
+    let repo = format!("{}/{}", config.repo_owner, config.repo_name);
+    let excludes: Vec<String> = LOCAL_PATHS.iter().map(|path| format!(":(exclude){}", path)).collect();
+    let mut pathspecs = vec!["."];
+    pathspecs.extend(excludes.iter().map(String::as_str));
+
+    let mut args = vec!["add", "-A", "--"];
+    args.extend(&pathspecs);
+    let mut result = run_git(config, git, &args);
+
+    if result.is_ok() && git.granularity == Granularity::PerIssue {
+        result = commit_each_issue(config, git, summary);
+    }
+
+    let result = result.and_then(|_| {
+        if git.granularity == Granularity::PerIssue && !has_staged_changes(config, git, &pathspecs)? {
+            return Ok(None);
+        }
+        let message = git.render_message(count, &repo);
+        let mut args = vec!["commit", "--quiet", "-m", &message, "--"];
+        args.extend(&pathspecs);
+        run_git(config, git, &args).map(|_| Some(message))
+    });
+
+    match result {
+        Ok(Some(message)) => println!("Committed: {}", message),
+        Ok(None) => {}
+        Err(e) => eprintln!("{}", config.colors.warning(&format!("Warning: git commit failed: {}", e))),
+    }
+}
+
+// Issues are committed in the order created, updated, pruned, each by number
+fn commit_each_issue(config: &Config, git: &GitCommit, summary: &SyncSummary) -> Result<(), String> {
+    let changes = [("Add", &summary.created), ("Update", &summary.updated), ("Remove", &summary.pruned)];
+    for (verb, numbers) in changes {
+        let mut numbers = numbers.clone();
+        numbers.sort();
+        for number in numbers {
+            let Some(paths) = summary.files.get(&number).filter(|paths| !paths.is_empty()) else {
+                continue;
+            };
+            let paths: Vec<&str> = paths.iter().filter_map(|path| path.to_str()).collect();
+            // A file written and removed again between two commits was never
+            // known to git, and committing it would fail
+            if !has_staged_changes(config, git, &paths)? {
+                continue;
+            }
+            let message = issue_message(verb, number, summary.titles.get(&number).map(String::as_str));
+
+            let mut args = vec!["commit", "--quiet", "-m", &message, "--"];
+            args.extend(paths);
+            run_git(config, git, &args)?;
+            println!("Committed: {}", message);
+        }
+    }
+    Ok(())
+}
+
+fn issue_message(verb: &str, number: i64, title: Option<&str>) -> String {
+    match title {
+        Some(title) => format!("{} issue #{}: {}", verb, number, title),
+        None => format!("{} issue #{}", verb, number),
+    }
+}
+
+// Whether the index differs from HEAD for `pathspecs`
+fn has_staged_changes(config: &Config, git: &GitCommit, pathspecs: &[&str]) -> Result<bool, String> {
+    let mut args = vec!["diff", "--cached", "--quiet", "--"];
+    args.extend(pathspecs);
+    let status = git_command(config, git, &args).status().map_err(|e| format!("failed to run git: {}", e))?;
+    // `git diff --quiet` exits with 1 when there are differences
+    match status.code() {
+        Some(0) => Ok(false),
+        Some(1) => Ok(true),
+        _ => Err(format!("git diff exited with {}", status)),
+    }
+}
+
+fn run_git(config: &Config, git: &GitCommit, args: &[&str]) -> Result<(), String> {
+    let output = git_command(config, git, args).output().map_err(|e| format!("failed to run git: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("git {} exited with {}: {}", args[0], output.status, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+fn git_command(config: &Config, git: &GitCommit, args: &[&str]) -> Command {
+    let mut command = Command::new("git");
+    command.current_dir(&config.issues_dir).args(args);
+    if let Some(author) = &git.author {
+        command.env("GIT_AUTHOR_NAME", author).env("GIT_COMMITTER_NAME", author);
+    }
+    if let Some(email) = &git.email {
+        command.env("GIT_AUTHOR_EMAIL", email).env("GIT_COMMITTER_EMAIL", email);
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_message_fills_tokens() {
+        let git = GitCommit { message: "{repo}: {count} on {date}".to_string(), ..Default::default() };
+        let message = git.render_message(3, "owner/repo");
+        assert!(message.starts_with("owner/repo: 3 on "));
+        assert!(!message.contains('{'));
+    }
+
+    #[tokio::test]
+    async fn test_per_issue_commits_each_issue_then_the_rest() {
+        let server = wiremock::MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = crate::tests::test_config(&server, dir.path());
+        let git = GitCommit {
+            message: "Sync {count} issue(s)".to_string(),
+            author: Some("retasks".to_string()),
+            email: Some("retasks@example.com".to_string()),
+            granularity: Granularity::PerIssue,
+        };
+        run_git(&config, &git, &["init", "--quiet"]).unwrap();
+        std::fs::write(dir.path().join("issue-3.md"), "old").unwrap();
+        run_git(&config, &git, &["add", "-A"]).unwrap();
+        run_git(&config, &git, &["commit", "--quiet", "-m", "Initial"]).unwrap();
+
+        // Issue 1 is new, issue 3 was pruned, and an attachment came along
+        std::fs::write(dir.path().join("issue-1.md"), "new").unwrap();
+        std::fs::remove_file(dir.path().join("issue-3.md")).unwrap();
+        std::fs::write(dir.path().join("image.png"), "png").unwrap();
+        // Machine-local files stay out of every commit
+        std::fs::write(dir.path().join(cache::CACHE_FILE), "{}").unwrap();
+        std::fs::create_dir(dir.path().join(raw::RAW_DIR)).unwrap();
+        std::fs::write(dir.path().join(raw::RAW_DIR).join("issue-1.json"), "{}").unwrap();
+        let summary = SyncSummary {
+            created: vec![1],
+            pruned: vec![3],
+            files: [(1, vec![dir.path().join("issue-1.md")]), (3, vec![dir.path().join("issue-3.md")])].into(),
+            titles: [(1, "First".to_string())].into(),
+            ..Default::default()
+        };
+        config.git_commit = Some(git.clone());
+        commit_pulled_changes(&config, &summary);
+
+        let log = git_command(&config, &git, &["log", "--format=%s"]).output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&log.stdout),
+            "Sync 2 issue(s)\nRemove issue #3\nAdd issue #1: First\nInitial\n"
+        );
+        let files = git_command(&config, &git, &["ls-files"]).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&files.stdout), "image.png\nissue-1.md\n");
+    }
+}
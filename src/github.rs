@@ -0,0 +1,709 @@
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use octorust::{auth::Credentials, Client, ClientError};
+use reqwest::header::HeaderMap;
+
+use crate::error::SyncError;
+
+/// How long to back off when GitHub's abuse detection (the "secondary rate
+/// limit") rejects a request without saying when to retry.
+const SECONDARY_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(60);
+const SECONDARY_RATE_LIMIT_RETRIES: u32 = 3;
+/// Retries after a 5xx response or no response at all, unless
+/// `--server-error-retries` says otherwise.
+pub const SERVER_ERROR_RETRIES: u32 = 3;
+/// The wait before the first of those retries; it doubles with each one.
+const SERVER_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_SERVER_ERROR_BACKOFF: Duration = Duration::from_secs(30);
+/// Timeline events per page, GitHub's maximum.
+const PAGE_SIZE: usize = 100;
+
+/// A single GitHub client shared by all requests, rotating through one or
+/// more tokens to spread the rate limit.
+///
+/// The underlying HTTP client is built once; rotating only swaps the
+/// credentials on a cheap clone of it.
+pub struct GitHub {
+    client: Client,
+    tokens: Vec<String>,
+    next: AtomicUsize,
+    secondary_backoff: Duration,
+    server_error_retries: u32,
+    server_backoff: Duration,
+    api_url: String,
+    /// For the requests octorust has no call for. The same client octorust
+    /// uses, so it sends the same `--header`s and, as it doesn't follow
+    /// redirects, never sends them to another host.
+    http: reqwest::Client,
+    /// Names of the `--header`s sent with every request.
+    header_names: Vec<String>,
+}
+
+impl GitHub {
+    /// `api_url` overrides the default `https://api.github.com`, e.g. for
+    /// GitHub Enterprise or a mock server in tests.
+    /// `headers` are sent with every request, e.g. for a gateway in front
+    /// of the API; see [`parse_headers`].
+    pub fn new(tokens: Vec<String>, api_url: Option<&str>, headers: reqwest::header::HeaderMap) -> Result<Self> {
+        if tokens.is_empty() {
+            return Err(anyhow::anyhow!("At least one GitHub token is required (--token, --token-file or `retasks init`)"));
+        }
+
+        // Without octorust's retry middleware, which retries server errors
+        // silently; with_rotation retries them itself, logging each attempt
+        let http = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .default_headers(headers.clone())
+            .build()
+            .context("Failed to create GitHub client")?;
+        let mut client = Client::custom(
+            "github-issues-sync".to_string(),
+            Credentials::Token(tokens[0].clone()),
+            reqwest_middleware::ClientBuilder::new(http.clone()).build(),
+        );
+        if let Some(url) = api_url {
+            client.with_host_override(url.trim_end_matches('/'));
+        }
+
+        Ok(GitHub {
+            client,
+            tokens,
+            next: AtomicUsize::new(0),
+            secondary_backoff: SECONDARY_RATE_LIMIT_BACKOFF,
+            server_error_retries: SERVER_ERROR_RETRIES,
+            server_backoff: SERVER_ERROR_BACKOFF,
+            api_url: api_url.unwrap_or(octorust::FALLBACK_HOST).trim_end_matches('/').to_string(),
+            header_names: headers.keys().map(|name| name.to_string()).collect(),
+            http,
+        })
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    pub fn token_count(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// The names of the `--header`s; their values are never shown.
+    pub fn header_names(&self) -> &[String] {
+        &self.header_names
+    }
+
+    pub fn set_server_error_retries(&mut self, retries: u32) {
+        self.server_error_retries = retries;
+    }
+
+    pub fn server_error_retries(&self) -> u32 {
+        self.server_error_retries
+    }
+
+    /// Returns a client carrying the next token in the rotation.
+    pub fn client(&self) -> Client {
+        let mut client = self.client.clone();
+        client.set_credentials(Credentials::Token(self.next_token().to_string()));
+        client
+    }
+
+    /// One client per token, in order, for checking each token on its own.
+    pub fn clients(&self) -> Vec<Client> {
+        self.tokens
+            .iter()
+            .map(|token| {
+                let mut client = self.client.clone();
+                client.set_credentials(Credentials::Token(token.clone()));
+                client
+            })
+            .collect()
+    }
+
+    fn next_token(&self) -> &str {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.tokens.len();
+        &self.tokens[index]
+    }
+
+    /// Sets an issue's state together with its `state_reason` (`completed`,
+    /// `not_planned` or `reopened`). octorust's update request has no field
+    /// for the reason, so this one is sent as a plain PATCH.
+    pub async fn update_state_reason(&self, owner: &str, repo: &str, number: i64, state: &str, reason: &str) -> Result<()> {
+        let url = format!("{}/repos/{}/{}/issues/{}", self.api_url, owner, repo, number);
+        let payload = serde_json::json!({ "state": state, "state_reason": reason });
+        self.with_raw_retries(|| self.send_state_reason(&url, &payload, number)).await
+    }
+
+    async fn send_state_reason(&self, url: &str, payload: &serde_json::Value, number: i64) -> Result<()> {
+        self.try_send_json(reqwest::Method::PATCH, url, payload)
+            .await
+            .context(format!("GitHub rejected state_reason for issue #{}", number))?;
+        Ok(())
+    }
+
+    /// The `state_reason` of one issue, which octorust's issue type doesn't
+    /// carry. `None` for issues GitHub has no reason recorded for.
+    pub async fn state_reason(&self, owner: &str, repo: &str, number: i64) -> Result<Option<String>> {
+        let url = format!("{}/repos/{}/{}/issues/{}", self.api_url, owner, repo, number);
+        let issue = self.get_json(&url).await?;
+        Ok(issue["state_reason"].as_str().map(str::to_string))
+    }
+
+    /// One page of the repository's issues, as GitHub returns them, with
+    /// the response headers for the `Link` to the next. Unlike octorust's
+    /// listing this keeps every field, `state_reason` included.
+    pub async fn list_issues_page(&self, owner: &str, repo: &str, query: &[(&str, String)]) -> Result<(Vec<serde_json::Value>, HeaderMap)> {
+        let base = format!("{}/repos/{}/{}/issues", self.api_url, owner, repo);
+        let url = reqwest::Url::parse_with_params(&base, query).context(format!("Invalid URL {}", base))?;
+        let (issues, headers) = self.with_raw_retries(|| self.try_get(url.as_str())).await?;
+        match issues {
+            serde_json::Value::Array(issues) => Ok((issues, headers)),
+            issues => Err(anyhow::anyhow!("Unexpected response listing issues: {}", issues)),
+        }
+    }
+
+    /// Every event on an issue's timeline, oldest first, as GitHub returns
+    /// them. octorust's type for these events has no fields.
+    pub async fn timeline(&self, owner: &str, repo: &str, number: i64) -> Result<Vec<serde_json::Value>> {
+        let mut events = Vec::new();
+        for page in 1.. {
+            let url = format!(
+                "{}/repos/{}/{}/issues/{}/timeline?per_page={}&page={}",
+                self.api_url, owner, repo, number, PAGE_SIZE, page
+            );
+            let response = self.get_json(&url).await?;
+            let serde_json::Value::Array(batch) = response else {
+                return Err(anyhow::anyhow!("Unexpected response listing the timeline of issue #{}: {}", number, response));
+            };
+            let done = batch.len() < PAGE_SIZE;
+            events.extend(batch);
+            if done {
+                break;
+            }
+        }
+        Ok(events)
+    }
+
+    /// Runs a GraphQL query and returns its `data`. Errors GitHub reports in
+    /// the response body, which come with a 200 status, fail the request,
+    /// except `RATE_LIMITED`, which is retried like a REST rate limit.
+    pub async fn graphql(&self, query: &str, variables: serde_json::Value) -> Result<serde_json::Value> {
+        let payload = serde_json::json!({ "query": query, "variables": variables });
+        let url = self.graphql_url();
+        self.with_raw_retries(|| self.try_graphql(&url, &payload)).await
+    }
+
+    async fn try_graphql(&self, url: &str, payload: &serde_json::Value) -> Result<serde_json::Value> {
+        let mut response = self.try_send_json(reqwest::Method::POST, url, payload).await?;
+        if let Some(errors) = response["errors"].as_array().filter(|errors| !errors.is_empty()) {
+            let messages: Vec<&str> = errors.iter().filter_map(|error| error["message"].as_str()).collect();
+            let error = anyhow::anyhow!("GitHub rejected a GraphQL query: {}", messages.join("; "));
+            // The token's points are used up; other tokens may have some left,
+            // and without any the limit is waited out
+            if errors.iter().any(|error| error["type"] == "RATE_LIMITED") {
+                return Err(error.context(RateLimit { secondary: false, retry_after: Some(self.secondary_backoff) }));
+            }
+            return Err(error);
+        }
+        Ok(response["data"].take())
+    }
+
+    // GitHub Enterprise serves REST under /api/v3 and GraphQL at /api/graphql
+    fn graphql_url(&self) -> String {
+        match self.api_url.strip_suffix("/api/v3") {
+            Some(host) => format!("{}/api/graphql", host),
+            None => format!("{}/graphql", self.api_url),
+        }
+    }
+
+    /// Edits a milestone. octorust's request leaves out empty fields, so it
+    /// can't clear a description or due date; this sends them as null.
+    pub async fn update_milestone(&self, owner: &str, repo: &str, number: i64, payload: &serde_json::Value) -> Result<()> {
+        let url = format!("{}/repos/{}/{}/milestones/{}", self.api_url, owner, repo, number);
+        self.with_raw_retries(|| self.try_send_json(reqwest::Method::PATCH, &url, payload)).await?;
+        Ok(())
+    }
+
+    async fn try_send_json(&self, method: reqwest::Method, url: &str, payload: &serde_json::Value) -> Result<serde_json::Value> {
+        let response = self.http
+            .request(method, url)
+            .header("Authorization", format!("Bearer {}", self.next_token()))
+            .header("Accept", "application/vnd.github+json")
+            .header("Content-Type", "application/json")
+            .header("User-Agent", "github-issues-sync")
+            .body(payload.to_string())
+            .send()
+            .await
+            .map_err(SyncError::from)
+            .context(format!("Failed to request {}", url))?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response
+            .text()
+            .await
+            .map_err(SyncError::from)
+            .context(format!("Failed to read response from {}", url))?;
+        if !status.is_success() {
+            return Err(response_error(status.as_u16(), &headers, body)).context(format!("Failed to request {}", url));
+        }
+        serde_json::from_str(&body).context(format!("Invalid JSON from {}", url))
+    }
+
+    async fn get_json(&self, url: &str) -> Result<serde_json::Value> {
+        let (value, _) = self.with_raw_retries(|| self.try_get(url)).await?;
+        Ok(value)
+    }
+
+    async fn try_get(&self, url: &str) -> Result<(serde_json::Value, HeaderMap)> {
+        let response = self.http
+            .get(url)
+            .header("Authorization", format!("Bearer {}", self.next_token()))
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "github-issues-sync")
+            .send()
+            .await
+            .map_err(SyncError::from)
+            .context(format!("Failed to request {}", url))?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response
+            .text()
+            .await
+            .map_err(SyncError::from)
+            .context(format!("Failed to read response from {}", url))?;
+        if !status.is_success() {
+            return Err(response_error(status.as_u16(), &headers, body)).context(format!("Failed to request {}", url));
+        }
+        let value = serde_json::from_str(&body).context(format!("Invalid JSON from {}", url))?;
+        Ok((value, headers))
+    }
+
+    /// Runs a request, moving on to the next token whenever the current one
+    /// has hit its rate limit. Gives up once every token has been tried.
+    ///
+    /// Secondary rate limits apply to the whole account rather than a token,
+    /// so those are retried after a fixed backoff instead of rotating.
+    /// What fails in the end is returned as a [`SyncError`].
+    pub async fn with_rotation<T, F, Fut>(&self, mut request: F) -> Result<T, SyncError>
+    where
+        F: FnMut(Client) -> Fut,
+        Fut: Future<Output = Result<T, ClientError>>,
+    {
+        let mut attempts = 0;
+        let mut secondary_retries = 0;
+        let mut server_retries = 0;
+        loop {
+            attempts += 1;
+            match request(self.client()).await {
+                Err(ClientError::RateLimited { duration }) if attempts < self.tokens.len() => {
+                    eprintln!(
+                        "Token rate limited for the next {} seconds, switching to the next token",
+                        duration
+                    );
+                }
+                Err(error) if is_secondary_rate_limit(&error) && secondary_retries < SECONDARY_RATE_LIMIT_RETRIES => {
+                    secondary_retries += 1;
+                    let backoff = retry_after(&error).unwrap_or(self.secondary_backoff);
+                    eprintln!(
+                        "GitHub secondary rate limit (abuse detection) hit, waiting {} seconds before retrying ({}/{})",
+                        backoff.as_secs(),
+                        secondary_retries,
+                        SECONDARY_RATE_LIMIT_RETRIES
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(error) if server_retries < self.server_error_retries && server_failure(&error).is_some() => {
+                    server_retries += 1;
+                    if let Some(failure) = server_failure(&error) {
+                        self.wait_to_retry(&failure, server_retries).await;
+                    }
+                }
+                result => return result.map_err(SyncError::from),
+            }
+        }
+    }
+
+    /// Runs a request made without octorust the way [`with_rotation`] runs
+    /// octorust's: each attempt takes the next token, so a rate limited
+    /// token is followed by the others, secondary rate limits (and others
+    /// that say how long to wait) are retried after a backoff, and 5xx
+    /// responses and failures to get a response are retried.
+    ///
+    /// [`with_rotation`]: GitHub::with_rotation
+    async fn with_raw_retries<T, F, Fut>(&self, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempts = 0;
+        let mut limit_retries = 0;
+        let mut retries = 0;
+        loop {
+            attempts += 1;
+            match request().await {
+                Err(error) if rate_limit(&error).is_some_and(|limit| !limit.secondary) && attempts < self.tokens.len() => {
+                    eprintln!("Token rate limited, switching to the next token");
+                }
+                Err(error)
+                    if rate_limit(&error).is_some_and(|limit| limit.secondary || limit.retry_after.is_some())
+                        && limit_retries < SECONDARY_RATE_LIMIT_RETRIES =>
+                {
+                    limit_retries += 1;
+                    let Some(limit) = rate_limit(&error) else { return Err(error) };
+                    let backoff = limit.retry_after.unwrap_or(self.secondary_backoff);
+                    eprintln!(
+                        "{}, waiting {} seconds before retrying ({}/{})",
+                        limit,
+                        backoff.as_secs(),
+                        limit_retries,
+                        SECONDARY_RATE_LIMIT_RETRIES
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(error) if retries < self.server_error_retries => {
+                    let Some(failure) = error.chain().find_map(|cause| cause.downcast_ref::<SyncError>()).filter(|e| is_server_failure(e)) else {
+                        return Err(error);
+                    };
+                    retries += 1;
+                    self.wait_to_retry(failure, retries).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // Logs the retry and waits for it, doubling the wait each time
+    async fn wait_to_retry(&self, failure: &SyncError, retry: u32) {
+        let backoff = self.server_backoff.saturating_mul(1 << (retry - 1).min(16)).min(MAX_SERVER_ERROR_BACKOFF);
+        eprintln!(
+            "{}; retrying in {} seconds ({}/{})",
+            failure,
+            backoff.as_secs(),
+            retry,
+            self.server_error_retries
+        );
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+/// A rate limited request made without octorust, as context on its
+/// [`SyncError`], for [`GitHub::with_raw_retries`] to act on.
+#[derive(Debug)]
+struct RateLimit {
+    /// The secondary rate limit (abuse detection), which applies to the
+    /// whole account, rather than the token's own.
+    secondary: bool,
+    retry_after: Option<Duration>,
+}
+
+impl std::fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.secondary {
+            true => write!(f, "GitHub secondary rate limit (abuse detection) hit"),
+            false => write!(f, "GitHub rate limit hit"),
+        }
+    }
+}
+
+// The error for an unsuccessful response to a request made without
+// octorust, marked as a RateLimit when it is one
+fn response_error(status: u16, headers: &HeaderMap, body: String) -> anyhow::Error {
+    let exhausted = headers.get("x-ratelimit-remaining").is_some_and(|remaining| remaining == "0");
+    let secondary = body.to_lowercase().contains("secondary rate limit");
+    let retry_after = headers
+        .get("retry-after")
+        .and_then(|value| value.to_str().ok()?.trim().parse().ok())
+        .map(Duration::from_secs);
+    let error = anyhow::Error::new(SyncError::from_status(status, body));
+    if matches!(status, 403 | 429) && (exhausted || secondary) {
+        return error.context(RateLimit { secondary, retry_after });
+    }
+    error
+}
+
+fn rate_limit(error: &anyhow::Error) -> Option<&RateLimit> {
+    error.downcast_ref::<RateLimit>()
+}
+
+// 5xx responses and requests that got no response are likely to succeed if
+// tried again; 4xx responses mean the request itself was wrong
+fn is_server_failure(error: &SyncError) -> bool {
+    match error {
+        SyncError::Http { status, .. } => (500..=599).contains(status),
+        SyncError::Network { .. } => true,
+        _ => false,
+    }
+}
+
+// The same failures as is_server_failure, from octorust
+fn server_failure(error: &ClientError) -> Option<SyncError> {
+    let failure = match error {
+        ClientError::HttpError { status, error, .. } => SyncError::from_status(status.as_u16(), error.clone()),
+        ClientError::ReqwestError(error) if error.status().is_none() && !error.is_decode() && !error.is_body() => {
+            SyncError::Network { message: error.to_string() }
+        }
+        ClientError::ReqwestMiddleWareError(error) => SyncError::Network { message: error.to_string() },
+        _ => return None,
+    };
+    is_server_failure(&failure).then_some(failure)
+}
+
+fn is_secondary_rate_limit(error: &ClientError) -> bool {
+    match error {
+        ClientError::HttpError { status, error, .. } => {
+            (status.as_u16() == 403 || status.as_u16() == 429) && error.to_lowercase().contains("secondary rate limit")
+        }
+        _ => false,
+    }
+}
+
+// GitHub sometimes does send Retry-After with a secondary rate limit; prefer it when present
+fn retry_after(error: &ClientError) -> Option<Duration> {
+    let ClientError::HttpError { headers, .. } = error else {
+        return None;
+    };
+    let seconds = headers.get("retry-after")?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Reads `--header 'Name: Value'` arguments. Values are marked sensitive
+/// and left out of error messages, since they are usually credentials.
+/// `Authorization` is refused: the tokens set it.
+pub fn parse_headers<'a>(values: impl IntoIterator<Item = &'a str>) -> Result<reqwest::header::HeaderMap> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    for value in values {
+        let Some((name, value)) = value.split_once(':') else {
+            return Err(anyhow::anyhow!("Invalid --header: expected 'Name: Value'"));
+        };
+        let name = reqwest::header::HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| anyhow::anyhow!("Invalid --header name: {:?}", name.trim()))?;
+        if name == reqwest::header::AUTHORIZATION {
+            return Err(anyhow::anyhow!("--header can't set Authorization; pass the token with --token"));
+        }
+        let mut value = reqwest::header::HeaderValue::from_str(value.trim())
+            .map_err(|_| anyhow::anyhow!("Invalid --header value for {}: it must be visible ASCII", name))?;
+        value.set_sensitive(true);
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// Reads tokens from a file, one per line. Blank lines and `#` comments are ignored.
+pub fn read_token_file(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read token file: {}", path.display()))?;
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_server_errors_are_retried_and_client_errors_are_not() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(502))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(crate::tests::issue_json(1, "Issue", "open", &[], "")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/2"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({ "message": "Not Found" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/3"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let mut github = GitHub::new(vec!["token".to_string()], Some(&server.uri()), Default::default()).unwrap();
+        github.server_backoff = Duration::ZERO;
+        github.set_server_error_retries(2);
+        let issue = github
+            .with_rotation(|client| async move { client.issues().get("owner", "repo", 1).await })
+            .await
+            .unwrap();
+        assert_eq!(issue.body.title, "Issue");
+
+        let error = github.with_rotation(|client| async move { client.issues().get("owner", "repo", 2).await }).await;
+        assert!(matches!(error, Err(SyncError::Http { status: 404, .. })));
+        let error = github.state_reason("owner", "repo", 3).await.unwrap_err();
+        assert!(error.to_string().contains("Failed to request"), "{:#}", error);
+    }
+
+    #[tokio::test]
+    async fn test_secondary_rate_limit_is_retried_after_backoff() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(serde_json::json!({
+                "message": "You have exceeded a secondary rate limit. Please wait a few minutes before you try again."
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(crate::tests::issue_json(1, "Issue", "open", &[], "")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut github = GitHub::new(vec!["token".to_string()], Some(&server.uri()), Default::default()).unwrap();
+        github.secondary_backoff = Duration::ZERO;
+        let issue = github
+            .with_rotation(|client| async move { client.issues().get("owner", "repo", 1).await })
+            .await
+            .unwrap();
+        assert_eq!(issue.body.title, "Issue");
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_octorust_rotate_tokens_and_back_off_on_rate_limits() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .and(header("Authorization", "Bearer first"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .insert_header("x-ratelimit-remaining", "0")
+                    .set_body_json(serde_json::json!({ "message": "API rate limit exceeded" })),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .and(header("Authorization", "Bearer second"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "0")
+                    .set_body_json(serde_json::json!({ "message": "You have exceeded a secondary rate limit." })),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut closed = crate::tests::issue_json(1, "Issue", "closed", &[], "");
+        closed["state_reason"] = serde_json::json!("completed");
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(closed))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let tokens = vec!["first".to_string(), "second".to_string()];
+        let github = GitHub::new(tokens, Some(&server.uri()), Default::default()).unwrap();
+        // The first token is out, so the second is tried; its secondary
+        // limit is waited out before trying again
+        assert_eq!(github.state_reason("owner", "repo", 1).await.unwrap().as_deref(), Some("completed"));
+    }
+
+    #[tokio::test]
+    async fn test_graphql_rate_limited_errors_are_waited_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": null,
+                "errors": [{ "type": "RATE_LIMITED", "message": "API rate limit exceeded for user ID 1." }],
+            })))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "data": { "viewer": { "login": "octocat" } } })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut github = GitHub::new(vec!["token".to_string()], Some(&server.uri()), Default::default()).unwrap();
+        github.secondary_backoff = Duration::ZERO;
+        let data = github.graphql("query { viewer { login } }", serde_json::json!({})).await.unwrap();
+        assert_eq!(data["viewer"]["login"], "octocat");
+    }
+
+    #[tokio::test]
+    async fn test_custom_headers_go_with_every_request_and_stay_secret() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .and(header("X-Corp-Token", "s3cret"))
+            .and(header("X-Team", "tools"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(crate::tests::issue_json(1, "Issue", "open", &[], "")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1/timeline"))
+            .and(header("X-Corp-Token", "s3cret"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let headers = parse_headers(["X-Corp-Token: s3cret", "x-team:tools"]).unwrap();
+        let github = GitHub::new(vec!["token".to_string()], Some(&server.uri()), headers).unwrap();
+        assert_eq!(github.header_names(), ["x-corp-token", "x-team"]);
+        github.with_rotation(|client| async move { client.issues().get("owner", "repo", 1).await }).await.unwrap();
+        github.timeline("owner", "repo", 1).await.unwrap();
+
+        // Errors name the header, never its value
+        let error = parse_headers(["X-Corp-Token: s3c\u{1}ret"]).unwrap_err().to_string();
+        assert!(error.contains("x-corp-token"), "{}", error);
+        assert!(!error.contains("s3cret"), "{}", error);
+        assert!(!parse_headers(["s3cret"]).unwrap_err().to_string().contains("s3cret"));
+        assert!(parse_headers(["Bad Name: value"]).is_err());
+        assert!(parse_headers(["Authorization: Bearer x"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_custom_headers_never_follow_a_redirect_to_another_host() {
+        let elsewhere = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .expect(0)
+            .mount(&elsewhere)
+            .await;
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1/timeline"))
+            .respond_with(ResponseTemplate::new(302).insert_header("Location", format!("{}/collect", elsewhere.uri())))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let headers = parse_headers(["X-Corp-Token: s3cret"]).unwrap();
+        let github = GitHub::new(vec!["token".to_string()], Some(&server.uri()), headers).unwrap();
+        assert!(github.timeline("owner", "repo", 1).await.is_err());
+    }
+}
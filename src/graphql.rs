@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use octorust::types;
+use serde::Serialize;
+
+use crate::{Config, Issue};
+
+/// Which GitHub API full pulls list issues with. `Rest` pages through the
+/// REST listing; `Graphql` asks for only the fields retasks uses. Either
+/// resumes interrupted pulls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Api {
+    Rest,
+    Graphql,
+}
+
+impl Api {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "rest" => Ok(Api::Rest),
+            "graphql" => Ok(Api::Graphql),
+            _ => Err(anyhow::anyhow!("Invalid --api value: {} (expected rest or graphql)", value)),
+        }
+    }
+}
+
+const ISSUES_QUERY: &str = "query($owner: String!, $name: String!, $cursor: String, $filterBy: IssueFilters, $orderBy: IssueOrder) {
+  repository(owner: $owner, name: $name) {
+    issues(first: 100, after: $cursor, filterBy: $filterBy, orderBy: $orderBy) {
+      pageInfo { hasNextPage endCursor }
+      nodes {
+        number title body state stateReason url id locked authorAssociation createdAt updatedAt
+        comments { totalCount }
+        labels(first: 100) { pageInfo { hasNextPage } nodes { name } }
+        assignees(first: 100) { pageInfo { hasNextPage } nodes { login } }
+      }
+    }
+  }
+}";
+
+/// One page of issues, or with `since` of those updated at or after it,
+/// from `cursor` on. Returns the GraphQL nodes and the cursor of the next
+/// page, if there is one.
+pub async fn fetch_page(
+    config: &Config,
+    since: Option<DateTime<Utc>>,
+    cursor: Option<String>,
+) -> Result<(Vec<serde_json::Value>, Option<String>)> {
+    let variables = serde_json::json!({
+        "owner": config.repo_owner,
+        "name": config.repo_name,
+        "cursor": cursor,
+        "filterBy": {
+            "since": since.map(|since| since.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            "createdBy": config.creator,
+            "mentioned": config.mentioned,
+        },
+        "orderBy": order_by(config),
+    });
+    let mut data = config.github.graphql(ISSUES_QUERY, variables).await.context("Failed to list issues from GitHub")?;
+    let mut listing = data["repository"]["issues"].take();
+    let serde_json::Value::Array(nodes) = listing["nodes"].take() else {
+        return Err(anyhow::anyhow!("Unexpected response listing issues: {}", data));
+    };
+    let next = match listing["pageInfo"]["endCursor"].as_str() {
+        Some(end) if listing["pageInfo"]["hasNextPage"].as_bool() == Some(true) => Some(end.to_string()),
+        _ => None,
+    };
+    Ok((nodes, next))
+}
+
+/// The nodes in the shape of the REST API's issues. The query takes the
+/// first 100 labels and assignees; an issue with more is fetched over REST,
+/// so that pushing its file back doesn't drop the rest.
+pub async fn issues_from_nodes(config: &Config, nodes: &[serde_json::Value]) -> Result<Vec<types::Issue>> {
+    let mut issues = Vec::new();
+    for node in nodes {
+        let truncated = ["labels", "assignees"].iter().any(|field| node[field]["pageInfo"]["hasNextPage"] == true);
+        let issue = match node["number"].as_i64() {
+            Some(number) if truncated => {
+                config
+                    .github
+                    .with_rotation(|client| async move { client.issues().get(&config.repo_owner, &config.repo_name, number).await })
+                    .await
+                    .context(format!("Failed to get issue #{} from GitHub", number))?
+                    .body
+            }
+            _ => issue_from_node(node)?,
+        };
+        issues.push(issue);
+    }
+    Ok(issues)
+}
+
+/// Sets the `state_reason` of each closed issue from its GraphQL node,
+/// where it comes in capitals, e.g. `NOT_PLANNED`.
+pub fn fill_state_reasons(issues: &mut [Issue], nodes: &HashMap<i64, serde_json::Value>) {
+    for issue in issues.iter_mut().filter(|issue| issue.state.eq_ignore_ascii_case("closed")) {
+        issue.state_reason = nodes
+            .get(&issue.number)
+            .and_then(|node| node["stateReason"].as_str())
+            .map(str::to_lowercase);
+    }
+}
+
+// --sort and --order, as GraphQL spells them. Unset, issues are listed
+// oldest first, like the REST checkpoint does.
+fn order_by(config: &Config) -> serde_json::Value {
+    let field = match config.sort {
+        Some(types::IssuesListSort::Updated) => "UPDATED_AT",
+        Some(types::IssuesListSort::Comments) => "COMMENTS",
+        _ => "CREATED_AT",
+    };
+    let direction = match config.order {
+        Some(types::Order::Desc) => "DESC",
+        _ => "ASC",
+    };
+    serde_json::json!({ "field": field, "direction": direction })
+}
+
+// The REST API's names for the fields issue_from_remote reads
+fn issue_from_node(node: &serde_json::Value) -> Result<types::Issue> {
+    let names = |connection: &serde_json::Value, field: &str| -> Vec<serde_json::Value> {
+        connection["nodes"]
+            .as_array()
+            .map(|nodes| nodes.iter().map(|node| serde_json::json!({ field: node[field] })).collect())
+            .unwrap_or_default()
+    };
+    let issue = serde_json::json!({
+        "number": node["number"],
+        "title": node["title"],
+        "body": node["body"],
+        "state": node["state"].as_str().unwrap_or_default().to_lowercase(),
+        "html_url": node["url"],
+        "node_id": node["id"],
+        "locked": node["locked"],
+        "author_association": node["authorAssociation"],
+        "comments": node["comments"]["totalCount"],
+        "labels": names(&node["labels"], "name"),
+        "assignees": names(&node["assignees"], "login"),
+        "created_at": node["createdAt"],
+        "updated_at": node["updatedAt"],
+    });
+    serde_json::from_value(issue).context(format!("Failed to read issue {} from GitHub's response", node["number"]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use crate::{parse_markdown_file, sync_github_to_local};
+    use std::fs;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn node(number: i64, state: &str, reason: Option<&str>, labels: &[&str]) -> serde_json::Value {
+        serde_json::json!({
+            "number": number,
+            "title": format!("Issue {}", number),
+            "body": "Body",
+            "state": state,
+            "stateReason": reason,
+            "url": format!("https://github.com/owner/repo/issues/{}", number),
+            "id": format!("I_kwDOtest{}", number),
+            "locked": false,
+            "authorAssociation": "OWNER",
+            "createdAt": "2020-01-01T00:00:00Z",
+            "updatedAt": "2020-01-02T00:00:00Z",
+            "comments": { "totalCount": 2 },
+            "labels": { "nodes": labels.iter().map(|name| serde_json::json!({ "name": name })).collect::<Vec<_>>() },
+            "assignees": { "nodes": [{ "login": "octocat" }] },
+        })
+    }
+
+    fn page(nodes: Vec<serde_json::Value>, next: Option<&str>) -> serde_json::Value {
+        serde_json::json!({ "data": { "repository": { "issues": {
+            "pageInfo": { "hasNextPage": next.is_some(), "endCursor": next },
+            "nodes": nodes,
+        } } } })
+    }
+
+    #[tokio::test]
+    async fn test_graphql_pull_follows_the_cursor_and_keeps_state_reasons() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_partial_json(serde_json::json!({ "variables": { "cursor": null } })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![node(2, "CLOSED", Some("NOT_PLANNED"), &["bug"])], Some("c1"))))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_partial_json(serde_json::json!({ "variables": { "cursor": "c1" } })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![node(1, "OPEN", None, &[])], None)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.api = Api::Graphql;
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![1, 2]);
+
+        let (frontmatter, body) = parse_markdown_file(&fs::read_to_string(dir.path().join("issue-2.md")).unwrap()).unwrap();
+        assert_eq!(frontmatter["state"], "closed");
+        assert_eq!(frontmatter["state_reason"], "not_planned");
+        assert_eq!(frontmatter["labels"], "[bug]");
+        assert_eq!(frontmatter["assignees"], "[octocat]");
+        assert_eq!(frontmatter["comments"], "2");
+        assert_eq!(body, "Body");
+        // No REST listing, not even for the state reasons
+        assert!(server.received_requests().await.unwrap().iter().all(|request| request.method.as_str() == "POST"));
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_graphql_pull_resumes_from_the_saved_cursor() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_partial_json(serde_json::json!({ "variables": { "cursor": null } })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![node(1, "OPEN", None, &[])], Some("c1"))))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_partial_json(serde_json::json!({ "variables": { "cursor": "c1" } })))
+            .respond_with(ResponseTemplate::new(400))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_partial_json(serde_json::json!({ "variables": { "cursor": "c1" } })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![node(2, "OPEN", None, &[])], None)))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.api = Api::Graphql;
+        assert!(sync_github_to_local(&config, None).await.is_err());
+        // The first page isn't asked for again
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_issues_with_more_labels_than_the_query_takes_come_from_rest() {
+        let server = MockServer::start().await;
+        let mut crowded = node(1, "OPEN", None, &["label-1"]);
+        crowded["labels"]["pageInfo"] = serde_json::json!({ "hasNextPage": true });
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![crowded, node(2, "OPEN", None, &["bug"])], None)))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(crate::tests::issue_json(1, "Issue 1", "open", &["label-1", "label-101"], "Body")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.api = Api::Graphql;
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let labels = |number: i64| {
+            let content = fs::read_to_string(dir.path().join(format!("issue-{}.md", number))).unwrap();
+            parse_markdown_file(&content).unwrap().0["labels"].clone()
+        };
+        assert_eq!(labels(1), "[label-1, label-101]");
+        assert_eq!(labels(2), "[bug]");
+    }
+
+    #[tokio::test]
+    async fn test_graphql_errors_fail_the_pull() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": null,
+                "errors": [{ "message": "Could not resolve to a Repository with the name 'owner/repo'." }],
+            })))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.api = Api::Graphql;
+        let error = sync_github_to_local(&config, None).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("Could not resolve to a Repository"));
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::{attachments, milestones, Issue};
+
+/// The folder for issues without a matching label.
+pub const UNGROUPED: &str = "ungrouped";
+
+/// `--group-by label:PREFIX`: each issue's file goes in a folder named
+/// after its label that starts with PREFIX, with the prefix stripped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupBy {
+    prefix: String,
+}
+
+impl GroupBy {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.strip_prefix("label:") {
+            Some(prefix) if !prefix.is_empty() => Ok(GroupBy { prefix: prefix.to_string() }),
+            _ => Err(anyhow::anyhow!("Invalid --group-by value: {} (expected label:PREFIX, e.g. label:area:)", value)),
+        }
+    }
+
+    /// The folder for `issue`. With several matching labels, the first in
+    /// alphabetical order wins, so the choice doesn't depend on label order.
+    pub fn folder(&self, issue: &Issue) -> String {
+        let mut groups: Vec<&str> = issue
+            .labels
+            .iter()
+            .filter_map(|label| label.strip_prefix(&self.prefix))
+            .map(str::trim)
+            .filter(|group| !group.is_empty())
+            .collect();
+        groups.sort();
+        groups.first().map_or_else(|| UNGROUPED.to_string(), |group| folder_name(group))
+    }
+
+    pub fn path(&self, issues_dir: &Path, issue: &Issue) -> PathBuf {
+        issues_dir.join(self.folder(issue)).join(format!("issue-{}.md", issue.number))
+    }
+}
+
+impl fmt::Display for GroupBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "label:{}", self.prefix)
+    }
+}
+
+// A single path component that scans don't skip: separators and other
+// characters unsafe in file names become `-`, and names of hidden or
+// reserved folders get a `_` in front
+fn folder_name(group: &str) -> String {
+    let name: String = group
+        .chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '-' } else { c })
+        .collect();
+    if name.starts_with('.') || name == attachments::ASSETS_DIR || name == milestones::MILESTONES_DIR {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use crate::{sync_github_to_local, sync_local_to_github};
+    use std::fs;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_folders_come_from_the_prefixed_label() {
+        let group_by = GroupBy::parse("label:area:").unwrap();
+        let issue = |labels: &[&str]| -> Issue {
+            serde_json::from_value(serde_json::json!({
+                "number": 1, "title": "Issue", "state": "open", "labels": labels, "html_url": ""
+            }))
+            .unwrap()
+        };
+        assert_eq!(group_by.folder(&issue(&["bug", "area:ui"])), "ui");
+        assert_eq!(group_by.folder(&issue(&["area:web/api", "area:cli"])), "cli");
+        assert_eq!(group_by.folder(&issue(&["area:web/api"])), "web-api");
+        assert_eq!(group_by.folder(&issue(&["area:.git"])), "_.git");
+        assert_eq!(group_by.folder(&issue(&["bug", "area:"])), UNGROUPED);
+        assert!(GroupBy::parse("milestone").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_files_move_when_the_group_label_changes() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Button", "open", &["area:ui"], "Body"),
+                issue_json(2, "Stray", "open", &[], "Body"),
+            ])))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Button", "open", &["area:docs"], "Body"),
+                issue_json(2, "Stray", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Button", "open", &["area:docs"], "Edited")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.group_by = Some(GroupBy::parse("label:area:").unwrap());
+        sync_github_to_local(&config, None).await.unwrap();
+        assert!(dir.path().join("ui/issue-1.md").exists());
+        assert!(dir.path().join(UNGROUPED).join("issue-2.md").exists());
+
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.updated, vec![1]);
+        assert!(!dir.path().join("ui/issue-1.md").exists());
+        let moved = dir.path().join("docs/issue-1.md");
+        assert!(fs::read_to_string(&moved).unwrap().contains("labels: [\"area:docs\"]"));
+
+        // Pushes find the file in its folder
+        fs::write(&moved, fs::read_to_string(&moved).unwrap().replace("Body", "Edited")).unwrap();
+        assert_eq!(sync_local_to_github(&config, &moved).await.unwrap(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_links_between_grouped_files_are_relative_to_their_folder() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Button", "open", &["area:ui"], "See #2"),
+                issue_json(2, "Stray", "open", &[], "Blocks #1"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .and(body_partial_json(serde_json::json!({ "body": "See #2, edited" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Button", "open", &["area:ui"], "See #2, edited")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.group_by = Some(GroupBy::parse("label:area:").unwrap());
+        config.rewrite_refs = true;
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let first = dir.path().join("ui/issue-1.md");
+        let content = fs::read_to_string(&first).unwrap();
+        assert!(content.contains("See [#2](../ungrouped/issue-2.md)"));
+        let second = fs::read_to_string(dir.path().join(UNGROUPED).join("issue-2.md")).unwrap();
+        assert!(second.contains("Blocks [#1](../ui/issue-1.md)"));
+
+        // The links turn back into plain references on the way out
+        fs::write(&first, content.replace("issue-2.md)", "issue-2.md), edited")).unwrap();
+        assert_eq!(sync_local_to_github(&config, &first).await.unwrap(), Some(1));
+    }
+}
@@ -0,0 +1,71 @@
+use std::process::Command;
+
+use crate::{Config, SyncSummary};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    Pull,
+    Push,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Pull => "pull",
+            Direction::Push => "push",
+        }
+    }
+}
+
+/// Runs the `--post-sync-hook` command, if one is configured.
+///
+/// The command is run through the shell with the changed issue numbers in
+/// `RETASKS_CREATED` / `RETASKS_UPDATED` (space separated) and the sync
+/// direction in `RETASKS_DIRECTION`. A failing hook is only reported, so a
+/// broken hook never takes down the watcher.
+pub fn run_post_sync_hook(config: &Config, direction: Direction, summary: &SyncSummary) {
+    let Some(command) = &config.post_sync_hook else {
+        return;
+    };
+    if config.dry_run {
+        return;
+    }
+
+    let status = shell_command(command)
+        .env("RETASKS_CREATED", join_numbers(&summary.created))
+        .env("RETASKS_UPDATED", join_numbers(&summary.updated))
+        .env("RETASKS_DIRECTION", direction.as_str())
+        .status();
+
+    match status {
+        Ok(status) if status.success() => {
+            println!("Post-sync hook finished ({})", status);
+        }
+        Ok(status) => {
+            eprintln!("{}", config.colors.warning(&format!("Warning: post-sync hook failed ({})", status)));
+        }
+        Err(e) => {
+            eprintln!("{}", config.colors.warning(&format!("Warning: failed to run post-sync hook: {}", e)));
+        }
+    }
+}
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+fn join_numbers(numbers: &[i64]) -> String {
+    numbers
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
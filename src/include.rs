@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::{fingerprint, issue_from_file, normalize_body, parse_markdown_file, read_issue_text, Config};
+
+// `<!-- include: path.md -->`, alone on its line
+const INCLUDE_PATTERN: &str = r"^\s*<!--\s*include:\s*(.+?)\s*-->\s*$";
+
+/// Replaces each `<!-- include: path.md -->` line of `body` with the
+/// content of that file, for pushing. Paths are relative to the folder of
+/// `issue_file` and must stay within the issues directory. Lines inside
+/// fenced code blocks are left alone, and included files aren't expanded
+/// in turn.
+pub fn expand(config: &Config, issue_file: &Path, body: &str) -> Result<String> {
+    let pattern = Regex::new(INCLUDE_PATTERN).unwrap();
+    if !body.lines().any(|line| pattern.is_match(line)) {
+        return Ok(body.to_string());
+    }
+
+    let base = issue_file.parent().unwrap_or(&config.issues_dir);
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+        }
+        match pattern.captures(line).filter(|_| !in_code) {
+            Some(captures) => lines.push(read_included(config, base, &captures[1])?),
+            None => lines.push(line.to_string()),
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+/// The fingerprint of an issue file that has includes, taken with them
+/// inlined the way a push sends it, to compare with the issue on GitHub
+/// after a push. None for a file without includes, or one that can't be
+/// read as an issue.
+pub fn expanded_fingerprint(config: &Config, issue_file: &Path, number: i64, content: &str) -> Option<String> {
+    let (mut frontmatter, body) = parse_markdown_file(content).ok()?;
+    let expanded = expand(config, issue_file, &body).ok()?;
+    if expanded == body {
+        return None;
+    }
+    config.state_map.apply(&mut frontmatter).ok()?;
+    Some(fingerprint(config, &issue_from_file(number, &frontmatter, expanded).ok()?))
+}
+
+fn read_included(config: &Config, base: &Path, target: &str) -> Result<String> {
+    let issues_dir = config
+        .issues_dir
+        .canonicalize()
+        .context(format!("Failed to resolve {}", config.issues_dir.display()))?;
+    let path = base
+        .join(target)
+        .canonicalize()
+        .context(format!("Included file {} not found", target))?;
+    if Path::new(target).is_absolute() || !path.starts_with(&issues_dir) {
+        return Err(anyhow::anyhow!("Included file {} is outside the issues directory", target));
+    }
+    let content = read_issue_text(config, &path).context(format!("Failed to read included file {}", path.display()))?;
+    Ok(normalize_body(&content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sync_github_to_local, sync_local_to_github};
+    use crate::tests::{issue_json, test_config};
+    use std::fs;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_includes_are_inlined_on_push_and_kept_inside_the_issues_dir() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Design", "open", &[], "Old")))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .and(body_partial_json(serde_json::json!({
+                "body": "Summary\n\n## Details\n\nLong content\n\n```\n<!-- include: details.md -->\n```"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Design", "open", &[], "Summary")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        fs::create_dir(dir.path().join(".includes")).unwrap();
+        fs::write(dir.path().join(".includes/details.md"), "## Details\r\n\r\nLong content\r\n").unwrap();
+        let file = dir.path().join("issue-1.md");
+        fs::write(
+            &file,
+            "---\nnumber: 1\ntitle: Design\nstate: open\n---\n\nSummary\n\n<!-- include: .includes/details.md -->\n\n```\n<!-- include: details.md -->\n```",
+        )
+        .unwrap();
+        assert_eq!(sync_local_to_github(&config, &file).await.unwrap(), Some(1));
+
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret.md"), "Secret").unwrap();
+        let traversal = format!("<!-- include: ../{}/secret.md -->", outside.path().file_name().unwrap().to_string_lossy());
+        let error = expand(&config, &file, &traversal).unwrap_err();
+        assert!(error.to_string().ends_with("is outside the issues directory"), "{}", error);
+        assert!(expand(&config, &file, "<!-- include: missing.md -->").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_include_directives_survive_the_pull_after_a_push() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Design", "open", &[], "Old")))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Design", "open", &[], "Summary\n\nDetails")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([issue_json(1, "Design", "open", &[], "Summary\n\nDetails")])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        fs::write(dir.path().join("details.txt"), "Details\n").unwrap();
+        let file = dir.path().join("issue-1.md");
+        let content = "---\nnumber: 1\ntitle: Design\nstate: open\nlabels: []\nassignees: []\n---\n\nSummary\n\n<!-- include: details.txt -->";
+        fs::write(&file, content).unwrap();
+        assert_eq!(sync_local_to_github(&config, &file).await.unwrap(), Some(1));
+
+        // GitHub now has the inlined text, which the pull mustn't write over the directive
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert!(summary.updated.is_empty());
+        assert_eq!(fs::read_to_string(&file).unwrap(), content);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{check_issues_dir, create_issues_dir};
+
+/// Where `init` writes the project config, and where it is looked for.
+pub const CONFIG_FILE: &str = "retasks.toml";
+
+/// The settings `init` saves, read on every run so the usual flags can be
+/// left off. Flags given on the command line still win.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issues_dir: Option<PathBuf>,
+}
+
+/// What `init` was given on the command line; anything missing is asked for.
+pub struct InitOptions {
+    pub path: PathBuf,
+    pub repo: Option<String>,
+    pub token: Option<String>,
+    pub token_file: Option<PathBuf>,
+    pub issues_dir: PathBuf,
+    pub force: bool,
+}
+
+/// Reads the project config. An explicit `--config` path must exist;
+/// without one, a missing `retasks.toml` just means no saved settings.
+pub fn load(path: Option<&Path>) -> Result<ProjectConfig> {
+    let (path, required) = match path {
+        Some(path) => (path, true),
+        None => (Path::new(CONFIG_FILE), false),
+    };
+    if !required && !path.exists() {
+        return Ok(ProjectConfig::default());
+    }
+
+    let content = fs::read_to_string(path).context(format!("Failed to read config: {}", path.display()))?;
+    let mut value: toml::Value = toml::from_str(&content).context(format!("Failed to parse config: {}", path.display()))?;
+    interpolate_strings(&mut value).context(format!("Failed to load config: {}", path.display()))?;
+    value.try_into().context(format!("Failed to parse config: {}", path.display()))
+}
+
+// Expands `${VAR}` in every string value, after parsing so that a value
+// containing quotes can't change the TOML around it
+fn interpolate_strings(value: &mut toml::Value) -> Result<()> {
+    match value {
+        toml::Value::String(s) => *s = interpolate(s)?,
+        toml::Value::Array(items) => items.iter_mut().try_for_each(interpolate_strings)?,
+        toml::Value::Table(table) => table.iter_mut().try_for_each(|(_, value)| interpolate_strings(value))?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Replaces each `${VAR}` in `value` with that environment variable, so a
+/// committed config can refer to secrets kept in the environment. `$$`
+/// stands for a literal `$`. An unset variable is an error.
+fn interpolate(value: &str) -> Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(index) = rest.find('$') {
+        result.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after.find('}').ok_or_else(|| anyhow::anyhow!("Unclosed ${{ in {:?}", value))?;
+            let name = &after[..end];
+            if name.is_empty() {
+                return Err(anyhow::anyhow!("Empty ${{}} in {:?}", value));
+            }
+            let expanded = std::env::var(name)
+                .map_err(|_| anyhow::anyhow!("Environment variable {} is not set (referenced as ${{{}}})", name, name))?;
+            result.push_str(&expanded);
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Writes the project config and creates the issues directory. Refuses to
+/// replace an existing config unless `force` is set.
+pub fn init(options: &InitOptions) -> Result<ProjectConfig> {
+    if options.path.exists() && !options.force {
+        return Err(anyhow::anyhow!("{} already exists; pass --force to overwrite it", options.path.display()));
+    }
+    check_issues_dir(&options.issues_dir)?;
+
+    let repo = match &options.repo {
+        Some(repo) => repo.clone(),
+        None => prompt("GitHub repository (owner/repo)")?,
+    };
+    if repo.split('/').count() != 2 || repo.split('/').any(str::is_empty) {
+        return Err(anyhow::anyhow!("Repository must be in format owner/repo"));
+    }
+
+    // A token file is referenced rather than copied into the config
+    let (token, token_file) = match (&options.token, &options.token_file) {
+        (_, Some(path)) => (None, Some(path.clone())),
+        (Some(token), None) => (Some(token.clone()), None),
+        (None, None) => (Some(prompt("GitHub token")?), None),
+    };
+
+    let config = ProjectConfig {
+        repo: Some(repo),
+        token,
+        token_file,
+        issues_dir: Some(options.issues_dir.clone()),
+    };
+    write_config(&options.path, &config)?;
+    println!("Wrote {}", options.path.display());
+
+    create_issues_dir(&options.issues_dir)?;
+    println!("Issues will be synced to {}", options.issues_dir.display());
+    if config.token.is_some() {
+        println!("{} holds your token; keep it out of version control.", options.path.display());
+    }
+
+    Ok(config)
+}
+
+fn write_config(path: &Path, config: &ProjectConfig) -> Result<()> {
+    let content = toml::to_string(config).context("Failed to serialize config")?;
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Only the owner may read a file holding a token
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path).context(format!("Failed to create {}", path.display()))?;
+    file.write_all(content.as_bytes()).context(format!("Failed to write {}", path.display()))
+}
+
+fn prompt(label: &str) -> Result<String> {
+    if !io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!("{} is required; pass it as a flag when not running interactively", label));
+    }
+
+    print!("{}: ", label);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).context("Failed to read from stdin")?;
+
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Err(anyhow::anyhow!("{} is required", label));
+    }
+    Ok(answer.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_writes_config_and_refuses_to_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut options = InitOptions {
+            path: dir.path().join(CONFIG_FILE),
+            repo: Some("owner/repo".to_string()),
+            token: Some("secret".to_string()),
+            token_file: None,
+            issues_dir: dir.path().join("issues"),
+            force: false,
+        };
+
+        let written = init(&options).unwrap();
+        assert!(dir.path().join("issues").is_dir());
+        assert_eq!(load(Some(&options.path)).unwrap(), written);
+        assert_eq!(written.repo.as_deref(), Some("owner/repo"));
+
+        options.repo = Some("owner/other".to_string());
+        assert!(init(&options).unwrap_err().to_string().contains("--force"));
+        assert_eq!(load(Some(&options.path)).unwrap().repo.as_deref(), Some("owner/repo"));
+
+        options.force = true;
+        assert_eq!(init(&options).unwrap().repo.as_deref(), Some("owner/other"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&options.path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_environment_variables_are_interpolated_on_load() {
+        std::env::set_var("RETASKS_TEST_TOKEN", "from-env");
+        std::env::remove_var("RETASKS_TEST_UNSET");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+
+        fs::write(&path, "repo = \"owner/repo\"\ntoken = \"${RETASKS_TEST_TOKEN}\"\nissues_dir = \"$$HOME/${RETASKS_TEST_TOKEN}\"\n").unwrap();
+        let config = load(Some(&path)).unwrap();
+        assert_eq!(config.token.as_deref(), Some("from-env"));
+        assert_eq!(config.issues_dir, Some(PathBuf::from("$HOME/from-env")));
+
+        fs::write(&path, "token = \"${RETASKS_TEST_UNSET}\"\n").unwrap();
+        let error = format!("{:#}", load(Some(&path)).unwrap_err());
+        assert!(error.contains("RETASKS_TEST_UNSET is not set"), "{}", error);
+    }
+}
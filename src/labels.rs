@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use octorust::types;
+
+use crate::Config;
+
+/// GitHub rejects longer label names.
+pub const MAX_LENGTH: usize = 50;
+
+/// Color of labels created by `--create-missing-labels`, GitHub's default grey.
+const NEW_LABEL_COLOR: &str = "ededed";
+
+/// The repository's label names, lowercased, fetched on the first push
+/// that sets labels and kept for the rest of the run. A label missing from
+/// it is looked up again before it is dropped, as it may have been added
+/// on GitHub since.
+#[derive(Default)]
+pub struct KnownLabels(Mutex<Option<HashSet<String>>>);
+
+/// Why GitHub would reject `name`, or None if it is a valid label name.
+pub fn invalid_reason(name: &str) -> Option<String> {
+    if name.chars().count() > MAX_LENGTH {
+        Some(format!("longer than {} characters", MAX_LENGTH))
+    } else if name.chars().any(char::is_control) {
+        Some("contains control characters".to_string())
+    } else {
+        None
+    }
+}
+
+/// Drops the labels of `update` that GitHub would reject: invalid names,
+/// and labels the repository doesn't have unless `--create-missing-labels`
+/// creates them. The rest of the update still goes through. Each skipped
+/// label is reported with the issue number, and the skipped names are
+/// returned.
+pub async fn prepare(config: &Config, number: i64, update: &mut types::IssuesUpdateRequest) -> Vec<String> {
+    if update.labels.is_empty() {
+        return Vec::new();
+    }
+
+    let cached = config.known_labels.0.lock().unwrap().clone();
+    let known = match cached {
+        Some(known) if update.labels.iter().all(|label| is_known(&known, label)) => Ok(known),
+        _ => fetch_known_labels(config).await,
+    };
+    let known = match known {
+        Ok(known) => Some(known),
+        Err(e) => {
+            // Without the list, GitHub gets to decide
+            eprintln!("{}", config.colors.warning(&format!("Warning: failed to list the repository's labels: {:#}", e)));
+            None
+        }
+    };
+
+    let mut skipped = Vec::new();
+    let mut kept = Vec::new();
+    for label in update.labels.drain(..) {
+        let name = match &label {
+            types::IssuesCreateRequestLabelsOneOf::String(name) => name.clone(),
+            _ => {
+                kept.push(label);
+                continue;
+            }
+        };
+
+        let reason = match invalid_reason(&name) {
+            Some(reason) => Some(reason),
+            None if known.as_ref().is_none_or(|known| known.contains(&name.to_lowercase())) => None,
+            None if !config.create_missing_labels => {
+                Some("does not exist in the repository; pass --create-missing-labels to create it".to_string())
+            }
+            None if config.dry_run => {
+                println!("Would create label {:?}", name);
+                None
+            }
+            None => create_label(config, &name).await.err().map(|e| format!("could not be created: {}", e)),
+        };
+        match reason {
+            Some(reason) => skipped.push((name, reason)),
+            None => kept.push(label),
+        }
+    }
+    update.labels = kept;
+
+    for (name, reason) in &skipped {
+        eprintln!("{}", config.colors.warning(&format!("Issue #{}: skipping label {:?}: {}", number, name, reason)));
+    }
+    skipped.into_iter().map(|(name, _)| name).collect()
+}
+
+// Labels given as objects, and invalid names, are not checked against the list
+fn is_known(known: &HashSet<String>, label: &types::IssuesCreateRequestLabelsOneOf) -> bool {
+    match label {
+        types::IssuesCreateRequestLabelsOneOf::String(name) => invalid_reason(name).is_some() || known.contains(&name.to_lowercase()),
+        _ => true,
+    }
+}
+
+async fn fetch_known_labels(config: &Config) -> Result<HashSet<String>> {
+    let labels = config
+        .github
+        .with_rotation(|client| async move {
+            client.issues().list_all_labels_for_repo(&config.repo_owner, &config.repo_name).await
+        })
+        .await?
+        .body;
+    let known: HashSet<String> = labels.into_iter().map(|label| label.name.to_lowercase()).collect();
+    *config.known_labels.0.lock().unwrap() = Some(known.clone());
+    Ok(known)
+}
+
+async fn create_label(config: &Config, name: &str) -> Result<()> {
+    let request = &types::IssuesCreateLabelRequest {
+        name: name.to_string(),
+        color: NEW_LABEL_COLOR.to_string(),
+        description: String::new(),
+    };
+    config
+        .github
+        .with_rotation(|client| async move { client.issues().create_label(&config.repo_owner, &config.repo_name, request).await })
+        .await?;
+
+    println!("Created label {:?}", name);
+    if let Some(known) = config.known_labels.0.lock().unwrap().as_mut() {
+        known.insert(name.to_lowercase());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn update_with_labels(labels: &[&str]) -> types::IssuesUpdateRequest {
+        types::IssuesUpdateRequest {
+            title: None,
+            body: String::new(),
+            state: None,
+            assignee: String::new(),
+            assignees: vec![],
+            milestone: None,
+            labels: labels.iter().map(|name| types::IssuesCreateRequestLabelsOneOf::String(name.to_string())).collect(),
+        }
+    }
+
+    fn names(update: &types::IssuesUpdateRequest) -> Vec<String> {
+        serde_json::from_value(serde_json::to_value(&update.labels).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_invalid_and_missing_labels_are_skipped_or_created() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/labels"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([{ "name": "Bug" }])))
+            // Again for the second update, whose missing label may be new on GitHub
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/owner/repo/labels"))
+            .and(body_partial_json(serde_json::json!({ "name": "feature" })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({ "name": "feature" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        let too_long = "x".repeat(MAX_LENGTH + 1);
+
+        let mut update = update_with_labels(&["bug", "feature", &too_long]);
+        let skipped = prepare(&config, 1, &mut update).await;
+        assert_eq!(skipped, vec!["feature".to_string(), too_long.clone()]);
+        assert_eq!(names(&update), vec!["bug"]);
+
+        config.create_missing_labels = true;
+        let mut update = update_with_labels(&["bug", "feature", "tab\there"]);
+        let skipped = prepare(&config, 1, &mut update).await;
+        assert_eq!(skipped, vec!["tab\there".to_string()]);
+        assert_eq!(names(&update), vec!["bug", "feature"]);
+
+        // Once created, the label is known and not created again
+        let mut update = update_with_labels(&["feature"]);
+        assert!(prepare(&config, 2, &mut update).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_labels_added_on_github_during_the_run_are_kept() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/labels"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([{ "name": "bug" }])))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/labels"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([{ "name": "bug" }, { "name": "docs" }])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        let mut update = update_with_labels(&["bug"]);
+        assert!(prepare(&config, 1, &mut update).await.is_empty());
+
+        let mut update = update_with_labels(&["bug", "docs"]);
+        assert!(prepare(&config, 2, &mut update).await.is_empty());
+        assert_eq!(names(&update), vec!["bug", "docs"]);
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// One lock per issue number, so a pull and a push of the same issue never
+/// interleave: a push holds its issue's lock from reading the file until the
+/// state is updated, and a pull holds the locks of the issues it writes while
+/// writing them. Different issues don't wait for each other.
+#[derive(Default)]
+pub struct IssueLocks(Mutex<HashMap<i64, Arc<AsyncMutex<()>>>>);
+
+impl IssueLocks {
+    /// Waits for the lock of issue `number`, held until the guard is dropped.
+    pub async fn lock(&self, number: i64) -> OwnedMutexGuard<()> {
+        let lock = self.0.lock().unwrap().entry(number).or_default().clone();
+        lock.lock_owned().await
+    }
+
+    /// Waits for the locks of all of `numbers`. They are taken in ascending
+    /// order, so two callers locking overlapping sets can't deadlock.
+    pub async fn lock_all(&self, numbers: impl IntoIterator<Item = i64>) -> Vec<OwnedMutexGuard<()>> {
+        let mut numbers: Vec<i64> = numbers.into_iter().collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+
+        let mut guards = Vec::with_capacity(numbers.len());
+        for number in numbers {
+            guards.push(self.lock(number).await);
+        }
+        guards
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use crate::{parse_markdown_file, sync_github_to_local, sync_local_to_github, write_atomically};
+    use std::fs;
+    use std::path::Path;
+    use std::time::Duration;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    fn set_body(path: &Path, body: &str) {
+        let content = fs::read_to_string(path).unwrap();
+        let (frontmatter, _) = content.split_once("\n---\n\n").unwrap();
+        write_atomically(path, format!("{}\n---\n\n{}", frontmatter, body).as_bytes()).unwrap();
+    }
+
+    // Until `count` tasks hold or wait for the lock of issue `number`: each
+    // keeps a reference to it, as do the map and this function
+    async fn wait_for_lockers(locks: &IssueLocks, number: i64, count: usize) {
+        let lock = locks.0.lock().unwrap()[&number].clone();
+        let waiting = async {
+            while Arc::strong_count(&lock) < count + 2 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(30), waiting).await.expect("tasks never reached the lock");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_pull_and_push_of_one_issue_are_serialized() {
+        // GitHub as a map of bodies: listings return them, slow updates change them
+        let bodies = Arc::new(Mutex::new(HashMap::from([(1, "Body".to_string()), (2, "Body".to_string())])));
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with({
+                let bodies = bodies.clone();
+                move |_: &Request| {
+                    let bodies = bodies.lock().unwrap();
+                    let issues: Vec<_> = [1, 2].iter().map(|n| issue_json(*n, "Issue", "open", &[], &bodies[n])).collect();
+                    ResponseTemplate::new(200).set_body_json(issues)
+                }
+            })
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .respond_with({
+                let bodies = bodies.clone();
+                move |request: &Request| {
+                    let number: i64 = request.url.path().rsplit('/').next().unwrap().parse().unwrap();
+                    let body = request.body_json::<serde_json::Value>().unwrap()["body"].as_str().unwrap().to_string();
+                    bodies.lock().unwrap().insert(number, body.clone());
+                    ResponseTemplate::new(200)
+                        .set_body_json(issue_json(number, "Issue", "open", &[], &body))
+                        .set_delay(Duration::from_millis(300))
+                }
+            })
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = Arc::new(test_config(&server, dir.path()));
+        sync_github_to_local(&config, None).await.unwrap();
+        let paths = [dir.path().join("issue-1.md"), dir.path().join("issue-2.md")];
+
+        // A pull started while issue 1 is being pushed waits for the push, and
+        // leaves the pushed file alone
+        set_body(&paths[0], "Edited");
+        let guard = config.issue_locks.lock(1).await;
+        let push = tokio::spawn({
+            let config = config.clone();
+            let path = paths[0].clone();
+            async move { sync_local_to_github(&config, &path).await }
+        });
+        wait_for_lockers(&config.issue_locks, 1, 2).await;
+        let pull = tokio::spawn({
+            let config = config.clone();
+            async move { sync_github_to_local(&config, None).await }
+        });
+        wait_for_lockers(&config.issue_locks, 1, 3).await;
+        assert!(!pull.is_finished() && !push.is_finished());
+        drop(guard);
+        assert_eq!(push.await.unwrap().unwrap(), Some(1));
+        pull.await.unwrap().unwrap();
+        assert!(fs::read_to_string(&paths[0]).unwrap().ends_with("\n\nEdited"));
+
+        // Pushes of different issues don't wait for each other: issue 2 goes
+        // through while issue 1's lock is held
+        set_body(&paths[0], "Again");
+        set_body(&paths[1], "Again");
+        let guard = config.issue_locks.lock(1).await;
+        let blocked = tokio::spawn({
+            let config = config.clone();
+            let path = paths[0].clone();
+            async move { sync_local_to_github(&config, &path).await }
+        });
+        assert_eq!(sync_local_to_github(&config, &paths[1]).await.unwrap(), Some(2));
+        assert!(!blocked.is_finished());
+        drop(guard);
+        assert_eq!(blocked.await.unwrap().unwrap(), Some(1));
+
+        // Pulls one after another while pushes of the same issue keep coming
+        // all succeed and leave a well-formed file behind
+        let pulls = tokio::spawn({
+            let config = config.clone();
+            async move {
+                for _ in 0..10 {
+                    sync_github_to_local(&config, None).await?;
+                }
+                anyhow::Ok(())
+            }
+        });
+        let pushes = (0..10).map(|i| {
+            let config = config.clone();
+            let path = paths[0].clone();
+            tokio::spawn(async move {
+                set_body(&path, &format!("Round {}", i));
+                sync_local_to_github(&config, &path).await
+            })
+        });
+        for push in futures::future::join_all(pushes).await {
+            push.unwrap().unwrap();
+        }
+        pulls.await.unwrap().unwrap();
+        let (frontmatter, _) = parse_markdown_file(&fs::read_to_string(&paths[0]).unwrap()).unwrap();
+        assert_eq!(frontmatter["number"], "1");
+    }
+}
@@ -1,33 +1,232 @@
+// serde_json::json! recurses once per key, and dump_config has many
+#![recursion_limit = "256"]
+
 use anyhow::{Context, Result};
-use clap::{App, Arg};
-use hotwatch::{Hotwatch, Event};
-use octorust::{auth::Credentials, Client, types};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use futures::StreamExt;
+use octorust::types;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use tokio::runtime::Runtime;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+mod attachments;
+mod bulk;
+mod cache;
+mod check;
+mod checkpoint;
+mod color;
+mod doctor;
+mod error;
+mod export;
+mod fields;
+mod format;
+mod frontmatter;
+mod git;
+mod github;
+mod graphql;
+mod group;
+mod hook;
+mod include;
+mod init;
+mod labels;
+mod lock;
+mod markers;
+mod milestones;
+mod notify;
+mod participants;
+mod patch;
+mod plan;
+mod progress;
+mod raw;
+mod refs;
+mod repo_config;
+mod report;
+mod schema;
+mod service;
+mod sidecar;
+mod since_file;
+mod source;
+mod stage;
+mod state;
+mod state_map;
+mod stdin;
+mod tasks;
+mod template;
+mod timeline;
+mod timezone;
+mod wrap;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 struct Issue {
     number: i64,
     title: String,
     body: Option<String>,
     state: String,
+    /// Why a closed issue was closed: `completed` or `not_planned`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    state_reason: Option<String>,
     labels: Vec<String>,
+    #[serde(default)]
+    assignees: Vec<String>,
+    html_url: String,
+    /// GitHub's global node ID, which the GraphQL API keys on.
+    #[serde(default)]
+    node_id: String,
+    /// Whether the conversation is locked to collaborators.
+    #[serde(default)]
+    locked: bool,
+    /// How many comments the issue has.
+    #[serde(default)]
+    comments: i64,
+    /// Logins of the commenters, with --fetch-participants.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    participants: Option<Vec<String>>,
+    /// URLs of the images and attached files in the body, with --list-attachments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attachments: Option<Vec<String>>,
+    /// Checked and total task list items in the body, with --track-tasks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tasks: Option<tasks::Tasks>,
+    /// When the issue was opened and last updated, in the `--timezone` zone.
+    #[serde(default)]
+    created_at: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    updated_at: Option<DateTime<FixedOffset>>,
 }
 
 struct Config {
-    token: String,
+    github: github::GitHub,
     repo_owner: String,
     repo_name: String,
     issues_dir: PathBuf,
     watch: bool,
+    /// In watch mode, pull on a timer (off with --no-periodic-sync).
+    periodic_sync: bool,
+    /// In watch mode, push local file changes (off with --no-watch-files).
+    watch_files: bool,
     sync_interval: Duration,
+    io_retries: u32,
+    post_sync_hook: Option<String>,
+    /// Webhook that gets a JSON summary of each sync.
+    notify: Option<notify::Notifier>,
+    /// Where a record of every sync is appended (`--report-file`).
+    report: Option<report::Report>,
+    colors: color::Colors,
+    prune: bool,
+    /// Before pruning a file, check that its issue is really gone.
+    safe_prune: bool,
+    only: Option<i64>,
+    /// Only list issues opened by this login (`--creator`).
+    creator: Option<String>,
+    /// Only list issues that mention this login (`--mentioned`).
+    mentioned: Option<String>,
+    /// What listings sort by and in which direction (`--sort`, `--order`).
+    /// Unset, each listing keeps its own default, and pulls write issues by
+    /// ascending number.
+    sort: Option<types::IssuesListSort>,
+    order: Option<types::Order>,
+    /// The zone frontmatter timestamps are written in (`--timezone`).
+    timezone: timezone::Timezone,
+    /// Which API full pulls list issues with (`--api`).
+    api: graphql::Api,
+    /// Only pull issues numbered from `--number-min` to `--number-max`.
+    number_min: Option<i64>,
+    number_max: Option<i64>,
+    dry_run: bool,
+    full_resync: bool,
+    /// File holding the sync cursor, owned by whatever runs retasks; used
+    /// instead of the state file's for the first pull and updated after each.
+    since_file: Option<PathBuf>,
+    state: state::StateStore,
+    format: format::Format,
+    raw_body: bool,
+    download_attachments: bool,
+    conflict_strategy: plan::ConflictStrategy,
+    /// Push local edits made while retasks wasn't running before the first pull.
+    push_on_startup: bool,
+    git_commit: Option<git::GitCommit>,
+    stage_dir: Option<PathBuf>,
+    rewrite_refs: bool,
+    /// Create issues with only their title, labels and assignees, leaving
+    /// the body to be written on GitHub.
+    no_body_on_create: bool,
+    /// Encoding to read local files in when they aren't valid UTF-8.
+    input_encoding: Option<&'static encoding_rs::Encoding>,
+    /// How many files the `push` subcommand sends at once.
+    push_concurrency: usize,
+    /// How many issues' comments a pull lists at once.
+    comment_concurrency: usize,
+    /// Column to hard-wrap bodies at in the local files.
+    wrap_body: Option<usize>,
+    /// Append each issue's event history to its file on pull.
+    include_timeline: bool,
+    /// List each issue's comments on pull to write its participants.
+    fetch_participants: bool,
+    /// Write the URLs of the attachments in each body to its frontmatter on pull.
+    list_attachments: bool,
+    /// Write a summary of each body's task lists to its frontmatter on pull.
+    track_tasks: bool,
+    /// Pushes of more changed files than this need --confirm-bulk.
+    bulk_max_files: usize,
+    /// Likewise for more than this percentage of the issue files.
+    bulk_max_percent: Option<u32>,
+    /// Push however many files changed.
+    confirm_bulk: bool,
+    /// Folders issue files are sorted into by label, with --group-by.
+    group_by: Option<group::GroupBy>,
+    /// Write each pulled issue's API JSON to `.raw/` for debugging.
+    keep_raw: bool,
+    /// Mirror milestones to `milestones/` on pull and push their edits.
+    sync_milestones: bool,
+    /// Compare bodies ignoring trailing spaces and blank-line counts.
+    ignore_whitespace: bool,
+    /// The managed fields that pushes send.
+    push_fields: fields::PushFields,
+    /// Whether issue files keep their frontmatter inline or in a sidecar.
+    metadata: sidecar::Metadata,
+    /// Create labels the repository doesn't have instead of skipping them.
+    create_missing_labels: bool,
+    known_labels: labels::KnownLabels,
+    /// Custom `state` values standing for a GitHub state plus labels.
+    state_map: state_map::StateMap,
+    /// Keeps a pull and a push of the same issue from interleaving.
+    issue_locks: lock::IssueLocks,
+    /// Only pull issues assigned to this login, or `@me`.
+    assignee: Option<String>,
+    /// Never push files of closed issues.
+    readonly_closed: bool,
+    /// Use the cached issues and queue pushes while GitHub is unreachable.
+    offline_fallback: bool,
+    /// How long the startup pull may take before it is given up.
+    initial_sync_timeout: Option<Duration>,
+}
+
+/// What a sync changed, reported to the post-sync hook.
+#[derive(Debug, Default)]
+struct SyncSummary {
+    created: Vec<i64>,
+    updated: Vec<i64>,
+    pruned: Vec<i64>,
+    /// The files written or removed for each issue, for per-issue git commits.
+    files: HashMap<i64, Vec<PathBuf>>,
+    /// Titles of the created and updated issues.
+    titles: HashMap<i64, String>,
+}
+
+/// A markdown file found in the issues directory.
+struct LocalFile {
+    path: PathBuf,
+    /// The `number` frontmatter key, or None for files retasks didn't create.
+    number: Option<i64>,
 }
 
 fn main() -> Result<()> {
@@ -36,80 +235,409 @@ fn main() -> Result<()> {
 
     let matches = get_app_args();
 
-    let repo_parts: Vec<&str> = matches
+    // With --pull, init goes on to the first pull with the config it wrote
+    if let ("init", Some(sub)) = matches.subcommand() {
+        init::init(&init::InitOptions {
+            path: PathBuf::from(matches.value_of("config").unwrap_or(init::CONFIG_FILE)),
+            repo: matches.value_of("repo").map(|s| s.to_string()),
+            token: match matches.values_of("token").map(|v| v.collect::<Vec<_>>()) {
+                Some(tokens) if tokens.len() > 1 => {
+                    return Err(anyhow::anyhow!("init saves a single --token; use --token-file for several"))
+                }
+                tokens => tokens.and_then(|t| t.first().map(|t| t.to_string())),
+            },
+            token_file: matches.value_of("token-file").map(PathBuf::from),
+            issues_dir: PathBuf::from(matches.value_of("issues-dir").unwrap_or("./issues")),
+            force: sub.is_present("force"),
+        })?;
+        if !sub.is_present("pull") {
+            return Ok(());
+        }
+    }
+
+    let project = init::load(matches.value_of("config").map(Path::new))?;
+    let repo = matches
         .value_of("repo")
-        .unwrap()
-        .split('/')
-        .collect();
+        .map(|s| s.to_string())
+        .or_else(|| project.repo.clone())
+        .ok_or_else(|| anyhow::anyhow!("--repo is required (or run `retasks init` to save it)"))?;
+
+    let repo_parts: Vec<&str> = repo.split('/').collect();
     
     if repo_parts.len() != 2 {
         return Err(anyhow::anyhow!("Repository must be in format owner/repo"));
     }
 
+    // Command line flags win over retasks.toml, then the repo config file, then the defaults
+    let settings = match matches.value_of("repo-config") {
+        Some(path) => repo_config::load(Path::new(path), &repo)?,
+        None => repo_config::Settings::default(),
+    };
+
+    let issues_dir = matches
+        .value_of("issues-dir")
+        .map(PathBuf::from)
+        .or(project.issues_dir.clone())
+        .or(settings.issues_dir)
+        .unwrap_or_else(|| PathBuf::from("./issues"));
+    check_issues_dir(&issues_dir)?;
+
     let config = Config {
-        token: matches.value_of("token").unwrap().to_string(),
+        github: {
+            let mut github = github::GitHub::new(
+                collect_tokens(&matches, &project)?,
+                matches.value_of("api-url"),
+                github::parse_headers(matches.values_of("header").into_iter().flatten())?,
+            )?;
+            if let Some(retries) = matches.value_of("server-error-retries") {
+                github.set_server_error_retries(
+                    retries.parse().map_err(|_| anyhow::anyhow!("Invalid --server-error-retries: {}", retries))?,
+                );
+            }
+            github
+        },
         repo_owner: repo_parts[0].to_string(),
         repo_name: repo_parts[1].to_string(),
-        issues_dir: PathBuf::from(matches.value_of("issues-dir").unwrap_or("./issues")),
+        state: state::StateStore::load(&issues_dir)?,
+        issues_dir,
         watch: matches.is_present("watch"),
+        periodic_sync: !matches.is_present("no-periodic-sync"),
+        watch_files: !matches.is_present("no-watch-files"),
         sync_interval: Duration::from_secs(
             matches
                 .value_of("interval")
-                .unwrap_or("300")
-                .parse()
+                .and_then(|interval| interval.parse().ok())
+                .or(settings.interval)
                 .unwrap_or(300),
         ),
+        io_retries: match matches.value_of("io-retries").unwrap_or("3").parse() {
+            Ok(n) if n <= MAX_IO_RETRIES => n,
+            _ => return Err(anyhow::anyhow!("--io-retries must be a number from 0 to {}", MAX_IO_RETRIES)),
+        },
+        post_sync_hook: matches.value_of("post-sync-hook").map(|s| s.to_string()),
+        notify: matches.value_of("notify").map(notify::Notifier::new),
+        report: matches
+            .value_of("report-file")
+            .map(|path| report::Report::new(Path::new(path), matches.value_of("report-format"), matches.value_of("report-max-size")))
+            .transpose()?,
+        colors: color::Colors::new(color::ColorChoice::parse(matches.value_of("color").unwrap_or("auto"))?),
+        prune: matches.is_present("prune") || settings.prune.unwrap_or(false),
+        safe_prune: matches.is_present("safe-prune"),
+        only: matches
+            .value_of("only")
+            .map(|n| n.parse::<i64>().map_err(|_| anyhow::anyhow!("Invalid issue number for --only: {}", n)))
+            .transpose()?,
+        creator: login_arg(&matches, "creator")?,
+        mentioned: login_arg(&matches, "mentioned")?,
+        sort: matches.value_of("sort").map(|sort| match sort {
+            "updated" => types::IssuesListSort::Updated,
+            "comments" => types::IssuesListSort::Comments,
+            _ => types::IssuesListSort::Created,
+        }),
+        order: matches.value_of("order").map(|order| if order == "asc" { types::Order::Asc } else { types::Order::Desc }),
+        timezone: timezone::Timezone::parse(matches.value_of("timezone").unwrap_or("UTC"))?,
+        api: graphql::Api::parse(matches.value_of("api").unwrap_or("rest"))?,
+        number_min: number_bound(&matches, "number-min")?,
+        number_max: number_bound(&matches, "number-max")?,
+        dry_run: matches.is_present("dry-run"),
+        full_resync: matches.is_present("full-resync"),
+        since_file: matches.value_of("since-file").map(PathBuf::from),
+        format: format::Format::parse(matches.value_of("format").or(settings.format.as_deref()).unwrap_or("markdown"))?,
+        raw_body: matches.is_present("raw-body"),
+        download_attachments: matches.is_present("download-attachments") || settings.download_attachments.unwrap_or(false),
+        conflict_strategy: plan::ConflictStrategy::parse(
+            matches.value_of("conflict-strategy").or(settings.conflict_strategy.as_deref()).unwrap_or("remote-wins"),
+        )?,
+        push_on_startup: matches.is_present("push-on-startup"),
+        git_commit: matches
+            .is_present("git-commit")
+            .then(|| -> Result<git::GitCommit> {
+                Ok(git::GitCommit {
+                    message: matches.value_of("git-commit-message").unwrap_or(git::DEFAULT_MESSAGE).to_string(),
+                    author: matches.value_of("git-author").map(|s| s.to_string()),
+                    email: matches.value_of("git-email").map(|s| s.to_string()),
+                    granularity: git::Granularity::parse(matches.value_of("git-commit-granularity").unwrap_or("per-sync"))?,
+                })
+            })
+            .transpose()?,
+        stage_dir: matches.value_of("stage-dir").map(PathBuf::from),
+        rewrite_refs: matches.is_present("rewrite-refs") || settings.rewrite_refs.unwrap_or(false),
+        no_body_on_create: !matches.is_present("with-body")
+            && (matches.is_present("no-body-on-create") || settings.no_body_on_create.unwrap_or(false)),
+        input_encoding: matches
+            .value_of("input-encoding")
+            .map(|label| {
+                encoding_rs::Encoding::for_label(label.as_bytes())
+                    .ok_or_else(|| anyhow::anyhow!("Unknown --input-encoding: {}", label))
+            })
+            .transpose()?,
+        push_concurrency: match matches.value_of("push-concurrency").unwrap_or("4").parse() {
+            Ok(n) if n > 0 => n,
+            _ => return Err(anyhow::anyhow!("--push-concurrency must be a positive number")),
+        },
+        comment_concurrency: match matches.value_of("comment-concurrency").unwrap_or("4").parse() {
+            Ok(n) if n > 0 => n,
+            _ => return Err(anyhow::anyhow!("--comment-concurrency must be a positive number")),
+        },
+        wrap_body: match matches.value_of("wrap-body").map(str::parse) {
+            None => None,
+            Some(Ok(width)) if width > 0 => Some(width),
+            Some(_) => return Err(anyhow::anyhow!("--wrap-body must be a positive number of columns")),
+        },
+        include_timeline: matches.is_present("include-timeline"),
+        fetch_participants: matches.is_present("fetch-participants"),
+        list_attachments: matches.is_present("list-attachments"),
+        track_tasks: matches.is_present("track-tasks"),
+        keep_raw: matches.is_present("keep-raw"),
+        sync_milestones: matches.is_present("sync-milestones"),
+        ignore_whitespace: matches.is_present("ignore-whitespace"),
+        group_by: matches.value_of("group-by").map(group::GroupBy::parse).transpose()?,
+        bulk_max_files: matches
+            .value_of("bulk-max-files")
+            .map(|n| n.parse().map_err(|_| anyhow::anyhow!("Invalid --bulk-max-files: {}", n)))
+            .transpose()?
+            .unwrap_or(bulk::DEFAULT_MAX_FILES),
+        bulk_max_percent: matches
+            .value_of("bulk-max-percent")
+            .map(|n| {
+                n.parse()
+                    .ok()
+                    .filter(|percent| *percent <= 100)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid --bulk-max-percent: {} (expected 0 to 100)", n))
+            })
+            .transpose()?,
+        confirm_bulk: matches.is_present("confirm-bulk"),
+        push_fields: fields::PushFields::parse(matches.value_of("push-fields"), matches.value_of("no-push-fields"))?,
+        metadata: sidecar::Metadata::parse(matches.value_of("metadata").unwrap_or("inline"))?,
+        create_missing_labels: matches.is_present("create-missing-labels"),
+        known_labels: labels::KnownLabels::default(),
+        state_map: state_map::StateMap::parse(matches.values_of("state-map").into_iter().flatten())?,
+        issue_locks: lock::IssueLocks::default(),
+        assignee: matches.value_of("assignee").map(|s| s.to_string()),
+        readonly_closed: matches.is_present("readonly-closed"),
+        offline_fallback: matches.is_present("offline-fallback"),
+        initial_sync_timeout: match matches.value_of("initial-sync-timeout").map(str::parse) {
+            None => None,
+            Some(Ok(seconds)) if seconds > 0 => Some(Duration::from_secs(seconds)),
+            Some(_) => return Err(anyhow::anyhow!("--initial-sync-timeout must be a positive number of seconds")),
+        },
     };
 
-    // Create issues directory if it doesn't exist
-    if !config.issues_dir.exists() {
-        fs::create_dir_all(&config.issues_dir).context("Failed to create issues directory")?;
+    if config.rewrite_refs && config.format != format::Format::Markdown {
+        return Err(anyhow::anyhow!("--rewrite-refs links to issue files, so it needs --format markdown"));
+    }
+    if config.group_by.is_some() && config.format != format::Format::Markdown {
+        return Err(anyhow::anyhow!("--group-by sorts issue files into folders, so it needs --format markdown"));
+    }
+    if !config.state_map.is_empty() && config.format != format::Format::Markdown {
+        return Err(anyhow::anyhow!("--state-map requires --format markdown"));
+    }
+    if config.sync_milestones && config.format != format::Format::Markdown {
+        return Err(anyhow::anyhow!("--sync-milestones writes milestone files next to issue files, so it needs --format markdown"));
+    }
+    if config.wrap_body.is_some() && config.format != format::Format::Markdown {
+        return Err(anyhow::anyhow!("--wrap-body wraps issue files, so it needs --format markdown"));
+    }
+    if config.include_timeline && config.format != format::Format::Markdown {
+        return Err(anyhow::anyhow!("--include-timeline appends to issue files, so it needs --format markdown"));
+    }
+    if config.readonly_closed && config.format != format::Format::Markdown {
+        return Err(anyhow::anyhow!("--readonly-closed applies to issue files, so it needs --format markdown"));
+    }
+    if config.metadata == sidecar::Metadata::Sidecar && config.format != format::Format::Markdown {
+        return Err(anyhow::anyhow!("--metadata sidecar splits issue files, so it needs --format markdown"));
+    }
+    if config.push_on_startup && config.format != format::Format::Markdown {
+        return Err(anyhow::anyhow!("--push-on-startup pushes issue files, so it needs --format markdown"));
+    }
+    if let (Some(min), Some(max)) = (config.number_min, config.number_max) {
+        if min > max {
+            return Err(anyhow::anyhow!("--number-min {} is greater than --number-max {}", min, max));
+        }
     }
 
-    // Initial sync from GitHub to local
-    println!("Performing initial sync from GitHub to local...");
-    rt.block_on(sync_github_to_local(&config)).context("Failed to sync from GitHub to local")?;
+    if matches.is_present("dump-config") {
+        println!("{}", serde_json::to_string_pretty(&dump_config(&config))?);
+        return Ok(());
+    }
 
-    if config.watch {
-        println!("Watch mode enabled. Monitoring for changes...");
-        
-        let config_arc = Arc::new(config);
-        let config_clone = Arc::clone(&config_arc);
-        
-        // Thread for periodic GitHub to local sync
-        let rt_handle = rt.handle().clone();
-        thread::spawn(move || {
-            let config = config_clone;
-            loop {
-                thread::sleep(config.sync_interval);
-                println!("Performing scheduled sync from GitHub to local...");
-                if let Err(e) = rt_handle.block_on(sync_github_to_local(&config)) {
-                    eprintln!("Error syncing from GitHub: {}", e);
-                }
+    // Subcommands that talk to GitHub directly and don't need the local directory
+    match matches.subcommand() {
+        ("close", Some(sub)) => {
+            let number = parse_issue_number(sub)?;
+            return rt.block_on(set_issue_state(&config, number, types::State::Closed));
+        }
+        ("reopen", Some(sub)) => {
+            let number = parse_issue_number(sub)?;
+            return rt.block_on(set_issue_state(&config, number, types::State::Open));
+        }
+        ("check", Some(_)) => {
+            return rt.block_on(check::check(&config));
+        }
+        ("bulk", Some(sub)) => {
+            let Some(close) = sub.subcommand_matches("close") else {
+                return Err(anyhow::anyhow!("bulk needs a subcommand: close"));
+            };
+            let options = bulk::CloseOptions {
+                filters: close
+                    .values_of("filter")
+                    .map(|values| values.map(patch::Filter::parse).collect::<Result<_>>())
+                    .transpose()?
+                    .unwrap_or_default(),
+                limit: close
+                    .value_of("limit")
+                    .map(|n| {
+                        n.parse().ok().filter(|limit| *limit > 0).ok_or_else(|| anyhow::anyhow!("--limit must be a positive number"))
+                    })
+                    .transpose()?,
+                yes: close.is_present("yes"),
+            };
+            return rt.block_on(bulk::close(&config, &options)).map(|_| ());
+        }
+        ("apply", Some(_)) => {
+            let stage_dir = config
+                .stage_dir
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("apply needs --stage-dir to know where the staged changes are"))?;
+            let updated = rt.block_on(stage::apply(&config, stage_dir))?;
+            if !updated.is_empty() {
+                hook::run_post_sync_hook(&config, hook::Direction::Push, &SyncSummary { updated, ..Default::default() });
             }
-        });
-
-        // Watch local directory for changes
-        let config_clone = Arc::clone(&config_arc);
-        let rt_handle = rt.handle().clone();
-        let mut hotwatch = Hotwatch::new().context("Failed to initialize hotwatch")?;
-        
-        hotwatch.watch(&config_arc.issues_dir, move |event: Event| {
-            if let Event::Write(path) = event {
-                if path.extension().map_or(false, |ext| ext == "md") {
-                    println!("Local file changed: {:?}", path);
-                    let config = &config_clone;
-                    if let Err(e) = rt_handle.block_on(sync_local_to_github(config, &path)) {
-                        eprintln!("Error syncing to GitHub: {}", e);
-                    }
+            return Ok(());
+        }
+        ("verify", Some(_)) => {
+            // Unlike the other commands, never create the issues directory
+            if !config.issues_dir.is_dir() {
+                return Err(anyhow::anyhow!("Issues directory {} does not exist", config.issues_dir.display()));
+            }
+            return rt.block_on(plan::verify(&config));
+        }
+        ("new", Some(sub)) => {
+            let template = match (sub.is_present("template"), sub.value_of("template")) {
+                (true, None) => {
+                    template::print_templates(&rt.block_on(template::list_templates(&config))?);
+                    return Ok(());
                 }
+                (_, Some(name)) => Some(rt.block_on(template::find_template(&config, name))?),
+                (false, None) => None,
+            };
+            return rt.block_on(create_issue(&config, sub.value_of("title"), template.as_ref())).map(|_| ());
+        }
+        ("export", Some(sub)) => {
+            let options = export::ExportOptions {
+                output: PathBuf::from(sub.value_of("output").unwrap()),
+                state: sub.value_of("state").filter(|s| *s != "all").map(|s| s.to_string()),
+                labels: sub.values_of("label").map(|v| v.map(|l| l.to_string()).collect()).unwrap_or_default(),
+                include_assets: sub.is_present("include-assets"),
+            };
+            return rt.block_on(export::export(&config, &options)).map(|_| ());
+        }
+        _ => {}
+    }
+
+    // Create issues directory if it doesn't exist
+    create_issues_dir(&config.issues_dir)?;
+
+    if let ("doctor", Some(sub)) = matches.subcommand() {
+        rt.block_on(doctor::doctor(&config, sub.is_present("fix")))?;
+        return Ok(());
+    }
+
+    warn_about_foreign_files(&config)?;
+
+    if let ("push", Some(sub)) = matches.subcommand() {
+        if sub.is_present("warn-missing-local") {
+            rt.block_on(warn_about_missing_local(&config));
+        }
+        let result = match sub.value_of("input") {
+            Some(_) => rt.block_on(stdin::push(&config, io::stdin().lock())),
+            None => rt.block_on(push_all_files(&config)).map(|updated| SyncSummary { updated, ..Default::default() }),
+        };
+        let summary = match result {
+            Ok(summary) => summary,
+            Err(e) => {
+                report::record(&config, hook::Direction::Push, Err(&e));
+                rt.block_on(notify::notify(&config, hook::Direction::Push, Err(&e)));
+                return Err(e);
             }
-        }).context("Failed to watch directory")?;
+        };
+        if !summary.updated.is_empty() || !summary.created.is_empty() {
+            hook::run_post_sync_hook(&config, hook::Direction::Push, &summary);
+        }
+        report::record(&config, hook::Direction::Push, Ok(&summary));
+        rt.block_on(notify::notify(&config, hook::Direction::Push, Ok(&summary)));
+        return Ok(());
+    }
+
+    if let ("patch", Some(sub)) = matches.subcommand() {
+        let values = |name: &str| -> Vec<String> {
+            sub.values_of(name).map(|v| v.map(|s| s.to_string()).collect()).unwrap_or_default()
+        };
+        let options = patch::PatchOptions {
+            filters: values("filter").iter().map(|f| patch::Filter::parse(f)).collect::<Result<_>>()?,
+            add_labels: values("add-label"),
+            remove_labels: values("remove-label"),
+            add_assignees: values("add-assignee"),
+            remove_assignees: values("remove-assignee"),
+            state: sub.value_of("set-state").map(|s| s.to_string()),
+            push: sub.is_present("push"),
+        };
+        let patched = rt.block_on(patch::patch(&config, &options))?;
+        if options.push && !patched.is_empty() {
+            hook::run_post_sync_hook(&config, hook::Direction::Push, &SyncSummary { updated: patched, ..Default::default() });
+        }
+        return Ok(());
+    }
+
+    if let ("plan", Some(_)) = matches.subcommand() {
+        let plan = rt.block_on(plan::compute_sync_plan(&config))?;
+        plan::print_plan(&config, &plan);
+        return Ok(());
+    }
+
+    if config.push_on_startup {
+        println!("Pushing issue files edited while retasks was not running...");
+        let summary = SyncSummary { updated: rt.block_on(push_offline_edits(&config))?, ..Default::default() };
+        if !summary.updated.is_empty() {
+            hook::run_post_sync_hook(&config, hook::Direction::Push, &summary);
+            report::record(&config, hook::Direction::Push, Ok(&summary));
+            rt.block_on(notify::notify(&config, hook::Direction::Push, Ok(&summary)));
+        }
+    } else {
+        warn_about_offline_edits(&config)?;
+    }
 
-        // Keep the main thread alive
-        loop {
-            thread::sleep(Duration::from_secs(60));
+    // Initial sync from GitHub to local, catching up from the saved cursor if there is one
+    let since = startup_since(&config)?;
+    match since {
+        Some(since) => println!("Catching up on issues updated since {}...", since),
+        None => println!("Performing initial sync from GitHub to local..."),
+    }
+    match rt.block_on(initial_sync(&config, since)) {
+        Ok(Some(summary)) => {
+            git::commit_pulled_changes(&config, &summary);
+            hook::run_post_sync_hook(&config, hook::Direction::Pull, &summary);
+            report::record(&config, hook::Direction::Pull, Ok(&summary));
+            rt.block_on(notify::notify(&config, hook::Direction::Pull, Ok(&summary)));
+        }
+        Ok(None) => {}
+        Err(e) => {
+            report::record(&config, hook::Direction::Pull, Err(&e));
+            rt.block_on(notify::notify(&config, hook::Direction::Pull, Err(&e)));
+            return Err(e);
         }
+    }
+
+    if config.watch {
+        println!("Watch mode enabled. Monitoring for changes...");
+
+        // Runs until Ctrl-C, letting a pull or push in progress finish
+        rt.block_on(async {
+            let service = service::SyncService::start(Arc::new(config))?;
+            tokio::signal::ctrl_c().await.context("Failed to listen for Ctrl-C")?;
+            println!("Shutting down...");
+            service.stop().await
+        })?;
     } else {
         println!("One-time sync completed. Use --watch for continuous sync.");
     }
@@ -117,8 +645,136 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+// Where the first pull starts: the --since-file's cursor if given, else
+// the state file's. None pulls every issue.
+fn startup_since(config: &Config) -> Result<Option<DateTime<Utc>>> {
+    match &config.since_file {
+        _ if config.full_resync => Ok(None),
+        Some(path) => since_file::read(path),
+        None => Ok(config.state.get().last_pull_at),
+    }
+}
+
+// The startup pull, bounded by --initial-sync-timeout. Files written before
+// the timeout stay; the cursor isn't advanced, so the next pull fetches the
+// rest. Outside watch mode a timeout is an error; in watch mode it is a
+// warning and None, and the scheduled pulls carry on from there.
+async fn initial_sync(config: &Config, since: Option<DateTime<Utc>>) -> Result<Option<SyncSummary>> {
+    let sync = sync_github_to_local(config, since);
+    let result = match config.initial_sync_timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, sync).await {
+            Ok(result) => result,
+            Err(_) if config.watch => {
+                eprintln!(
+                    "{}",
+                    config.colors.warning(&format!(
+                        "Warning: initial sync did not finish within {}s (--initial-sync-timeout); the local files may be incomplete until the next scheduled pull",
+                        timeout.as_secs()
+                    ))
+                );
+                return Ok(None);
+            }
+            Err(_) => {
+                return Err(anyhow::anyhow!(
+                    "Initial sync did not finish within {}s (--initial-sync-timeout); files already written were kept",
+                    timeout.as_secs()
+                ))
+            }
+        },
+        None => sync.await,
+    };
+    result.context("Failed to sync from GitHub to local").map(Some)
+}
+
+// An --issues-dir pointing at a file would otherwise fail later with a
+// bare "Not a directory" from whichever read or write gets there first.
+fn check_issues_dir(path: &Path) -> Result<()> {
+    if path.exists() && !path.is_dir() {
+        return Err(anyhow::anyhow!("issues-dir {} is a file, expected a directory", path.display()));
+    }
+    Ok(())
+}
+
+fn create_issues_dir(path: &Path) -> Result<()> {
+    check_issues_dir(path)?;
+    fs::create_dir_all(path).context(format!("Failed to create issues directory: {}", path.display()))
+}
+
+// The resolved configuration for --dump-config, with tokens redacted
+fn dump_config(config: &Config) -> serde_json::Value {
+    serde_json::json!({
+        "repo": format!("{}/{}", config.repo_owner, config.repo_name),
+        "api_url": config.github.api_url(),
+        "tokens": vec!["<redacted>"; config.github.token_count()],
+        "headers": config.github.header_names().iter().map(|name| (name.clone(), "<redacted>")).collect::<HashMap<_, _>>(),
+        "issues_dir": config.issues_dir,
+        "watch": config.watch,
+        "periodic_sync": config.periodic_sync,
+        "watch_files": config.watch_files,
+        "sync_interval_secs": config.sync_interval.as_secs(),
+        "io_retries": config.io_retries,
+        "server_error_retries": config.github.server_error_retries(),
+        "post_sync_hook": config.post_sync_hook,
+        // Webhook URLs usually embed a secret
+        "notify": config.notify.as_ref().map(|_| "<redacted>"),
+        "report": config.report,
+        "colors": config.colors,
+        "prune": config.prune,
+        "safe_prune": config.safe_prune,
+        "only": config.only,
+        "creator": config.creator,
+        "mentioned": config.mentioned,
+        "sort": config.sort.as_ref().map(ToString::to_string),
+        "order": config.order.as_ref().map(ToString::to_string),
+        "timezone": config.timezone.to_string(),
+        "api": config.api,
+        "number_min": config.number_min,
+        "number_max": config.number_max,
+        "dry_run": config.dry_run,
+        "full_resync": config.full_resync,
+        "since_file": config.since_file,
+        "state_file": config.issues_dir.join(state::STATE_FILE),
+        "format": config.format,
+        "raw_body": config.raw_body,
+        "download_attachments": config.download_attachments,
+        "conflict_strategy": config.conflict_strategy,
+        "push_on_startup": config.push_on_startup,
+        "git_commit": config.git_commit,
+        "stage_dir": config.stage_dir,
+        "rewrite_refs": config.rewrite_refs,
+        "no_body_on_create": config.no_body_on_create,
+        "input_encoding": config.input_encoding.map(|encoding| encoding.name()),
+        "push_concurrency": config.push_concurrency,
+        "comment_concurrency": config.comment_concurrency,
+        "wrap_body": config.wrap_body,
+        "include_timeline": config.include_timeline,
+        "fetch_participants": config.fetch_participants,
+        "list_attachments": config.list_attachments,
+        "track_tasks": config.track_tasks,
+        "keep_raw": config.keep_raw,
+        "sync_milestones": config.sync_milestones,
+        "ignore_whitespace": config.ignore_whitespace,
+        "group_by": config.group_by.as_ref().map(ToString::to_string),
+        "bulk_max_files": config.bulk_max_files,
+        "bulk_max_percent": config.bulk_max_percent,
+        "confirm_bulk": config.confirm_bulk,
+        "push_fields": config.push_fields,
+        "metadata": config.metadata,
+        "create_missing_labels": config.create_missing_labels,
+        "state_map": config.state_map,
+        "assignee": config.assignee,
+        "readonly_closed": config.readonly_closed,
+        "offline_fallback": config.offline_fallback,
+        "initial_sync_timeout_secs": config.initial_sync_timeout.map(|timeout| timeout.as_secs()),
+    })
+}
+
 // Moved argument parsing into a separate function for testability
-fn get_app_args() -> clap::ArgMatches<'static> {
+fn get_app_args() -> ArgMatches<'static> {
+    build_app().get_matches()
+}
+
+fn build_app() -> App<'static, 'static> {
     App::new("Retasks")
         .version("1.0")
         .author("Eugen Soloviov (@suenot)")
@@ -135,12 +791,33 @@ fn get_app_args() -> clap::ArgMatches<'static> {
                 .long("watch")
                 .help("Watch for changes and sync automatically"),
         )
+        .arg(
+            Arg::with_name("no-periodic-sync")
+                .long("no-periodic-sync")
+                .help("With --watch, only push local changes; don't pull from GitHub every --interval")
+                .requires("watch")
+                .conflicts_with("no-watch-files"),
+        )
+        .arg(
+            Arg::with_name("no-watch-files")
+                .long("no-watch-files")
+                .help("With --watch, only pull from GitHub every --interval; don't watch local files")
+                .requires("watch"),
+        )
         .arg(
             Arg::with_name("token")
                 .long("token")
                 .value_name("TOKEN")
-                .help("GitHub API token")
-                .required(true)
+                .help("GitHub API token (repeat to rotate between several tokens)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("token-file")
+                .long("token-file")
+                .value_name("PATH")
+                .help("File with one GitHub API token per line")
                 .takes_value(true),
         )
         .arg(
@@ -148,9 +825,38 @@ fn get_app_args() -> clap::ArgMatches<'static> {
                 .long("repo")
                 .value_name("OWNER/REPO")
                 .help("GitHub repository in format owner/repo")
-                .required(true)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("PATH")
+                .help("Settings saved by `init` (default: retasks.toml, if present)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("repo-config")
+                .long("repo-config")
+                .value_name("PATH")
+                .help("YAML file with default settings and per-repository overrides keyed by owner/repo")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("api-url")
+                .long("api-url")
+                .value_name("URL")
+                .help("GitHub API base URL (default: https://api.github.com)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("header")
+                .long("header")
+                .value_name("NAME: VALUE")
+                .help("Send this header with every GitHub API request, e.g. for a gateway in front of the API (repeat for several)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
         .arg(
             Arg::with_name("interval")
                 .long("interval")
@@ -158,205 +864,3747 @@ fn get_app_args() -> clap::ArgMatches<'static> {
                 .help("Sync interval in seconds when using --watch (default: 300)")
                 .takes_value(true),
         )
-        .get_matches()
-}
-
-async fn sync_github_to_local(config: &Config) -> Result<()> {
-    let client = Client::new(
-        "github-issues-sync".to_string(),
-        Credentials::Token(config.token.clone()),
-    )?;
-
-    let issues_client = client.issues();
-    
-    // List issues with the correct parameters
-    let issues_response = issues_client.list(
-        types::Filter::All,
-        types::IssuesListState::All,
-        &config.repo_owner,
-        types::IssuesListSort::Created,
-        types::Order::Desc,
-        None, 
-        false, 
-        false, 
-        false, 
-        false, 
-        100, 
-        1
-    ).await.context("Failed to list issues from GitHub")?;
-    
-    let issues = issues_response.body;
-
-    for issue in issues {
-        // Extract labels - use a simpler approach since the exact structure is complex
-        let labels: Vec<String> = Vec::new(); // Default to empty labels if we can't extract them properly
-
-        let local_issue = Issue {
-            number: issue.number,
-            title: issue.title,
-            body: Some(issue.body),
-            state: issue.state,
-            labels,
-        };
-
-        let file_path = config.issues_dir.join(format!("issue-{}.md", issue.number));
-        let mut file = File::create(&file_path).context(format!("Failed to create file: {}", file_path.display()))?;
-
-        // Create frontmatter with issue metadata
-        let frontmatter = format!(
-            "---\nnumber: {}\ntitle: {}\nstate: {}\nlabels: [{}]\n---\n\n",
-            local_issue.number,
-            local_issue.title,
-            local_issue.state,
-            local_issue.labels.join(", ")
-        );
-
-        file.write_all(frontmatter.as_bytes()).context("Failed to write frontmatter")?;
-        
-        // Write issue body
-        if let Some(body) = local_issue.body {
-            file.write_all(body.as_bytes()).context("Failed to write issue body")?;
-        }
-
-        println!("Synced issue #{} to {}", issue.number, file_path.display());
-    }
-
-    Ok(())
-}
-
-async fn sync_local_to_github(config: &Config, file_path: &Path) -> Result<()> {
-    if !file_path.is_file() || file_path.extension().map_or(true, |ext| ext != "md") {
-        return Ok(());
-    }
-
-    let mut file = File::open(file_path).context(format!("Failed to open file: {}", file_path.display()))?;
-    let mut content = String::new();
-    file.read_to_string(&mut content).context("Failed to read file content")?;
-
-    // Parse frontmatter and body
-    let (frontmatter, body) = parse_markdown_file(&content).context("Failed to parse markdown file")?;
-    
-    let client = Client::new(
-        "github-issues-sync".to_string(),
-        Credentials::Token(config.token.clone()),
-    )?;
-
-    // Extract issue number from filename or frontmatter
-    let issue_number = frontmatter.get("number")
-        .and_then(|n| n.parse::<i64>().ok())
-        .ok_or_else(|| anyhow::anyhow!("Could not determine issue number"))?;
-
-    // Get the current state as a proper enum value
-    let state = if let Some(state_str) = frontmatter.get("state") {
-        match state_str.to_lowercase().as_str() {
-            "closed" => Some(types::State::Closed),
-            "open" => Some(types::State::Open),
-            _ => None
-        }
-    } else {
-        None
-    };
-    
-    // Create update request with required empty string for assignee
-    let mut update = types::IssuesUpdateRequest {
-        title: None,
-        body: body, // No need for Some() wrapper here as the type is String, not Option<String>
-        state,
-        assignee: String::new(),
-        assignees: vec![],
-        milestone: None,
-        labels: vec![],
-    };
-    
-    // Set title if available
-    if let Some(title) = frontmatter.get("title") {
-        update.title = Some(types::TitleOneOf::String(title.clone()));
-    }
-    
-    // Process labels
-    if let Some(labels_str) = frontmatter.get("labels") {
-        let labels: Vec<String> = labels_str
-            .split(',')
-            .map(|s| s.trim().trim_matches(|c| c == '[' || c == ']').to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
-        
-        if !labels.is_empty() {
-            update.labels = labels.into_iter()
-                .map(|label| types::IssuesCreateRequestLabelsOneOf::String(label))
-                .collect();
-        }
-    }
-
-    client.issues().update(
-        &config.repo_owner,
-        &config.repo_name,
-        issue_number,
-        &update,
-    ).await.context(format!("Failed to update issue #{} on GitHub", issue_number))?;
-
-    println!("Updated issue #{} on GitHub from {}", issue_number, file_path.display());
-    Ok(())
-}
-
-fn parse_markdown_file(content: &str) -> Result<(HashMap<String, String>, String)> {
-    let mut frontmatter = HashMap::new();
-    let mut body = String::new();
-
-    // Check if the file has frontmatter (starts with ---)
-    if content.starts_with("---") {
-        if let Some(end_index) = content[3..].find("---") {
-            let frontmatter_str = &content[3..end_index + 3];
-            
-            // Parse frontmatter
-            for line in frontmatter_str.lines() {
-                if let Some(index) = line.find(':') {
-                    let key = line[..index].trim().to_string();
-                    let value = line[index + 1..].trim().to_string();
-                    frontmatter.insert(key, value);
-                }
-            }
-            
-            // Get body (everything after frontmatter)
-            if end_index + 6 <= content.len() {
-                body = content[end_index + 6..].trim().to_string();
-            }
-        } else {
-            // No end marker for frontmatter
-            body = content.to_string();
-        }
-    } else {
-        // No frontmatter
-        body = content.to_string();
-    }
-
-    Ok((frontmatter, body))
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_parse_markdown_file() {
-        let content = r#"---
-number: 42
-title: Test Issue
-state: open
-labels: [bug, enhancement]
----
-
-This is the body of the issue.
-
-It has multiple lines."#;
-
-        let result = parse_markdown_file(content).unwrap();
-        let (frontmatter, body) = result;
-
-        assert_eq!(frontmatter.get("number"), Some(&"42".to_string()));
-        assert_eq!(frontmatter.get("title"), Some(&"Test Issue".to_string()));
-        assert_eq!(frontmatter.get("state"), Some(&"open".to_string()));
-        assert_eq!(frontmatter.get("labels"), Some(&"[bug, enhancement]".to_string()));
-        assert_eq!(body, "This is the body of the issue.\n\nIt has multiple lines.");
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("Storage format: markdown (one file per issue), json or yaml (a single issues.json/issues.yaml)")
+                .possible_values(&["markdown", "json", "yaml"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("raw-body")
+                .long("raw-body")
+                .help("Store issue bodies byte-for-byte as GitHub returns them (for export-only use)"),
+        )
+        .arg(
+            Arg::with_name("download-attachments")
+                .long("download-attachments")
+                .help("Download images attached to issue bodies into assets/ and link to the local copies"),
+        )
+        .arg(
+            Arg::with_name("rewrite-refs")
+                .long("rewrite-refs")
+                .help("Link #N references to the local issue files on pull, and turn them back into #N on push"),
+        )
+        .arg(
+            Arg::with_name("wrap-body")
+                .long("wrap-body")
+                .value_name("COLUMNS")
+                .help("Hard-wrap issue bodies at this many columns on pull, and unwrap them again on push")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("include-timeline")
+                .long("include-timeline")
+                .help("Append each issue's event history to its file on pull (read-only, never pushed)"),
+        )
+        .arg(
+            Arg::with_name("fetch-participants")
+                .long("fetch-participants")
+                .help("Write the logins of each issue's commenters to its frontmatter on pull (one extra request per commented issue)"),
+        )
+        .arg(
+            Arg::with_name("list-attachments")
+                .long("list-attachments")
+                .help("Write the URLs of the images and attached files in each body to its frontmatter on pull, without downloading them"),
+        )
+        .arg(
+            Arg::with_name("track-tasks")
+                .long("track-tasks")
+                .help("Write how many of the task list items in each body are checked to its frontmatter on pull"),
+        )
+        .arg(
+            Arg::with_name("no-body-on-create")
+                .long("no-body-on-create")
+                .help("Create issues with only their title, labels and assignees, leaving the body empty"),
+        )
+        .arg(
+            Arg::with_name("with-body")
+                .long("with-body")
+                .conflicts_with("no-body-on-create")
+                .help("Send the body when creating issues, even if no_body_on_create is set in --repo-config"),
+        )
+        .arg(
+            Arg::with_name("bulk-max-files")
+                .long("bulk-max-files")
+                .value_name("COUNT")
+                .help("Refuse to push when more issue files than this changed, unless --confirm-bulk is given (default: 50)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bulk-max-percent")
+                .long("bulk-max-percent")
+                .value_name("PERCENT")
+                .help("Refuse to push when more than this share of the issue files changed, unless --confirm-bulk is given")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("confirm-bulk")
+                .long("confirm-bulk")
+                .help("Push even when more files changed than --bulk-max-files or --bulk-max-percent allow"),
+        )
+        .arg(
+            Arg::with_name("group-by")
+                .long("group-by")
+                .value_name("label:PREFIX")
+                .help("Write each issue file into a folder named after its label with this prefix, or ungrouped/")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sync-milestones")
+                .long("sync-milestones")
+                .help("Write each milestone's title, state, due date and description to milestones/ on pull, and push edits to them"),
+        )
+        .arg(
+            Arg::with_name("ignore-whitespace")
+                .long("ignore-whitespace")
+                .help("Treat bodies that differ only in trailing spaces or the number of blank lines as unchanged, so they aren't pushed"),
+        )
+        .arg(
+            Arg::with_name("keep-raw")
+                .long("keep-raw")
+                .help("Also write each pulled issue's JSON from the API to .raw/issue-N.json, for debugging"),
+        )
+        .arg(
+            Arg::with_name("push-fields")
+                .long("push-fields")
+                .value_name("FIELDS")
+                .help("Only push these fields: comma-separated title, body, state, labels, assignees")
+                .takes_value(true)
+                .conflicts_with("no-push-fields"),
+        )
+        .arg(
+            Arg::with_name("no-push-fields")
+                .long("no-push-fields")
+                .value_name("FIELDS")
+                .help("Never push these fields: comma-separated title, body, state, labels, assignees")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("metadata")
+                .long("metadata")
+                .value_name("MODE")
+                .help("Where issue metadata is kept: inline (frontmatter, the default) or sidecar (issue-N.json next to the body)")
+                .possible_values(&["inline", "sidecar"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("state-map")
+                .long("state-map")
+                .value_name("NAME=STATE:LABEL")
+                .help("Write issues in STATE with LABEL as `state: NAME` on pull, and push `state: NAME` as STATE plus LABEL (several labels separated by commas); can be given more than once")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("create-missing-labels")
+                .long("create-missing-labels")
+                .help("Create labels that don't exist in the repository when pushing, instead of skipping them"),
+        )
+        .arg(
+            Arg::with_name("offline-fallback")
+                .long("offline-fallback")
+                .help("While GitHub is unreachable, pull from the cache of the last pull and queue pushes to retry later"),
+        )
+        .arg(
+            Arg::with_name("readonly-closed")
+                .long("readonly-closed")
+                .help("Never push changes to files of closed issues; use the reopen subcommand to reopen one"),
+        )
+        .arg(
+            Arg::with_name("assignee")
+                .long("assignee")
+                .value_name("LOGIN")
+                .help("Only pull issues assigned to this user; @me is the user the token belongs to")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("only")
+                .long("only")
+                .value_name("NUMBER")
+                .help("Only pull and push the issue with this number")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("creator")
+                .long("creator")
+                .value_name("LOGIN")
+                .help("Only pull issues opened by this user; disables pruning")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("mentioned")
+                .long("mentioned")
+                .value_name("LOGIN")
+                .help("Only pull issues that mention this user; disables pruning")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .value_name("FIELD")
+                .possible_values(&["created", "updated", "comments"])
+                .help("What to list issues by; pulls then write them in the listing's order")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("order")
+                .long("order")
+                .value_name("DIRECTION")
+                .possible_values(&["asc", "desc"])
+                .help("Direction to list issues in; pulls then write them in the listing's order")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("timezone")
+                .long("timezone")
+                .value_name("ZONE")
+                .help("Zone to write created_at and updated_at in: UTC, local or an offset like +02:00 [default: UTC]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("api")
+                .long("api")
+                .value_name("API")
+                .possible_values(&["rest", "graphql"])
+                .help("API to list issues with on full pulls; graphql fetches only the fields retasks uses [default: rest]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("number-min")
+                .long("number-min")
+                .value_name("NUMBER")
+                .help("Only pull issues numbered NUMBER or higher; files of other issues are never pruned")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("number-max")
+                .long("number-max")
+                .value_name("NUMBER")
+                .help("Only pull issues numbered NUMBER or lower; files of other issues are never pruned")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Show what would be written or sent without changing anything"),
+        )
+        .arg(
+            Arg::with_name("conflict-strategy")
+                .long("conflict-strategy")
+                .value_name("STRATEGY")
+                .help("When an issue changed both locally and on GitHub: remote-wins, local-wins or skip (default: remote-wins)")
+                .possible_values(&["remote-wins", "local-wins", "skip"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("push-on-startup")
+                .long("push-on-startup")
+                .help("Before the first pull, push issue files edited while retasks was not running, following --conflict-strategy"),
+        )
+        .arg(
+            Arg::with_name("full-resync")
+                .long("full-resync")
+                .help("Ignore the saved sync cursor and pull every issue on startup"),
+        )
+        .arg(
+            Arg::with_name("since-file")
+                .long("since-file")
+                .value_name("PATH")
+                .help("Start pulling from the timestamp in this file instead of the saved cursor, and write the new one back after each pull; a missing file means a full pull")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("prune")
+                .long("prune")
+                .help("Delete local issue files whose issue no longer exists on GitHub"),
+        )
+        .arg(
+            Arg::with_name("safe-prune")
+                .long("safe-prune")
+                .help("With --prune, look up each issue missing from the listing and only delete its file if GitHub answers 404 or 410"),
+        )
+        .arg(
+            Arg::with_name("input-encoding")
+                .long("input-encoding")
+                .value_name("ENCODING")
+                .help("Read local files that aren't valid UTF-8 in this encoding, e.g. windows-1251")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("push-concurrency")
+                .long("push-concurrency")
+                .value_name("COUNT")
+                .help("How many files the push subcommand sends to GitHub at once (default: 4)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("comment-concurrency")
+                .long("comment-concurrency")
+                .value_name("COUNT")
+                .help("How many issues' comments --fetch-participants lists at once during a pull (default: 4)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("initial-sync-timeout")
+                .long("initial-sync-timeout")
+                .value_name("SECONDS")
+                .help("Give up the startup pull after this long; watch mode then carries on with a warning")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("io-retries")
+                .long("io-retries")
+                .value_name("COUNT")
+                .help("Retries for transient local file write errors (0-8, default: 3)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("server-error-retries")
+                .long("server-error-retries")
+                .value_name("COUNT")
+                .help("Retries, with backoff, when GitHub answers with a 5xx error or not at all (default: 3)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("report-file")
+                .long("report-file")
+                .value_name("PATH")
+                .help("Append a record of every pull and push (time, direction, counts, each issue's action and any error) to this file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("report-format")
+                .long("report-format")
+                .value_name("FORMAT")
+                .help("Format of --report-file entries: json (one object per line) or markdown; defaults to markdown for .md files")
+                .possible_values(&["json", "markdown"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("report-max-size")
+                .long("report-max-size")
+                .value_name("SIZE")
+                .help("Once --report-file would grow past this size (e.g. 500K or 10M), move it to PATH.1 and start a new one")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("notify")
+                .long("notify")
+                .value_name("URL")
+                .help("POST a JSON summary to this URL after each sync that changed something or failed")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("post-sync-hook")
+                .long("post-sync-hook")
+                .value_name("CMD")
+                .help("Shell command to run after each successful pull or push")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("stage-dir")
+                .long("stage-dir")
+                .value_name("DIR")
+                .help("Stage local edits as diffs in DIR instead of pushing them; push them with `apply`")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("dump-config")
+                .long("dump-config")
+                .help("Print the resolved configuration as JSON, with tokens redacted, and exit"),
+        )
+        .arg(
+            Arg::with_name("git-commit")
+                .long("git-commit")
+                .help("Commit the issues directory to git after each pull that changed something"),
+        )
+        .arg(
+            Arg::with_name("git-commit-message")
+                .long("git-commit-message")
+                .value_name("TEMPLATE")
+                .help("Commit message for --git-commit, with {count}, {repo} and {date} tokens")
+                .requires("git-commit")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("git-commit-granularity")
+                .long("git-commit-granularity")
+                .value_name("GRANULARITY")
+                .help("Commit each changed issue separately (per-issue) or a whole pull at once (per-sync, the default)")
+                .possible_values(&["per-issue", "per-sync"])
+                .requires("git-commit")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("git-author")
+                .long("git-author")
+                .value_name("NAME")
+                .help("Author name for --git-commit commits (default: git's user.name)")
+                .requires("git-commit")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("git-email")
+                .long("git-email")
+                .value_name("EMAIL")
+                .help("Author email for --git-commit commits (default: git's user.email)")
+                .requires("git-commit")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .value_name("WHEN")
+                .help("Colorize output: auto, always or never (default: auto, honours NO_COLOR)")
+                .possible_values(&["auto", "always", "never"])
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("init")
+                .about("Saves the repository, token and issues directory to retasks.toml, asking for what isn't given")
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Overwrite an existing config file"),
+                )
+                .arg(
+                    Arg::with_name("pull")
+                        .long("pull")
+                        .help("Run the first pull right away"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks that the tokens and the repository work, and shows the remaining rate limit"),
+        )
+        .subcommand(
+            SubCommand::with_name("plan")
+                .about("Shows what a pull and a push would change, without changing anything"),
+        )
+        .subcommand(
+            SubCommand::with_name("push")
+                .about("Pushes every local issue file that changed since the last sync, then exits")
+                .arg(
+                    Arg::with_name("input")
+                        .value_name("-")
+                        .possible_values(&["-"])
+                        .help("Push the one issue read from stdin instead; without a `number` it is created")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("warn-missing-local")
+                        .long("warn-missing-local")
+                        .help("Warn about issues on GitHub that have no local file, e.g. because a pull was missed"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("patch")
+                .about("Edits the frontmatter of matching local issue files in place, e.g. to relabel many issues")
+                .arg(patch_list_arg("filter", "KEY=VALUE", "Only patch files matching label=, assignee=, state= or number= (repeat to require several)"))
+                .arg(patch_list_arg("add-label", "LABEL", "Add this label"))
+                .arg(patch_list_arg("remove-label", "LABEL", "Remove this label"))
+                .arg(patch_list_arg("add-assignee", "LOGIN", "Add this assignee"))
+                .arg(patch_list_arg("remove-assignee", "LOGIN", "Remove this assignee"))
+                .arg(
+                    Arg::with_name("set-state")
+                        .long("set-state")
+                        .value_name("STATE")
+                        .help("Set the state, dropping a state_reason that went with the old one")
+                        .possible_values(&["open", "closed"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("push")
+                        .long("push")
+                        .help("Push the patched files to GitHub afterwards"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bulk")
+                .about("Changes many issues on GitHub at once, without going through the local files")
+                .subcommand(
+                    SubCommand::with_name("close")
+                        .about("Closes the open issues on GitHub that match every filter; lists them unless --yes is given")
+                        .arg(
+                            patch_list_arg(
+                                "filter",
+                                "KEY=VALUE",
+                                "Only close issues matching label=, assignee=, state= or number= (repeat to require several)",
+                            )
+                            .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("limit")
+                                .long("limit")
+                                .value_name("COUNT")
+                                .help("Close at most this many issues, lowest numbers first")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("yes")
+                                .long("yes")
+                                .help("Close the matching issues instead of only listing them"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Exits with an error if the local files differ from GitHub, without changing anything"),
+        )
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about("Cross-checks the local files against GitHub and reports orphaned, duplicate, unnumbered and missing files")
+                .arg(
+                    Arg::with_name("fix")
+                        .long("fix")
+                        .help("Delete files whose issue no longer exists and pull issues that have no file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("apply")
+                .about("Pushes the local edits staged in --stage-dir"),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Pulls issues into a zip archive, without touching the issues directory")
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Zip file to write")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("state")
+                        .long("state")
+                        .value_name("STATE")
+                        .help("Only export issues in this state: open, closed or all (default: all)")
+                        .possible_values(&["open", "closed", "all"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("label")
+                        .long("label")
+                        .value_name("LABEL")
+                        .help("Only export issues with this label (repeat to require several)")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("include-assets")
+                        .long("include-assets")
+                        .help("Download images attached to issue bodies into the archive's assets/"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("new")
+                .about("Creates an issue on GitHub and writes its local file, to carry on writing it locally")
+                .arg(
+                    Arg::with_name("title")
+                        .long("title")
+                        .value_name("TITLE")
+                        .help("Issue title (appended to the template's title prefix, if any)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("template")
+                        .long("template")
+                        .value_name("NAME")
+                        .help("Prefill from .github/ISSUE_TEMPLATE/NAME.md; without NAME, lists the templates")
+                        .takes_value(true)
+                        .min_values(0),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("close")
+                .about("Closes an issue on GitHub without touching its other fields")
+                .arg(issue_number_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("reopen")
+                .about("Reopens an issue on GitHub without touching its other fields")
+                .arg(issue_number_arg()),
+        )
+}
+
+fn issue_number_arg() -> Arg<'static, 'static> {
+    Arg::with_name("number")
+        .value_name("NUMBER")
+        .help("Issue number")
+        .required(true)
+        .index(1)
+}
+
+// A repeatable option of the `patch` subcommand
+fn patch_list_arg(name: &'static str, value_name: &'static str, help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(name)
+        .long(name)
+        .value_name(value_name)
+        .help(help)
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+}
+
+fn login_arg(matches: &ArgMatches, name: &str) -> Result<Option<String>> {
+    let Some(login) = matches.value_of(name) else {
+        return Ok(None);
+    };
+    if !is_login(login) {
+        return Err(anyhow::anyhow!("Invalid GitHub login for --{}: {:?}", name, login));
+    }
+    Ok(Some(login.to_string()))
+}
+
+// GitHub logins are up to 39 letters, digits and single hyphens, and
+// neither start nor end with a hyphen
+fn is_login(login: &str) -> bool {
+    (1..=39).contains(&login.len())
+        && login.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !login.starts_with('-')
+        && !login.ends_with('-')
+        && !login.contains("--")
+}
+
+fn number_bound(matches: &ArgMatches, name: &str) -> Result<Option<i64>> {
+    matches
+        .value_of(name)
+        .map(|n| n.parse::<i64>().map_err(|_| anyhow::anyhow!("Invalid issue number for --{}: {}", name, n)))
+        .transpose()
+}
+
+fn parse_issue_number(matches: &ArgMatches) -> Result<i64> {
+    let value = matches.value_of("number").unwrap();
+    value
+        .parse::<i64>()
+        .map_err(|_| anyhow::anyhow!("Invalid issue number: {}", value))
+}
+
+// Tokens from repeated --token flags followed by those in --token-file. Only
+// when neither is given are the token and token file saved in retasks.toml used.
+fn collect_tokens(matches: &ArgMatches, project: &init::ProjectConfig) -> Result<Vec<String>> {
+    let mut tokens: Vec<String> = matches
+        .values_of("token")
+        .map(|values| values.map(|t| t.to_string()).collect())
+        .unwrap_or_default();
+
+    if let Some(path) = matches.value_of("token-file") {
+        tokens.extend(github::read_token_file(Path::new(path))?);
+    }
+
+    if tokens.is_empty() {
+        tokens.extend(project.token.clone());
+        if let Some(path) = &project.token_file {
+            tokens.extend(github::read_token_file(path)?);
+        }
+    }
+
+    Ok(tokens)
+}
+
+// With `since`, only issues updated at or after that time are fetched
+async fn sync_github_to_local(config: &Config, since: Option<DateTime<Utc>>) -> Result<SyncSummary> {
+    let fingerprints_before = config.state.get().fingerprints;
+    // A full listing, over REST or GraphQL, is saved page by page, so an
+    // interrupted pull can resume. It also brings each issue as GitHub
+    // listed it: the REST JSON or the GraphQL node.
+    let fetched = match config.only {
+        Some(_) => {
+            let started_at = Utc::now();
+            fetch_issues(config, since).await.map(|fetched| (fetched, None, started_at))
+        }
+        None => checkpoint::fetch_all(config, since).await.map(|(fetched, listed, started_at)| (fetched, Some(listed), started_at)),
+    };
+    let (fetched, listed, started_at) = match fetched {
+        Ok(fetched) => fetched,
+        Err(e) if config.offline_fallback && cache::is_unreachable(&e) => return pull_from_cache(config, e).await,
+        Err(e) => return Err(e),
+    };
+    let raw = match &listed {
+        _ if !config.keep_raw => HashMap::new(),
+        Some(listed) => listed.clone(),
+        None => raw::collect(&fetched)?,
+    };
+    let mut issues: Vec<Issue> = fetched
+        .into_iter()
+        .map(|issue| issue_from_remote(config, issue))
+        .collect();
+    // Unless --sort or --order asks for the listing's order, it is only for
+    // fetching; files, output and hooks go by ascending number so repeated
+    // pulls are reproducible
+    if config.sort.is_none() && config.order.is_none() {
+        issues.sort_by_key(|issue| issue.number);
+    } else {
+        // An issue updated during the listing can be listed twice
+        let mut seen = HashSet::new();
+        issues.retain(|issue| seen.insert(issue.number));
+    }
+    match &listed {
+        Some(nodes) if config.api == graphql::Api::Graphql => graphql::fill_state_reasons(&mut issues, nodes),
+        listed => fill_state_reasons(config, &mut issues, listed.as_ref()).await,
+    }
+
+    // Pruning goes by every issue on GitHub, so files of issues assigned to
+    // someone else are never deleted for not matching --assignee
+    let mut remote_numbers: HashSet<i64> = issues.iter().map(|issue| issue.number).collect();
+    if let Some(login) = assignee_login(config).await? {
+        issues.retain(|issue| is_assigned(issue, &login));
+    }
+    // The API can't filter by number, so the range is applied here
+    issues.retain(|issue| in_number_range(config, issue.number));
+    config.state_map.localize(&mut issues);
+
+    // Before --download-attachments points the body at local copies
+    if config.list_attachments {
+        attachments::inventory(&mut issues);
+    }
+    if config.track_tasks {
+        tasks::fill(&mut issues);
+    }
+    if config.download_attachments {
+        attachments::localize(config, &mut issues).await?;
+    }
+    if config.rewrite_refs {
+        refs::localize(config, &mut issues);
+    }
+    if let Some(width) = config.wrap_body {
+        for issue in &mut issues {
+            issue.body = issue.body.as_deref().map(|body| wrap::wrap(body, width));
+        }
+    }
+    if config.include_timeline {
+        timeline::append(config, &mut issues).await;
+    }
+    if config.fetch_participants {
+        participants::fill(config, &mut issues).await;
+    }
+
+    // Pushes of these issues wait until they are written. An issue synced
+    // while they were being fetched may have been fetched as it was before,
+    // so its file is left alone and the next pull brings it up to date.
+    let _locks = config.issue_locks.lock_all(issues.iter().map(|issue| issue.number)).await;
+    let fingerprints = config.state.get().fingerprints;
+    issues.retain(|issue| {
+        let synced = fingerprints.get(&issue.number) != fingerprints_before.get(&issue.number);
+        if synced {
+            println!("Issue #{} was synced while this pull was fetching it; leaving its file for the next pull", issue.number);
+        }
+        !synced
+    });
+
+    // With --only, `since`, --creator or --mentioned the fetched set is
+    // partial, so it can't drive pruning
+    let complete = config.only.is_none() && since.is_none() && lists_every_issue(config);
+    if config.offline_fallback && !config.dry_run {
+        if let Err(e) = cache::save(config, &issues, complete) {
+            eprintln!("{}", config.colors.warning(&format!("Warning: failed to update the offline cache: {:#}", e)));
+        }
+    }
+    if config.keep_raw && !config.dry_run {
+        raw::save(config, &issues, &raw)?;
+    }
+    if config.prune && config.safe_prune && complete {
+        keep_existing_issues(config, &mut remote_numbers).await?;
+    }
+    let summary = write_pulled_issues(config, &issues, complete.then_some(&remote_numbers))?;
+    if config.sync_milestones && config.only.is_none() {
+        milestones::pull(config).await?;
+    }
+
+    if !config.dry_run {
+        config.state.update(|state| {
+            state.last_pull_at = Some(started_at);
+            state.last_event_at = Some(Utc::now());
+        })?;
+        if let Some(path) = &config.since_file {
+            since_file::write(path, started_at)?;
+        }
+        source::write(&config.issues_dir, &source::describe(config, started_at))?;
+        checkpoint::clear(config)?;
+    }
+
+    Ok(summary)
+}
+
+// With --offline-fallback, a pull that can't reach GitHub writes the issues
+// of the last pull instead of failing. Nothing is pruned, and the sync cursor
+// stays where it was so the next pull that gets through catches up.
+async fn pull_from_cache(config: &Config, error: anyhow::Error) -> Result<SyncSummary> {
+    let Some((pulled_at, issues)) = cache::load(config)? else {
+        return Err(error.context("GitHub is unreachable and nothing has been cached yet"));
+    };
+    eprintln!(
+        "{}",
+        config.colors.warning(&format!(
+            "Warning: GitHub is unreachable ({:#}); using the issues cached at {}, which may be stale",
+            error, pulled_at
+        ))
+    );
+    let issues: Vec<Issue> = issues.into_iter().filter(|issue| in_number_range(config, issue.number)).collect();
+    let _locks = config.issue_locks.lock_all(issues.iter().map(|issue| issue.number)).await;
+    write_pulled_issues(config, &issues, None)
+}
+
+// `remote_numbers` is passed on for pruning when the pull was complete
+fn write_pulled_issues(config: &Config, issues: &[Issue], remote_numbers: Option<&HashSet<i64>>) -> Result<SyncSummary> {
+    // The directory may have been replaced since startup
+    check_issues_dir(&config.issues_dir)?;
+    let (summary, failures) = match config.format {
+        format::Format::Markdown => write_issue_files(config, issues, remote_numbers)?,
+        _ => (format::write_data_file(config, issues, remote_numbers)?, Vec::new()),
+    };
+
+    println!(
+        "{}",
+        config.colors.success(&format!(
+            "Pull complete: {} created, {} updated, {} pruned",
+            summary.created.len(),
+            summary.updated.len(),
+            summary.pruned.len()
+        ))
+    );
+
+    if !failures.is_empty() {
+        let numbers: Vec<String> = failures.iter().map(|n| format!("#{}", n)).collect();
+        return Err(anyhow::anyhow!("Failed to write {} issue(s): {}", failures.len(), numbers.join(", ")));
+    }
+    Ok(summary)
+}
+
+// The login --assignee stands for, with `@me` looked up from the token
+async fn assignee_login(config: &Config) -> Result<Option<String>> {
+    match config.assignee.as_deref() {
+        None => Ok(None),
+        Some("@me") => {
+            let user = config
+                .github
+                .with_rotation(|client| async move { client.users().get_authenticated_public_user().await })
+                .await
+                .context("Failed to look up the user for --assignee @me")?;
+            Ok(Some(user.body.login))
+        }
+        Some(login) => Ok(Some(login.to_string())),
+    }
+}
+
+// Whether listings include every issue, rather than only those matching
+// --creator or --mentioned. Only a complete listing can tell which issues
+// were deleted.
+fn lists_every_issue(config: &Config) -> bool {
+    config.creator.is_none() && config.mentioned.is_none()
+}
+
+// Within --number-min and --number-max, if given
+fn in_number_range(config: &Config, number: i64) -> bool {
+    config.number_min.is_none_or(|min| number >= min) && config.number_max.is_none_or(|max| number <= max)
+}
+
+// GitHub logins are case-insensitive
+fn is_assigned(issue: &Issue, login: &str) -> bool {
+    issue.assignees.iter().any(|assignee| assignee.eq_ignore_ascii_case(login))
+}
+
+fn issue_from_remote(config: &Config, issue: types::Issue) -> Issue {
+    let body = if config.raw_body { issue.body } else { normalize_body(&issue.body) };
+
+    // GitHub doesn't guarantee an order, so keep lists sorted to avoid spurious diffs
+    let mut labels: Vec<String> = issue.labels.iter().filter_map(label_name).collect();
+    labels.sort();
+    let mut assignees: Vec<String> = issue.assignees.iter().map(|user| user.login.clone()).collect();
+    assignees.sort();
+
+    Issue {
+        number: issue.number,
+        labels,
+        assignees,
+        title: issue.title,
+        body: Some(body),
+        state: issue.state,
+        // octorust's issue type has no state_reason; see fill_state_reasons
+        state_reason: None,
+        html_url: issue.html_url,
+        node_id: issue.node_id,
+        locked: issue.locked,
+        comments: issue.comments,
+        // Filled in by participants::fill, which costs a request per issue
+        participants: None,
+        attachments: None,
+        tasks: None,
+        created_at: issue.created_at.map(|at| config.timezone.convert(at)),
+        updated_at: issue.updated_at.map(|at| config.timezone.convert(at)),
+    }
+}
+
+// Only the fields that are pushed count; html_url, node_id, locked, comments,
+// participants, attachments, tasks, created_at and updated_at are read-only.
+// state_reason isn't compared, since only a pull looks it up on GitHub.
+fn issues_differ(config: &Config, local: &Issue, remote: &Issue) -> bool {
+    local.title != remote.title
+        || comparable_body(config, local.body.as_deref().unwrap_or_default())
+            != comparable_body(config, remote.body.as_deref().unwrap_or_default())
+        || !local.state.trim().eq_ignore_ascii_case(remote.state.trim())
+        || sorted(&local.labels) != sorted(&remote.labels)
+        || sorted(&local.assignees) != sorted(&remote.assignees)
+}
+
+fn sorted(values: &[String]) -> Vec<&String> {
+    let mut values: Vec<&String> = values.iter().collect();
+    values.sort();
+    values
+}
+
+// Stable hash of the pushable fields, with lists in canonical order. Stored
+// per issue in the state file so a file that still matches what was last
+// synced isn't pushed again. It has two halves, the body as written and the
+// body as --ignore-whitespace compares it, so the stored value stays valid
+// when the flag is toggled; compare them with `fingerprints_match`.
+fn fingerprint(config: &Config, issue: &Issue) -> String {
+    let mapped = config.state_map.to_github(issue);
+    let issue = mapped.as_ref().unwrap_or(issue);
+    let labels = sorted(&issue.labels).iter().map(|s| s.as_str()).collect::<Vec<_>>().join("\x1f");
+    let assignees = sorted(&issue.assignees).iter().map(|s| s.as_str()).collect::<Vec<_>>().join("\x1f");
+    let hash = |body: &str| {
+        let canonical = [issue.title.as_str(), &issue.state.trim().to_lowercase(), &labels, &assignees, body].join("\0");
+        // Appended only when set, so fingerprints stored before it was synced stay valid
+        let canonical = match &issue.state_reason {
+            Some(reason) => format!("{}\0{}", canonical, reason),
+            None => canonical,
+        };
+        stable_hash(&canonical)
+    };
+
+    let body = issue.body.as_deref().unwrap_or_default();
+    format!("{}-{}", hash(body), hash(&collapse_whitespace(body)))
+}
+
+// Whether two fingerprints are of the same content, by the half that
+// --ignore-whitespace selects. Fingerprints stored before there were two
+// halves only have the first.
+fn fingerprints_match(config: &Config, a: &str, b: &str) -> bool {
+    let (a_exact, a_loose) = a.split_once('-').unwrap_or((a, ""));
+    let (b_exact, b_loose) = b.split_once('-').unwrap_or((b, ""));
+    if config.ignore_whitespace && !a_loose.is_empty() && !b_loose.is_empty() {
+        a_loose == b_loose
+    } else {
+        a_exact == b_exact
+    }
+}
+
+// FNV-1a, which unlike std's hasher is guaranteed stable between releases
+fn stable_hash(canonical: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in canonical.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+// The local file as an Issue, with absent keys left empty
+fn issue_from_file(number: i64, frontmatter: &HashMap<String, String>, body: String) -> Result<Issue> {
+    let frontmatter = frontmatter::Frontmatter::parse(frontmatter)?;
+    Ok(Issue {
+        number,
+        title: frontmatter.title.unwrap_or_default(),
+        body: Some(body),
+        state: frontmatter.state.map(|state| state.to_string()).unwrap_or_default(),
+        state_reason: frontmatter.state_reason.map(|r| r.trim().to_lowercase()).filter(|r| !r.is_empty()),
+        labels: frontmatter.labels.unwrap_or_default(),
+        assignees: frontmatter.assignees.unwrap_or_default(),
+        html_url: String::new(),
+        node_id: String::new(),
+        locked: frontmatter.locked.unwrap_or_default(),
+        comments: 0,
+        participants: None,
+        attachments: None,
+        tasks: None,
+        created_at: frontmatter.created_at,
+        updated_at: frontmatter.updated_at,
+    })
+}
+
+// Bodies are stored the way the push side reads them back (LF line endings,
+// no surrounding whitespace), so an untouched file never differs from GitHub.
+fn normalize_body(body: &str) -> String {
+    body.replace("\r\n", "\n").trim().to_string()
+}
+
+// The body as the unchanged checks see it. With --ignore-whitespace,
+// trailing spaces and the number of blank lines between paragraphs don't
+// count; what is written and pushed is never changed.
+fn comparable_body<'a>(config: &Config, body: &'a str) -> Cow<'a, str> {
+    if config.ignore_whitespace {
+        Cow::Owned(collapse_whitespace(body))
+    } else {
+        Cow::Borrowed(body)
+    }
+}
+
+fn collapse_whitespace(body: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in body.lines().map(str::trim_end) {
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    if lines.last() == Some(&"") {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+// Writes one markdown file per issue. Returns the numbers of issues whose file
+// couldn't be written alongside the summary, so one bad file doesn't stop the rest.
+// `remote_numbers` is every issue on GitHub, given only when the fetch was
+// complete; with --prune, files of other issues are removed.
+fn write_issue_files(
+    config: &Config,
+    issues: &[Issue],
+    remote_numbers: Option<&HashSet<i64>>,
+) -> Result<(SyncSummary, Vec<i64>)> {
+    let mut summary = SyncSummary::default();
+    let mut failures = Vec::new();
+    let mut fingerprints = Vec::new();
+    let state = config.state.get();
+
+    let mut existing_paths = existing_issue_paths(config)?;
+
+    let mut progress = progress::Progress::new(issues.len());
+    for issue in issues {
+        let _step = progress.step();
+        let current_path = existing_paths.remove(&issue.number);
+        let file_path = pulled_issue_path(config, issue, current_path.as_ref());
+        let current_path = current_path.unwrap_or_else(|| file_path.clone());
+        let content = render_issue_file(issue);
+
+        // Leave unchanged files alone so the watcher doesn't see a write
+        let existing = match read_issue_text(config, &current_path) {
+            Ok(existing) => Some(existing),
+            // Never overwrite a file that couldn't be read; it may hold unpushed edits
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                eprintln!("{}", config.colors.warning(&format!("Warning: not updating {}: {}", current_path.display(), e)));
+                continue;
+            }
+            Err(_) => None,
+        };
+        if existing.as_deref() == Some(content.as_str()) && current_path == file_path {
+            fingerprints.push((issue.number, fingerprint(config, issue)));
+            continue;
+        }
+        // A file with includes keeps its directives where GitHub has the
+        // included text; if inlining them gives what GitHub has, the file
+        // is up to date
+        let remote_fingerprint = fingerprint(config, issue);
+        if current_path == file_path
+            && existing
+                .as_deref()
+                .and_then(|existing| include::expanded_fingerprint(config, &current_path, issue.number, existing))
+                .is_some_and(|local| fingerprints_match(config, &local, &remote_fingerprint))
+        {
+            fingerprints.push((issue.number, remote_fingerprint));
+            continue;
+        }
+
+        let schema = match existing.as_deref().map(file_schema_status).transpose() {
+            Ok(schema) => schema,
+            Err(e) => {
+                eprintln!("{}", config.colors.warning(&format!("Warning: not updating {}: {:#}", current_path.display(), e)));
+                continue;
+            }
+        };
+        if let Some(schema::Status::Future(version)) = schema {
+            eprintln!(
+                "{}",
+                config.colors.warning(&format!(
+                    "Warning: {} has schema_version {}, newer than this retasks supports ({}); not updating it",
+                    current_path.display(),
+                    version,
+                    schema::SCHEMA_VERSION
+                ))
+            );
+            continue;
+        }
+
+        // Don't overwrite local edits that haven't been pushed yet
+        if let Some(existing) = &existing {
+            let modified = sidecar::modified(config.metadata, &current_path)?;
+            let keep_local = match plan::resolve_difference(&state, modified, issue.updated_at) {
+                plan::Change::Pull => false,
+                plan::Change::Push => true,
+                plan::Change::Conflict => config.conflict_strategy != plan::ConflictStrategy::RemoteWins,
+            };
+            if keep_local {
+                eprintln!(
+                    "{}",
+                    config.colors.warning(&format!(
+                        "Keeping local changes to issue #{} ({}); they have not been pushed yet",
+                        issue.number,
+                        current_path.display()
+                    ))
+                );
+                // The local edits stay, but in the current frontmatter format
+                if let Some(schema::Status::Outdated(version)) = schema {
+                    migrate_issue_file(config, &current_path, existing, version);
+                }
+                continue;
+            }
+        }
+
+        let moved = existing.is_some() && current_path != file_path;
+        if config.dry_run {
+            if moved {
+                println!("Would move {} to {} for issue #{}", current_path.display(), file_path.display(), issue.number);
+            } else {
+                let action = if existing.is_some() { "update" } else { "create" };
+                println!("Would {} {} for issue #{}", action, file_path.display(), issue.number);
+            }
+            continue;
+        }
+
+        // A failed write only drops this issue; the rest of the sync carries on
+        let written = file_path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| write_issue_text(config, &file_path, &content));
+        if let Err(e) = written {
+            eprintln!("{}", config.colors.error(&format!("Failed to write issue #{} to {}: {}", issue.number, file_path.display(), e)));
+            failures.push(issue.number);
+            continue;
+        }
+        let mut removed = Vec::new();
+        if moved {
+            for path in [current_path.clone(), sidecar::sidecar_path(&current_path)] {
+                if fs::remove_file(&path).is_ok() {
+                    removed.push(path);
+                }
+            }
+            println!("Moved issue #{} from {}", issue.number, current_path.display());
+        }
+
+        if existing.is_some() {
+            summary.updated.push(issue.number);
+        } else {
+            summary.created.push(issue.number);
+        }
+        let sidecar_path = sidecar::sidecar_path(&file_path);
+        let mut written = vec![file_path.clone()];
+        if config.metadata == sidecar::Metadata::Sidecar && sidecar_path.exists() {
+            written.push(sidecar_path);
+        }
+        written.extend(removed);
+        summary.files.insert(issue.number, written);
+        summary.titles.insert(issue.number, issue.title.clone());
+        fingerprints.push((issue.number, fingerprint(config, issue)));
+
+        println!("Synced issue #{} to {}", issue.number, file_path.display());
+    }
+    progress.finish();
+
+    if !config.dry_run {
+        config.state.update(|state| state.fingerprints.extend(fingerprints))?;
+    }
+
+    if let Some(remote_numbers) = remote_numbers.filter(|_| config.prune) {
+        for (number, removed) in prune_local_files(config, remote_numbers)? {
+            summary.pruned.push(number);
+            summary.files.insert(number, removed);
+        }
+    }
+
+    Ok((summary, failures))
+}
+
+// An issue already on disk is updated where it is, even if it was moved
+// into a subdirectory; new issues go at the top level
+fn existing_issue_paths(config: &Config) -> Result<HashMap<i64, PathBuf>> {
+    let mut paths: HashMap<i64, PathBuf> = HashMap::new();
+    for file in scan_issue_files(config)? {
+        if let Some(number) = file.number {
+            paths.entry(number).or_insert(file.path);
+        }
+    }
+    Ok(paths)
+}
+
+// With --group-by an issue whose group label changed moves to its new
+// folder; otherwise it stays wherever it is
+fn pulled_issue_path(config: &Config, issue: &Issue, existing: Option<&PathBuf>) -> PathBuf {
+    match (&config.group_by, existing) {
+        (None, Some(path)) => path.clone(),
+        _ => new_issue_path(config, issue),
+    }
+}
+
+// Where each issue's file will be once `issues` are pulled, including the
+// files of issues that aren't part of this pull
+fn issue_paths(config: &Config, issues: &[Issue]) -> Result<HashMap<i64, PathBuf>> {
+    let mut paths = existing_issue_paths(config)?;
+    for issue in issues {
+        let path = pulled_issue_path(config, issue, paths.get(&issue.number));
+        paths.insert(issue.number, path);
+    }
+    Ok(paths)
+}
+
+// A markdown link target for `to` from the file at `from`, both under the
+// issues directory, so links keep working from files in subdirectories
+fn relative_link(config: &Config, from: &Path, to: &Path) -> String {
+    let depth = from
+        .parent()
+        .and_then(|dir| dir.strip_prefix(&config.issues_dir).ok())
+        .map_or(0, |dir| dir.components().count());
+    let target = to.strip_prefix(&config.issues_dir).unwrap_or(to);
+    format!("{}{}", "../".repeat(depth), target.to_string_lossy().replace('\\', "/"))
+}
+
+// Where a pull or `new` writes an issue that has no file yet: its
+// --group-by folder, or the top of the issues directory
+fn new_issue_path(config: &Config, issue: &Issue) -> PathBuf {
+    match &config.group_by {
+        Some(group_by) => group_by.path(&config.issues_dir, issue),
+        None => config.issues_dir.join(format!("issue-{}.md", issue.number)),
+    }
+}
+
+async fn fetch_issues(config: &Config, since: Option<DateTime<Utc>>) -> Result<Vec<types::Issue>> {
+    if let Some(number) = config.only {
+        let response = config.github.with_rotation(|client| async move {
+            client.issues().get(&config.repo_owner, &config.repo_name, number).await
+        }).await.context(format!("Failed to get issue #{} from GitHub", number))?;
+        return Ok(Some(response.body).filter(|issue| issue.pull_request.is_none()).into_iter().collect());
+    }
+
+    // List the repository's issues. All pages are fetched, so unless
+    // --creator or --mentioned narrow it, the result is the complete set of
+    // issues that pruning compares against. The empty filters are the
+    // milestone (any), the assignee (any; --assignee is applied after
+    // listing so pruning still sees every issue) and the labels (any).
+    let response = config.github.with_rotation(|client| async move {
+        client.issues().list_all_for_repo(
+            &config.repo_owner,
+            &config.repo_name,
+            "",
+            types::IssuesListState::All,
+            "",
+            config.creator.as_deref().unwrap_or_default(),
+            config.mentioned.as_deref().unwrap_or_default(),
+            "",
+            config.sort.clone().unwrap_or(types::IssuesListSort::Created),
+            config.order.clone().unwrap_or(types::Order::Desc),
+            since,
+        ).await
+    }).await.context("Failed to list issues from GitHub")?;
+
+    // GitHub lists pull requests as issues too
+    response.body.into_iter().filter(|listed| listed.pull_request.is_none()).map(issue_from_listing).collect()
+}
+
+// The repository listing returns octorust's IssueSimple, which has every
+// field issue_from_remote reads but a different label type; going through
+// JSON turns it into the Issue that `get` returns.
+fn issue_from_listing(issue: types::IssueSimple) -> Result<types::Issue> {
+    let number = issue.number;
+    serde_json::to_value(issue)
+        .and_then(serde_json::from_value)
+        .context(format!("Failed to read issue #{} from GitHub's response", number))
+}
+
+// Adds the state_reason of closed issues, which octorust doesn't expose.
+// It is read from the REST listing's JSON; the single issue of --only is
+// looked up again for it. A failed lookup only costs the reason, so it is a
+// warning rather than failing the pull.
+async fn fill_state_reasons(config: &Config, issues: &mut [Issue], listed: Option<&HashMap<i64, serde_json::Value>>) {
+    if !issues.iter().any(|issue| issue.state.eq_ignore_ascii_case("closed")) {
+        return;
+    }
+
+    let reasons = match (listed, config.only) {
+        (Some(listed), _) => Ok(listed
+            .iter()
+            .filter_map(|(number, issue)| Some((*number, issue["state_reason"].as_str()?.to_string())))
+            .collect()),
+        (None, Some(number)) => config
+            .github
+            .state_reason(&config.repo_owner, &config.repo_name, number)
+            .await
+            .map(|reason| reason.map(|reason| (number, reason)).into_iter().collect()),
+        (None, None) => return,
+    };
+    let mut reasons: HashMap<i64, String> = match reasons {
+        Ok(reasons) => reasons,
+        Err(e) => {
+            eprintln!("{}", config.colors.warning(&format!("Warning: failed to read state_reason from GitHub: {:#}", e)));
+            return;
+        }
+    };
+
+    for issue in issues.iter_mut().filter(|issue| issue.state.eq_ignore_ascii_case("closed")) {
+        issue.state_reason = reasons.remove(&issue.number);
+    }
+}
+
+// With --safe-prune, each issue that a prune would remove the file of is
+// looked up on its own first. Only a 404 or 410 confirms it was deleted; an
+// issue that is still there, or can't be looked up, is added to
+// `remote_numbers` so its file is kept.
+async fn keep_existing_issues(config: &Config, remote_numbers: &mut HashSet<i64>) -> Result<()> {
+    let missing: Vec<i64> = scan_issue_files(config)?
+        .into_iter()
+        .filter_map(|file| file.number)
+        .filter(|number| !remote_numbers.contains(number) && in_number_range(config, *number))
+        .collect();
+    for number in missing {
+        let result = config
+            .github
+            .with_rotation(|client| async move { client.issues().get(&config.repo_owner, &config.repo_name, number).await })
+            .await;
+        match result {
+            Ok(_) => println!("Issue #{} is still on GitHub though the listing left it out; keeping its file", number),
+            Err(error::SyncError::Http { status: 404 | 410, .. }) => continue,
+            Err(e) => eprintln!(
+                "{}",
+                config.colors.warning(&format!(
+                    "Warning: could not check whether issue #{} was deleted ({:#}); keeping its file",
+                    number, e
+                ))
+            ),
+        }
+        remote_numbers.insert(number);
+    }
+    Ok(())
+}
+
+// Removes files for issues that are gone from GitHub. Only files with a
+// `number` key are considered; anything else in the directory is never touched.
+// Returns the pruned issues with the files removed for each.
+fn prune_local_files(config: &Config, remote_numbers: &HashSet<i64>) -> Result<Vec<(i64, Vec<PathBuf>)>> {
+    let mut pruned = Vec::new();
+
+    for file in scan_issue_files(config)? {
+        let Some(number) = file.number else { continue };
+        // Files outside --number-min/--number-max aren't this mirror's to remove
+        if remote_numbers.contains(&number) || !in_number_range(config, number) {
+            continue;
+        }
+
+        if config.dry_run {
+            println!("Would prune issue #{} ({})", number, file.path.display());
+            continue;
+        }
+
+        fs::remove_file(&file.path).context(format!("Failed to remove {}", file.path.display()))?;
+        let sidecar_path = sidecar::sidecar_path(&file.path);
+        let mut removed = vec![file.path.clone()];
+        if config.metadata == sidecar::Metadata::Sidecar && sidecar_path.exists() {
+            fs::remove_file(&sidecar_path).context(format!("Failed to remove {}", sidecar_path.display()))?;
+            removed.push(sidecar_path);
+        }
+        println!("Pruned issue #{} ({})", number, file.path.display());
+        pruned.push((number, removed));
+    }
+
+    Ok(pruned)
+}
+
+// Issue files may be kept in subdirectories, e.g. sorted by hand into
+// folders. Hidden directories, `assets/` and the stage directory are skipped.
+fn scan_issue_files(config: &Config) -> Result<Vec<LocalFile>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![config.issues_dir.clone()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).context(format!("Failed to read directory: {}", dir.display()))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                let skipped = path == config.issues_dir.join(attachments::ASSETS_DIR)
+                    || (config.sync_milestones && path == milestones::dir(config))
+                    || config.stage_dir.as_deref() == Some(path.as_path());
+                if !hidden && !skipped {
+                    dirs.push(path);
+                }
+                continue;
+            }
+            if let Some(file) = read_local_file(config, path) {
+                files.push(file);
+            }
+        }
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+fn read_local_file(config: &Config, path: PathBuf) -> Option<LocalFile> {
+    if !path.is_file() || path.extension().is_none_or(|ext| ext != "md") {
+        return None;
+    }
+
+    let number = read_issue_text(config, &path)
+        .ok()
+        .and_then(|content| parse_markdown_file(&content).ok())
+        .and_then(|(frontmatter, _)| frontmatter.get("number").and_then(|n| n.parse::<i64>().ok()));
+    Some(LocalFile { path, number })
+}
+
+// Markdown files without a `number` key weren't written by retasks. Pointing
+// --issues-dir at e.g. a docs folder is easy to do by accident, so say so up front.
+fn warn_about_foreign_files(config: &Config) -> Result<()> {
+    let foreign: Vec<LocalFile> = scan_issue_files(config)?
+        .into_iter()
+        .filter(|file| file.number.is_none())
+        .collect();
+
+    if foreign.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!(
+        "{}",
+        config.colors.warning(&format!(
+            "Warning: {} contains {} markdown file(s) without retasks frontmatter (no `number` key):",
+            config.issues_dir.display(),
+            foreign.len()
+        ))
+    );
+    for file in &foreign {
+        match read_issue_text(config, &file.path) {
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => eprintln!("  {} ({})", file.path.display(), e),
+            _ => eprintln!("  {}", file.path.display()),
+        }
+    }
+    eprintln!("They are left alone by --prune. Check that --issues-dir points at the right directory.");
+
+    Ok(())
+}
+
+// A push only sees the files that are there, so a directory that missed a
+// pull looks complete. Listing GitHub's issues is an extra request, hence
+// behind --warn-missing-local; if it fails, the push goes ahead anyway.
+async fn warn_about_missing_local(config: &Config) {
+    let missing = match missing_local_issues(config).await {
+        Ok(missing) => missing,
+        Err(e) => {
+            eprintln!("{}", config.colors.warning(&format!("Warning: failed to check for issues missing locally: {:#}", e)));
+            return;
+        }
+    };
+    if missing.is_empty() {
+        return;
+    }
+
+    let numbers: Vec<String> = missing.iter().map(|number| format!("#{}", number)).collect();
+    eprintln!(
+        "{}",
+        config.colors.warning(&format!(
+            "Warning: {} issue(s) on GitHub have no local file: {}. Run a pull to fetch them.",
+            missing.len(),
+            numbers.join(", ")
+        ))
+    );
+}
+
+// Issues on GitHub without a file in the issues directory, in ascending
+// order. With --assignee, only the user's issues are expected locally, and
+// with --number-min or --number-max only those in the range.
+async fn missing_local_issues(config: &Config) -> Result<Vec<i64>> {
+    if config.format.data_file_name().is_some() {
+        return Ok(Vec::new());
+    }
+
+    let local: HashSet<i64> = scan_issue_files(config)?.into_iter().filter_map(|file| file.number).collect();
+    let login = assignee_login(config).await?;
+    let mut missing: Vec<i64> = fetch_issues(config, None)
+        .await?
+        .into_iter()
+        .map(|issue| issue_from_remote(config, issue))
+        .filter(|issue| login.as_deref().is_none_or(|login| is_assigned(issue, login)))
+        .map(|issue| issue.number)
+        .filter(|number| !local.contains(number) && in_number_range(config, *number))
+        .collect();
+    missing.sort();
+    Ok(missing)
+}
+
+// Local edits made while the watcher was down aren't seen as file events, so
+// point them out on startup rather than letting them go unnoticed.
+fn warn_about_offline_edits(config: &Config) -> Result<()> {
+    let Some(last_event_at) = config.state.get().last_event_at else {
+        return Ok(());
+    };
+
+    for file in scan_issue_files(config)? {
+        let Some(number) = file.number else { continue };
+        let modified = sidecar::modified(config.metadata, &file.path)?;
+        if modified > last_event_at {
+            eprintln!(
+                "{}",
+                config.colors.warning(&format!(
+                    "Warning: issue #{} ({}) was modified while retasks was not running; save it again to push it",
+                    number,
+                    file.path.display()
+                ))
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// With --push-on-startup, local edits made while the watcher was down are
+// pushed before the first pull instead of waiting for the file to be saved
+// again. These are the files whose content no longer matches the last sync,
+// and the bulk check applies as for `push`. An issue that changed on GitHub
+// too follows --conflict-strategy: local-wins pushes it, remote-wins leaves
+// it to the pull and skip leaves it alone. Returns the updated issues; a
+// file that fails is only reported.
+async fn push_offline_edits(config: &Config) -> Result<Vec<i64>> {
+    let state = config.state.get();
+    if state.last_event_at.is_none() {
+        return Ok(Vec::new());
+    }
+
+    bulk::check(config)?;
+    // Collected before anything is pushed, since each push moves the cursor
+    // on. Only files whose content changed are looked up on GitHub.
+    let mut edited = Vec::new();
+    for (path, number) in bulk::changed(config)? {
+        let modified = sidecar::modified(config.metadata, &path)?;
+        edited.push((number, path, modified));
+    }
+
+    let mut updated = Vec::new();
+    for (number, path, modified) in edited {
+        let remote = config.github.with_rotation(|client| async move {
+            client.issues().get(&config.repo_owner, &config.repo_name, number).await
+        }).await.context(format!("Failed to get issue #{} from GitHub", number));
+        let result = match remote {
+            Ok(remote) => match plan::resolve_difference(&state, modified, remote.body.updated_at.map(|at| at.fixed_offset())) {
+                plan::Change::Conflict if config.conflict_strategy == plan::ConflictStrategy::RemoteWins => {
+                    eprintln!(
+                        "{}",
+                        config.colors.warning(&format!(
+                            "Issue #{} ({}) also changed on GitHub; not pushing it (--conflict-strategy remote-wins)",
+                            number,
+                            path.display()
+                        ))
+                    );
+                    continue;
+                }
+                plan::Change::Conflict if config.conflict_strategy == plan::ConflictStrategy::Skip => {
+                    eprintln!(
+                        "{}",
+                        config.colors.warning(&format!(
+                            "Warning: issue #{} ({}) changed both locally and on GitHub; skipping it (--conflict-strategy skip)",
+                            number,
+                            path.display()
+                        ))
+                    );
+                    continue;
+                }
+                _ => sync_local_to_github(config, &path).await,
+            },
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(Some(number)) => updated.push(number),
+            Ok(None) => {}
+            Err(e) => eprintln!("{}", config.colors.error(&format!("Error pushing {}: {:#}", path.display(), e))),
+        }
+    }
+    Ok(updated)
+}
+
+// Files without parseable frontmatter count as current; they are reported elsewhere
+fn file_schema_status(content: &str) -> Result<schema::Status> {
+    match parse_markdown_file(content) {
+        Ok((frontmatter, _)) => schema::status(&frontmatter),
+        Err(_) => Ok(schema::Status::Current),
+    }
+}
+
+// A failed migration only leaves the old file in place; it still reads fine
+fn migrate_issue_file(config: &Config, file_path: &Path, content: &str, version: u32) {
+    if config.dry_run {
+        println!("Would migrate {} from schema_version {}", file_path.display(), version);
+        return;
+    }
+
+    let result = schema::migrate(content, version)
+        .and_then(|migrated| Ok(write_issue_text(config, file_path, &migrated)?));
+    match result {
+        Ok(()) => println!(
+            "Migrated {} from schema_version {} to {}",
+            file_path.display(),
+            version,
+            schema::SCHEMA_VERSION
+        ),
+        Err(e) => eprintln!(
+            "{}",
+            config.colors.warning(&format!("Warning: failed to migrate {}: {:#}", file_path.display(), e))
+        ),
+    }
+}
+
+fn render_issue_file(issue: &Issue) -> String {
+    // Create frontmatter with issue metadata
+    let mut content = format!(
+        "---\nnumber: {}\ntitle: {}\nstate: {}\n",
+        issue.number,
+        format_scalar(&issue.title),
+        issue.state
+    );
+    if let Some(reason) = &issue.state_reason {
+        content.push_str(&format!("state_reason: {}\n", reason));
+    }
+    content.push_str(&format!("labels: {}\n", format_list(&issue.labels)));
+    content.push_str(&format!("assignees: {}\n", format_list(&issue.assignees)));
+
+    // Read-only link back to GitHub, ignored on push
+    if !issue.html_url.is_empty() {
+        content.push_str(&format!("html_url: {}\n", issue.html_url));
+    }
+    if !issue.node_id.is_empty() {
+        content.push_str(&format!("node_id: {}\n", issue.node_id));
+    }
+    content.push_str(&format!("locked: {}\n", issue.locked));
+    content.push_str(&format!("comments: {}\n", issue.comments));
+    if let Some(participants) = &issue.participants {
+        content.push_str(&format!("participants: {}\n", format_list(participants)));
+    }
+    if let Some(attachments) = &issue.attachments {
+        content.push_str(&format!("attachments: {}\n", format_list(attachments)));
+    }
+    if let Some(tasks) = &issue.tasks {
+        content.push_str(&format!("tasks: {}\n", tasks));
+    }
+    if let Some(at) = issue.created_at {
+        content.push_str(&format!("created_at: {}\n", at.to_rfc3339_opts(SecondsFormat::Secs, true)));
+    }
+    if let Some(at) = issue.updated_at {
+        content.push_str(&format!("updated_at: {}\n", at.to_rfc3339_opts(SecondsFormat::Secs, true)));
+    }
+    content.push_str(&format!("schema_version: {}\n", schema::SCHEMA_VERSION));
+    content.push_str("---\n\n");
+
+    // Append issue body
+    if let Some(body) = &issue.body {
+        content.push_str(body);
+    }
+
+    content
+}
+
+// Reads a local issue file. Files that aren't valid UTF-8 are decoded with
+// --input-encoding if one is given, and are an InvalidData error otherwise.
+// With --metadata sidecar, the sidecar is joined in so callers always see
+// inline frontmatter.
+fn read_issue_text(config: &Config, path: &Path) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    let content = match (String::from_utf8(bytes), config.input_encoding) {
+        (Ok(content), _) => content,
+        (Err(e), Some(encoding)) => encoding.decode_without_bom_handling(e.as_bytes()).0.into_owned(),
+        (Err(_), None) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "file is not valid UTF-8; pass --input-encoding (e.g. windows-1251) to read it",
+            ))
+        }
+    };
+    match config.metadata {
+        sidecar::Metadata::Inline => Ok(content),
+        sidecar::Metadata::Sidecar => sidecar::read(path, content),
+    }
+}
+
+// Writes an issue file given with inline frontmatter. With --metadata
+// sidecar, the frontmatter goes to the sidecar first, then the body.
+fn write_issue_text(config: &Config, path: &Path, content: &str) -> io::Result<()> {
+    if config.metadata == sidecar::Metadata::Inline {
+        return write_file_with_retry(path, content, config.io_retries);
+    }
+    let (body, json) = sidecar::split(content);
+    if let Some(json) = json {
+        write_file_with_retry(&sidecar::sidecar_path(path), &json, config.io_retries)?;
+    }
+    write_file_with_retry(path, &body, config.io_retries)
+}
+
+fn write_file_with_retry(path: &Path, content: &str, retries: u32) -> io::Result<()> {
+    retry_io(retries, || write_atomically(path, content.as_bytes()))
+}
+
+// Writes to a temporary file next to `path` and renames it into place, so
+// neither the watcher nor someone reading the file ever sees it half
+// written, even if the process is killed mid-write. The temporary name is
+// hidden and doesn't end in .md, so scans and the watcher ignore it.
+fn write_atomically(path: &Path, content: &[u8]) -> io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        // Keep the permissions of the file being replaced
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp_path, metadata.permissions())?;
+        }
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+// Upper bound for --io-retries; with the capped backoff below a write gives up
+// after roughly ten seconds of waiting.
+const MAX_IO_RETRIES: u32 = 8;
+const IO_RETRY_MAX_DELAY: Duration = Duration::from_secs(2);
+
+// Retries an I/O operation that failed with a transient error (lock contention,
+// EAGAIN on network filesystems) with a short exponential backoff, each wait
+// capped at IO_RETRY_MAX_DELAY. Permanent errors such as permission denied are
+// returned straight away.
+fn retry_io<T>(retries: u32, mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(e) if attempt < retries.min(MAX_IO_RETRIES) && is_transient_io_error(&e) => {
+                attempt += 1;
+                thread::sleep(Duration::from_millis(50 << attempt.min(6)).min(IO_RETRY_MAX_DELAY));
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient_io_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ResourceBusy
+    )
+}
+
+// Pushes every changed issue file, up to --push-concurrency at a time. Files
+// that still match the last sync are skipped by their fingerprint, and state
+// updates go through the state store's lock, so concurrent pushes are safe.
+// Returns the updated issues; one failed file doesn't stop the others.
+async fn push_all_files(config: &Config) -> Result<Vec<i64>> {
+    if config.format.data_file_name().is_some() {
+        return format::push_data_file(config).await;
+    }
+
+    bulk::check(config)?;
+    let files = scan_issue_files(config)?.into_iter().filter(|file| file.number.is_some());
+    let results: Vec<(PathBuf, Result<Option<i64>>)> = futures::stream::iter(files)
+        .map(|file| async move {
+            let result = sync_local_to_github(config, &file.path).await;
+            (file.path, result)
+        })
+        .buffer_unordered(config.push_concurrency)
+        .collect()
+        .await;
+
+    let mut updated = Vec::new();
+    let mut failures = Vec::new();
+    for (path, result) in results {
+        match result {
+            Ok(Some(number)) => updated.push(number),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("{}", config.colors.error(&format!("Error pushing {}: {:#}", path.display(), e)));
+                failures.push(path);
+            }
+        }
+    }
+    updated.sort();
+
+    println!("{}", config.colors.success(&format!("Push complete: {} updated, {} failed", updated.len(), failures.len())));
+    if !failures.is_empty() {
+        return Err(anyhow::anyhow!("Failed to push {} file(s)", failures.len()));
+    }
+    // Milestone files aren't issue files, so they are sent on their own
+    if config.sync_milestones {
+        milestones::push(config).await?;
+    }
+    Ok(updated)
+}
+
+// Returns the number of the issue that was updated, or None if the path isn't an issue file
+async fn sync_local_to_github(config: &Config, file_path: &Path) -> Result<Option<i64>> {
+    push_local_file(config, file_path, config.stage_dir.as_deref()).await
+}
+
+// With `stage_dir`, the edit is staged for `apply` rather than pushed
+async fn push_local_file(config: &Config, file_path: &Path, stage_dir: Option<&Path>) -> Result<Option<i64>> {
+    if !file_path.is_file() || file_path.extension().is_none_or(|ext| ext != "md") {
+        return Ok(None);
+    }
+
+    // Held until the push is recorded, so a pull can't rewrite the file between
+    // reading it and updating the issue
+    let _lock = match read_local_file(config, file_path.to_path_buf()).and_then(|file| file.number) {
+        Some(number) => Some(config.issue_locks.lock(number).await),
+        None => None,
+    };
+
+    // One file in an unknown encoding is skipped rather than failing the whole scan
+    let content = match read_issue_text(config, file_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            eprintln!("{}", config.colors.warning(&format!("Warning: not pushing {}: {}", file_path.display(), e)));
+            return Ok(None);
+        }
+        Err(e) => return Err(error::SyncError::io(file_path, e).into()),
+    };
+    push_issue_text(config, file_path, &content, stage_dir).await
+}
+
+// Pushes an issue file's `content` with every check a push makes, and records
+// it as synced. `push -` sends piped text through here too, with `file_path`
+// naming it in messages; the caller holds the issue's lock.
+async fn push_issue_text(config: &Config, file_path: &Path, content: &str, stage_dir: Option<&Path>) -> Result<Option<i64>> {
+    // A bad merge is never pushed; `verify` lists where the markers are
+    if !markers::find(content).is_empty() {
+        return Err(anyhow::anyhow!("{} has merge conflict markers; resolve them before pushing", file_path.display()));
+    }
+
+    // Parse frontmatter and body
+    let (mut frontmatter, body) = parse_markdown_file(content).context("Failed to parse markdown file")?;
+    config.state_map.apply(&mut frontmatter).map_err(|e| error::SyncError::parse(file_path, e))?;
+    // Pushed with included files inlined, so editing one changes the fingerprint
+    let body = include::expand(config, file_path, &body).map_err(|e| error::SyncError::parse(file_path, e))?;
+
+    // Extract issue number from filename or frontmatter
+    let parsed = frontmatter::Frontmatter::parse(&frontmatter)
+        .map_err(|e| error::SyncError::parse(file_path, e))?;
+    let issue_number = parsed
+        .number
+        .ok_or_else(|| error::SyncError::parse(file_path, anyhow::anyhow!("no `number` key")))?;
+
+    if config.only.is_some_and(|only| only != issue_number) {
+        return Ok(None);
+    }
+
+    if let schema::Status::Future(version) = schema::status(&frontmatter)
+        .map_err(|e| error::SyncError::parse(file_path, e))?
+    {
+        eprintln!(
+            "{}",
+            config.colors.warning(&format!(
+                "Warning: {} has schema_version {}, newer than this retasks supports ({}); not pushing it",
+                file_path.display(),
+                version,
+                schema::SCHEMA_VERSION
+            ))
+        );
+        return Ok(None);
+    }
+
+    // Skip files that still match what was last pulled or pushed, such as the
+    // watcher seeing our own pull write the file
+    let local_fingerprint = fingerprint(
+        config,
+        &issue_from_file(issue_number, &frontmatter, body.clone())
+            .map_err(|e| error::SyncError::parse(file_path, e))?,
+    );
+    let stored = config.state.get().fingerprints.get(&issue_number).cloned();
+    if stored.is_some_and(|stored| fingerprints_match(config, &stored, &local_fingerprint)) {
+        println!("Issue #{} is unchanged since the last sync, not pushing", issue_number);
+        return Ok(None);
+    }
+
+    // Checked after the fingerprint, so our own pulls of closed issues don't warn
+    if config.readonly_closed && parsed.state == Some(types::State::Closed) {
+        eprintln!(
+            "{}",
+            config.colors.warning(&format!(
+                "Warning: ignoring changes to closed issue #{} ({}) because of --readonly-closed; run `retasks reopen {}` to reopen it",
+                issue_number,
+                file_path.display(),
+                issue_number
+            ))
+        );
+        return Ok(None);
+    }
+
+    let (update, state_reason) = prepare_update(config, issue_number, &frontmatter, body, file_path).await?;
+
+    if config.dry_run {
+        println!("Would update issue #{} from {} with {}", issue_number, file_path.display(), serde_json::to_string(&update)?);
+        if let Some(reason) = state_reason {
+            println!("Would set state_reason of issue #{} to {}", issue_number, reason);
+        }
+        return Ok(None);
+    }
+
+    if let Some(stage_dir) = stage_dir {
+        stage::stage_change(config, stage_dir, issue_number, file_path, content, &local_fingerprint).await?;
+        return Ok(None);
+    }
+
+    let result = update_issue(config, issue_number, &update, state_reason).await;
+
+    // Kept for cache::push_queued to retry once GitHub is reachable again;
+    // piped text has no file to retry from
+    if let Err(e) = &result {
+        if config.offline_fallback && cache::is_unreachable(e) && file_path.is_file() {
+            eprintln!(
+                "{}",
+                config.colors.warning(&format!("Warning: GitHub is unreachable; queued issue #{} to push later", issue_number))
+            );
+            config.state.update(|state| {
+                state.queued_pushes.insert(issue_number, file_path.to_path_buf());
+            })?;
+            return Ok(None);
+        }
+    }
+    result?;
+
+    println!("{}", config.colors.success(&format!("Updated issue #{} on GitHub from {}", issue_number, file_path.display())));
+    config.state.update(|state| {
+        state.last_event_at = Some(Utc::now());
+        state.fingerprints.insert(issue_number, local_fingerprint);
+        state.queued_pushes.remove(&issue_number);
+    })?;
+    Ok(Some(issue_number))
+}
+
+// The update request for an issue's frontmatter and body, and the
+// state_reason to set with it. The body is turned back into what GitHub
+// holds: timeline and wrapping removed, attachment URLs and refs restored.
+// `source` names where it came from in errors.
+async fn prepare_update(
+    config: &Config,
+    issue_number: i64,
+    frontmatter: &HashMap<String, String>,
+    body: String,
+    source: &Path,
+) -> Result<(types::IssuesUpdateRequest, Option<&'static str>)> {
+    let body = if config.include_timeline { timeline::strip(&body) } else { body };
+    let body = match config.wrap_body {
+        Some(width) => wrap::unwrap(&body, width),
+        None => body,
+    };
+    let body = attachments::restore_remote_urls(config, &body)?;
+    let body = if config.rewrite_refs { refs::restore(&body) } else { body };
+    let mut update = build_update_request(frontmatter, body)
+        .map_err(|e| error::SyncError::parse(source, e))?;
+    config.push_fields.apply(&mut update);
+    labels::prepare(config, issue_number, &mut update).await;
+    let state_reason = parse_state_reason(frontmatter)
+        .map_err(|e| error::SyncError::parse(source, e))?
+        .filter(|_| config.push_fields.includes("state"));
+    Ok((update, state_reason))
+}
+
+async fn update_issue(
+    config: &Config,
+    issue_number: i64,
+    update: &types::IssuesUpdateRequest,
+    state_reason: Option<&str>,
+) -> Result<()> {
+    config.github.with_rotation(|client| async move {
+        client.issues().update(
+            &config.repo_owner,
+            &config.repo_name,
+            issue_number,
+            update,
+        ).await
+    }).await.context(format!("Failed to update issue #{} on GitHub", issue_number))?;
+
+    if let (Some(reason), Some(state)) = (state_reason, &update.state) {
+        config.github
+            .update_state_reason(&config.repo_owner, &config.repo_name, issue_number, &state.to_string(), reason)
+            .await?;
+    }
+    Ok(())
+}
+
+async fn set_issue_state(config: &Config, issue_number: i64, state: types::State) -> Result<()> {
+    if config.dry_run {
+        println!("Would set issue #{} to {}", issue_number, state);
+        return Ok(());
+    }
+
+    let update = &state_change_request(state.clone());
+    config.github.with_rotation(|client| async move {
+        client.issues().update(
+            &config.repo_owner,
+            &config.repo_name,
+            issue_number,
+            update,
+        ).await
+    }).await.context(format!("Failed to update state of issue #{} on GitHub", issue_number))?;
+
+    println!("{}", config.colors.success(&format!("Issue #{} is now {}", issue_number, state)));
+    Ok(())
+}
+
+// Creates an issue from the template (if any) and writes its file, so it can
+// be edited like any pulled issue. The template's title is a prefix such as
+// "[BUG] ", so `title` is appended to it.
+//
+// The body carries a hidden create id, saved in the state file before the
+// request is sent. If an earlier attempt with the same title got as far as
+// creating the issue, that issue is adopted rather than created again.
+// Returns the number of the created issue, or None in a dry run
+async fn create_issue(config: &Config, title: Option<&str>, template: Option<&template::IssueTemplate>) -> Result<Option<i64>> {
+    let template_title = template.map(|t| t.title.as_str()).unwrap_or_default();
+    let title = non_empty_title(&format!("{}{}", template_title, title.unwrap_or_default()))
+        .context("Pass --title, or a --template with a title")?;
+
+    let pending = config.state.get().pending_creates.into_iter().find(|(_, t)| *t == title).map(|(id, _)| id);
+    let create_id = pending.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let marker = create_marker(&create_id);
+    let mut body = template.map(|t| t.body.clone()).unwrap_or_default();
+    if config.no_body_on_create && !body.trim().is_empty() {
+        println!("Leaving out the body of \"{}\" (--no-body-on-create); write it on GitHub, or pass --with-body", title);
+        body.clear();
+    }
+
+    let request = &types::IssuesCreateRequest {
+        title: types::TitleOneOf::String(title.clone()),
+        body: if body.is_empty() { marker.clone() } else { format!("{}\n\n{}", body, marker) },
+        assignee: String::new(),
+        assignees: template.map(|t| t.assignees.clone()).unwrap_or_default(),
+        milestone: None,
+        labels: template
+            .map(|t| t.labels.iter().cloned().map(types::IssuesCreateRequestLabelsOneOf::String).collect())
+            .unwrap_or_default(),
+    };
+
+    if config.dry_run {
+        println!("Would create an issue with {}", serde_json::to_string(request)?);
+        return Ok(None);
+    }
+
+    let adopted = match pending {
+        Some(_) => find_created_issue(config, &marker).await?,
+        None => None,
+    };
+    let created = match adopted {
+        Some(number) => {
+            println!("Issue #{} was already created by an earlier attempt, adopting it", number);
+            config.github.with_rotation(|client| async move {
+                client.issues().get(&config.repo_owner, &config.repo_name, number).await
+            }).await.context(format!("Failed to get issue #{} from GitHub", number))?.body
+        }
+        None => {
+            config.state.update(|state| {
+                state.pending_creates.insert(create_id.clone(), title.clone());
+            })?;
+            let created = config.github.with_rotation(|client| async move {
+                client.issues().create(&config.repo_owner, &config.repo_name, request).await
+            }).await.context("Failed to create issue on GitHub")?.body;
+            println!("{}", config.colors.success(&format!("Created issue #{} ({})", created.number, created.html_url)));
+            created
+        }
+    };
+    let issue = issue_from_remote(config, created);
+
+    // The structured formats pick the issue up on the next pull
+    if config.format != format::Format::Markdown {
+        config.state.update(|state| {
+            state.pending_creates.remove(&create_id);
+        })?;
+        return Ok(Some(issue.number));
+    }
+
+    create_issues_dir(&config.issues_dir)?;
+    let file_path = new_issue_path(config, &issue);
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent).context(format!("Failed to create {}", parent.display()))?;
+    }
+    write_issue_text(config, &file_path, &render_issue_file(&issue))
+        .context(format!("Failed to write {}", file_path.display()))?;
+    config.state.update(|state| {
+        state.fingerprints.insert(issue.number, fingerprint(config, &issue));
+        state.pending_creates.remove(&create_id);
+    })?;
+    println!("Wrote issue #{} to {}", issue.number, file_path.display());
+    Ok(Some(issue.number))
+}
+
+fn create_marker(create_id: &str) -> String {
+    format!("<!-- retasks-create-id: {} -->", create_id)
+}
+
+// Looks for the marker among the most recently created open issues, which is
+// where an issue from an interrupted `new` would be. The search API isn't
+// used since it lags behind new issues.
+async fn find_created_issue(config: &Config, marker: &str) -> Result<Option<i64>> {
+    let recent = config.github.with_rotation(|client| async move {
+        client.issues().list_for_repo(
+            &config.repo_owner,
+            &config.repo_name,
+            "",
+            types::IssuesListState::Open,
+            "",
+            "",
+            "",
+            "",
+            types::IssuesListSort::Created,
+            types::Order::Desc,
+            None,
+            100,
+            1,
+        ).await
+    }).await.context("Failed to list recent issues on GitHub")?.body;
+
+    Ok(recent.into_iter().find(|issue| issue.body.contains(marker)).map(|issue| issue.number))
+}
+
+// An update that only carries the state. Every other field is left empty, which
+// octorust skips when serializing, so GitHub keeps the existing title, body and labels.
+fn state_change_request(state: types::State) -> types::IssuesUpdateRequest {
+    types::IssuesUpdateRequest {
+        title: None,
+        body: String::new(),
+        state: Some(state),
+        assignee: String::new(),
+        assignees: vec![],
+        milestone: None,
+        labels: vec![],
+    }
+}
+
+// Builds the update for an issue from a structured data file, where every
+// field is always present.
+fn update_request_for_issue(issue: &Issue) -> Result<types::IssuesUpdateRequest> {
+    let state = parse_state(&issue.state).context(format!("Issue #{}", issue.number))?;
+
+    let title = non_empty_title(&issue.title).context(format!("Issue #{}", issue.number))?;
+
+    Ok(types::IssuesUpdateRequest {
+        title: Some(types::TitleOneOf::String(title)),
+        body: issue.body.clone().unwrap_or_default(),
+        state: Some(state),
+        assignee: String::new(),
+        assignees: issue.assignees.clone(),
+        milestone: None,
+        labels: issue.labels.iter()
+            .cloned()
+            .map(types::IssuesCreateRequestLabelsOneOf::String)
+            .collect(),
+    })
+}
+
+// Builds the update for a local file. Only keys present in the frontmatter are sent,
+// so a file without e.g. a `labels` line leaves the remote labels untouched.
+fn build_update_request(frontmatter: &HashMap<String, String>, body: String) -> Result<types::IssuesUpdateRequest> {
+    // A typo'd state is an error rather than silently leaving the state alone
+    let frontmatter = frontmatter::Frontmatter::parse(frontmatter)?;
+
+    // Create update request with required empty string for assignee
+    let mut update = types::IssuesUpdateRequest {
+        title: None,
+        body, // No need for Some() wrapper here as the type is String, not Option<String>
+        state: frontmatter.state,
+        assignee: String::new(),
+        assignees: vec![],
+        milestone: None,
+        labels: vec![],
+    };
+
+    // Set title if available
+    if let Some(title) = &frontmatter.title {
+        update.title = Some(types::TitleOneOf::String(non_empty_title(title)?));
+    }
+
+    // Process labels
+    if let Some(assignees) = frontmatter.assignees {
+        update.assignees = assignees;
+    }
+
+    if let Some(labels) = frontmatter.labels {
+        if !labels.is_empty() {
+            update.labels = labels.into_iter()
+                .map(types::IssuesCreateRequestLabelsOneOf::String)
+                .collect();
+        }
+    }
+
+    Ok(update)
+}
+
+fn parse_state(value: &str) -> Result<types::State> {
+    match value.trim().to_lowercase().as_str() {
+        "closed" => Ok(types::State::Closed),
+        "open" => Ok(types::State::Open),
+        _ => Err(anyhow::anyhow!("Invalid state {:?} (expected open or closed)", value)),
+    }
+}
+
+// The optional `state_reason` key. GitHub only accepts `completed` and
+// `not_planned` on a closed issue and `reopened` on an open one.
+fn parse_state_reason(frontmatter: &HashMap<String, String>) -> Result<Option<&'static str>> {
+    let Some(value) = frontmatter.get("state_reason").map(|r| unquote_scalar(r)) else {
+        return Ok(None);
+    };
+    let state = frontmatter.get("state").map(|s| unquote_scalar(s));
+    check_state_reason(&value, state.as_deref())
+}
+
+fn check_state_reason(value: &str, state: Option<&str>) -> Result<Option<&'static str>> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let reason = match value.trim().to_lowercase().as_str() {
+        "completed" => "completed",
+        "not_planned" => "not_planned",
+        "reopened" => "reopened",
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Invalid state_reason {:?} (expected completed, not_planned or reopened)",
+                value
+            ))
+        }
+    };
+
+    match (state.map(parse_state).transpose()?, reason) {
+        (Some(types::State::Closed), "completed" | "not_planned") | (Some(types::State::Open), "reopened") => Ok(Some(reason)),
+        _ => Err(anyhow::anyhow!("state_reason {} does not go with state {}", reason, state.unwrap_or("(missing)"))),
+    }
+}
+
+// GitHub rejects updates with an empty title, so catch it before sending
+fn non_empty_title(title: &str) -> Result<String> {
+    if title.trim().is_empty() {
+        return Err(anyhow::anyhow!("Title is empty; GitHub does not allow issues without a title"));
+    }
+    Ok(title.to_string())
+}
+
+// Frontmatter values are written bare unless reading them back would change
+// them: empty or padded values and values starting with a quote are quoted.
+fn format_scalar(value: &str) -> String {
+    if value.is_empty() || value.trim() != value || value.starts_with('"') || value.starts_with('\'') {
+        serde_json::to_string(value).unwrap()
+    } else {
+        value.to_string()
+    }
+}
+
+fn unquote_scalar(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        if let Ok(unquoted) = serde_json::from_str::<String>(value) {
+            return unquoted;
+        }
+    }
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return value[1..value.len() - 1].replace("''", "'");
+    }
+    value.to_string()
+}
+
+fn label_name(label: &types::LabelsOneOf) -> Option<String> {
+    match label {
+        types::LabelsOneOf::String(name) => Some(name.clone()),
+        types::LabelsOneOf::LabelsData(data) if !data.name.is_empty() => Some(data.name.clone()),
+        types::LabelsOneOf::LabelsData(_) => None,
+    }
+}
+
+// Labels and assignees are written as a YAML flow sequence. Names that YAML
+// would split or reinterpret (commas, brackets, quotes, `true`, ...) are double-quoted.
+fn format_list(labels: &[String]) -> String {
+    let items: Vec<String> = labels
+        .iter()
+        .map(|label| {
+            if is_plain_label(label) {
+                label.clone()
+            } else {
+                serde_json::to_string(label).unwrap()
+            }
+        })
+        .collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn is_plain_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.trim() == label
+        && label.chars().all(|c| c.is_alphanumeric() || " -_./".contains(c))
+        && !label.starts_with('-')
+        && matches!(serde_yaml::from_str::<serde_yaml::Value>(label), Ok(serde_yaml::Value::String(_)))
+}
+
+fn parse_list(value: &str) -> Result<Vec<String>> {
+    let labels: Vec<String> = serde_yaml::from_str(value)
+        .map_err(|e| anyhow::anyhow!("Invalid list {}: {}", value, e))?;
+    Ok(labels.into_iter().filter(|label| !label.is_empty()).collect())
+}
+
+fn parse_markdown_file(content: &str) -> Result<(HashMap<String, String>, String)> {
+    let mut frontmatter = HashMap::new();
+    // Some Windows editors start UTF-8 files with a byte order mark, which
+    // would hide the opening `---`
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+
+    let body = if let Some((block, end)) = frontmatter::block(content) {
+        for line in block.lines() {
+            if let Some(index) = line.find(':') {
+                let key = line[..index].trim().to_string();
+                let value = line[index + 1..].trim().to_string();
+                frontmatter.insert(key, value);
+            }
+        }
+
+        // Everything after the closing `---`
+        content[end + 3..].trim().to_string()
+    } else {
+        // No frontmatter, or no end marker for it
+        content.to_string()
+    };
+
+    Ok((frontmatter, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    pub fn test_config(server: &MockServer, issues_dir: &Path) -> Config {
+        Config {
+            github: github::GitHub::new(vec!["test-token".to_string()], Some(&server.uri()), Default::default()).unwrap(),
+            repo_owner: "owner".to_string(),
+            repo_name: "repo".to_string(),
+            issues_dir: issues_dir.to_path_buf(),
+            watch: false,
+            periodic_sync: true,
+            watch_files: true,
+            sync_interval: Duration::from_secs(300),
+            io_retries: 0,
+            post_sync_hook: None,
+            notify: None,
+            report: None,
+            colors: color::Colors::new(color::ColorChoice::Never),
+            prune: false,
+            safe_prune: false,
+            only: None,
+            creator: None,
+            mentioned: None,
+            sort: None,
+            order: None,
+            timezone: timezone::Timezone::Utc,
+            api: graphql::Api::Rest,
+            number_min: None,
+            number_max: None,
+            dry_run: false,
+            full_resync: false,
+            since_file: None,
+            state: state::StateStore::load(issues_dir).unwrap(),
+            format: format::Format::Markdown,
+            raw_body: false,
+            download_attachments: false,
+            conflict_strategy: plan::ConflictStrategy::RemoteWins,
+            push_on_startup: false,
+            git_commit: None,
+            stage_dir: None,
+            rewrite_refs: false,
+            no_body_on_create: false,
+            input_encoding: None,
+            push_concurrency: 4,
+            comment_concurrency: 4,
+            wrap_body: None,
+            include_timeline: false,
+            fetch_participants: false,
+            list_attachments: false,
+            track_tasks: false,
+            keep_raw: false,
+            sync_milestones: false,
+            ignore_whitespace: false,
+            group_by: None,
+            bulk_max_files: bulk::DEFAULT_MAX_FILES,
+            bulk_max_percent: None,
+            confirm_bulk: false,
+            push_fields: fields::PushFields::default(),
+            metadata: sidecar::Metadata::Inline,
+            create_missing_labels: false,
+            known_labels: labels::KnownLabels::default(),
+            state_map: state_map::StateMap::default(),
+            issue_locks: lock::IssueLocks::default(),
+            assignee: None,
+            readonly_closed: false,
+            offline_fallback: false,
+            initial_sync_timeout: None,
+        }
+    }
+
+    pub fn issue_json(number: i64, title: &str, state: &str, labels: &[&str], body: &str) -> serde_json::Value {
+        serde_json::json!({
+            "number": number,
+            "html_url": format!("https://github.com/owner/repo/issues/{}", number),
+            "node_id": format!("I_kwDOtest{}", number),
+            "title": title,
+            "state": state,
+            "body": body,
+            "labels": labels.iter().map(|name| serde_json::json!({ "name": name })).collect::<Vec<_>>(),
+            "author_association": "OWNER",
+            "updated_at": "2020-01-01T00:00:00Z",
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pull_writes_issue_files_from_mock_server() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("state", "all"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "First issue", "open", &["bug"], "First body"),
+                issue_json(2, "Second issue", "closed", &[], "Second body"),
+            ])))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![1, 2]);
+
+        let first = fs::read_to_string(dir.path().join("issue-1.md")).unwrap();
+        assert_eq!(
+            first,
+            "---\nnumber: 1\ntitle: First issue\nstate: open\nlabels: [bug]\nassignees: []\nhtml_url: https://github.com/owner/repo/issues/1\nnode_id: I_kwDOtest1\nlocked: false\ncomments: 0\nupdated_at: 2020-01-01T00:00:00Z\nschema_version: 1\n---\n\nFirst body"
+        );
+        let second = fs::read_to_string(dir.path().join("issue-2.md")).unwrap();
+        assert!(second.contains("state: closed\nlabels: []\n"));
+
+        // A second pull with the same remote data leaves the files untouched
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert!(summary.created.is_empty() && summary.updated.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pull_skips_pull_requests_in_the_listing() {
+        let server = MockServer::start().await;
+        let mut pull_request = issue_json(2, "A pull request", "open", &[], "Body");
+        pull_request["pull_request"] = serde_json::json!({ "url": "https://api.github.com/repos/owner/repo/pulls/2" });
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "An issue", "open", &[], "Body"),
+                pull_request,
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![1]);
+        assert!(dir.path().join("issue-1.md").exists());
+        assert!(!dir.path().join("issue-2.md").exists());
+        assert!(missing_local_issues(&config).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pull_processes_issues_in_ascending_number_order() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(10, "Newest", "open", &[], "Body"),
+                issue_json(2, "Older", "open", &[], "Body"),
+                issue_json(7, "Middle", "closed", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![2, 7, 10]);
+
+        config.format = format::Format::Json;
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![2, 7, 10]);
+        let data: Vec<Issue> = serde_json::from_str(&fs::read_to_string(dir.path().join("issues.json")).unwrap()).unwrap();
+        assert_eq!(data.iter().map(|issue| issue.number).collect::<Vec<_>>(), vec![2, 7, 10]);
+    }
+
+    #[tokio::test]
+    async fn test_raw_body_keeps_body_byte_for_byte() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Issue", "open", &[], "  Line one\r\nLine two\r\n\r\n"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+
+        sync_github_to_local(&config, None).await.unwrap();
+        let normalized = fs::read_to_string(dir.path().join("issue-1.md")).unwrap();
+        assert!(normalized.ends_with("---\n\nLine one\nLine two"));
+
+        config.raw_body = true;
+        sync_github_to_local(&config, None).await.unwrap();
+        let raw = fs::read_to_string(dir.path().join("issue-1.md")).unwrap();
+        assert!(raw.ends_with("---\n\n  Line one\r\nLine two\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_files_without_retasks_frontmatter() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Still here", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("issue-5.md"), "---\nnumber: 5\ntitle: Deleted\n---\n\nGone").unwrap();
+        fs::write(dir.path().join("notes.md"), "# Unrelated notes").unwrap();
+
+        let mut config = test_config(&server, dir.path());
+        config.prune = true;
+
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.pruned, vec![5]);
+        assert!(dir.path().join("issue-1.md").exists());
+        assert!(!dir.path().join("issue-5.md").exists());
+        assert!(dir.path().join("notes.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_safe_prune_only_removes_issues_github_confirms_are_gone() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([issue_json(1, "Listed", "open", &[], "Body")])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(2, "Left out", "closed", &[], "Body")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/3"))
+            .respond_with(ResponseTemplate::new(410).set_body_json(serde_json::json!({ "message": "This issue was deleted" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/4"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({ "message": "Not Found" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/5"))
+            .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({ "message": "Unprocessable" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        for number in 2..=5 {
+            fs::write(dir.path().join(format!("issue-{}.md", number)), format!("---\nnumber: {}\ntitle: Old\n---\n\nBody", number))
+                .unwrap();
+        }
+        let mut config = test_config(&server, dir.path());
+        config.prune = true;
+        config.safe_prune = true;
+
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.pruned, vec![3, 4]);
+        // Still on GitHub, or not known to be gone
+        assert!(dir.path().join("issue-2.md").exists());
+        assert!(dir.path().join("issue-5.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_assignee_filter_pulls_own_issues_and_prunes_only_deleted_ones() {
+        let server = MockServer::start().await;
+        let assigned = |number: i64, login: &str| {
+            let mut issue = issue_json(number, "Issue", "open", &[], "Body");
+            issue["assignees"] = serde_json::json!([{ "login": login }]);
+            issue
+        };
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                assigned(1, "OctoCat"),
+                assigned(2, "someone-else"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "login": "octocat" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("issue-2.md"), "---\nnumber: 2\ntitle: Reassigned\n---\n\nKept").unwrap();
+        fs::write(dir.path().join("issue-5.md"), "---\nnumber: 5\ntitle: Deleted\n---\n\nGone").unwrap();
+
+        let mut config = test_config(&server, dir.path());
+        config.assignee = Some("@me".to_string());
+        config.prune = true;
+
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![1]);
+        assert_eq!(summary.pruned, vec![5]);
+        assert_eq!(fs::read_to_string(dir.path().join("issue-2.md")).unwrap(), "---\nnumber: 2\ntitle: Reassigned\n---\n\nKept");
+    }
+
+    #[tokio::test]
+    async fn test_number_range_limits_pull_and_prune() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Too old", "open", &[], "Body"),
+                issue_json(3, "In range", "open", &[], "Body"),
+                issue_json(9, "Too new", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("issue-2.md"), "---\nnumber: 2\ntitle: Deleted\n---\n\nGone").unwrap();
+        fs::write(dir.path().join("issue-7.md"), "---\nnumber: 7\ntitle: Deleted out of range\n---\n\nKept").unwrap();
+
+        let mut config = test_config(&server, dir.path());
+        config.number_min = Some(2);
+        config.number_max = Some(5);
+        config.prune = true;
+
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![3]);
+        assert_eq!(summary.pruned, vec![2]);
+        assert!(!dir.path().join("issue-1.md").exists() && !dir.path().join("issue-9.md").exists());
+        assert!(dir.path().join("issue-7.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_creator_and_mentioned_filter_the_listing_without_pruning() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("creator", "octocat"))
+            .and(query_param("mentioned", "hubot"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(3, "Opened by octocat", "open", &[], "Body"),
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("issue-2.md"), "---\nnumber: 2\ntitle: Someone else's\n---\n\nKept").unwrap();
+
+        let mut config = test_config(&server, dir.path());
+        config.creator = Some("octocat".to_string());
+        config.mentioned = Some("hubot".to_string());
+        config.prune = true;
+
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![3]);
+        assert!(summary.pruned.is_empty());
+        assert!(dir.path().join("issue-2.md").exists());
+
+        assert!(is_login("octocat") && is_login("a-b-1"));
+        assert!(!is_login("") && !is_login("-a") && !is_login("a-") && !is_login("a--b") && !is_login("a b"));
+        assert!(!is_login(&"a".repeat(40)));
+    }
+
+    #[tokio::test]
+    async fn test_pull_leaves_files_from_newer_schema_alone() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Remote title", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let future = "---\nnumber: 1\ntitle: Local title\nschema_version: 99\n---\n\nBody";
+        fs::write(dir.path().join("issue-1.md"), future).unwrap();
+
+        let config = test_config(&server, dir.path());
+        sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("issue-1.md")).unwrap(), future);
+    }
+
+    #[tokio::test]
+    async fn test_pull_keeps_issues_moved_into_subdirectories_in_place() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Moved", "open", &[], "Body"),
+                issue_json(2, "New", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        fs::create_dir_all(dir.path().join("archive")).unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".git/issue-9.md"), "---\nnumber: 9\n---\n").unwrap();
+        let moved = issue_from_remote(&config, serde_json::from_value(issue_json(1, "Moved", "open", &[], "Body")).unwrap());
+        fs::write(dir.path().join("archive/issue-1.md"), render_issue_file(&moved)).unwrap();
+
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![2]);
+        assert!(!dir.path().join("issue-1.md").exists());
+
+        let paths: Vec<PathBuf> = scan_issue_files(&config).unwrap().into_iter().map(|file| file.path).collect();
+        assert_eq!(paths, vec![dir.path().join("archive/issue-1.md"), dir.path().join("issue-2.md")]);
+    }
+
+    #[tokio::test]
+    async fn test_pull_saves_cursor_and_catch_up_skips_pruning() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "First issue", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.prune = true;
+
+        sync_github_to_local(&config, None).await.unwrap();
+        let saved = state::StateStore::load(dir.path()).unwrap().get();
+        let since = saved.last_pull_at.expect("cursor should be saved after a pull");
+
+        // A catch-up pull only sees recently updated issues, so nothing may be pruned
+        fs::write(dir.path().join("issue-9.md"), "---\nnumber: 9\n---\n\nOld").unwrap();
+        let summary = sync_github_to_local(&config, Some(since)).await.unwrap();
+        assert!(summary.pruned.is_empty());
+        assert!(dir.path().join("issue-9.md").exists());
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests[1].url.query().unwrap().contains("since="));
+    }
+
+    #[tokio::test]
+    async fn test_plan_reports_both_directions() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Edited locally", "open", &[], "Body"),
+                issue_json(2, "Unchanged", "open", &[], "Body"),
+            ])))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Edited locally", "open", &[], "Body"),
+                issue_json(2, "Unchanged", "open", &[], "Body"),
+                issue_json(3, "New on GitHub", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let file_path = dir.path().join("issue-1.md");
+        let edited = fs::read_to_string(&file_path).unwrap().replace("Body", "New body");
+        fs::write(&file_path, edited).unwrap();
+        let later = std::time::SystemTime::now() + Duration::from_secs(60);
+        fs::File::options().write(true).open(&file_path).unwrap().set_modified(later).unwrap();
+
+        let plan = plan::compute_sync_plan(&config).await.unwrap();
+        let actions: Vec<(i64, plan::Action)> = plan.changes.iter().map(|c| (c.number, c.action.clone())).collect();
+        assert_eq!(actions, vec![(1, plan::Action::Push), (3, plan::Action::PullCreate)]);
+        assert_eq!(plan.in_sync, 1);
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_which_side_is_ahead() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Edited locally", "open", &[], "Body"),
+            ])))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Edited locally", "open", &[], "Body"),
+                issue_json(2, "New on GitHub", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        sync_github_to_local(&config, None).await.unwrap();
+        plan::verify(&config).await.unwrap();
+
+        let file_path = dir.path().join("issue-1.md");
+        let edited = fs::read_to_string(&file_path).unwrap().replace("Body", "New body");
+        fs::write(&file_path, &edited).unwrap();
+        let later = std::time::SystemTime::now() + Duration::from_secs(60);
+        fs::File::options().write(true).open(&file_path).unwrap().set_modified(later).unwrap();
+        fs::write(dir.path().join("issue-8.md"), "---\nnumber: 8\n---\n\nGone").unwrap();
+
+        let error = plan::verify(&config).await.unwrap_err().to_string();
+        assert!(error.contains("1 local ahead, 1 remote ahead, 0 changed on both sides, 1 not on GitHub"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), edited);
+        assert!(!dir.path().join("issue-2.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_only_fetches_a_single_issue() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/7"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(7, "Seventh", "open", &[], "Body")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("issue-3.md"), "---\nnumber: 3\n---\n\nUntouched").unwrap();
+
+        let mut config = test_config(&server, dir.path());
+        config.only = Some(7);
+        config.prune = true;
+
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![7]);
+        assert!(summary.pruned.is_empty());
+        assert!(dir.path().join("issue-3.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_json_format_round_trips_through_single_file() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("state", "all"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "First issue", "open", &["bug"], "First body"),
+                issue_json(2, "Second issue", "open", &[], "Second body"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(2, "Second issue", "closed", &[], "Second body")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.format = format::Format::Json;
+
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![1, 2]);
+        assert!(!dir.path().join("issue-1.md").exists());
+
+        let data_path = dir.path().join("issues.json");
+        let mut issues: Vec<Issue> = serde_json::from_str(&fs::read_to_string(&data_path).unwrap()).unwrap();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].labels, vec!["bug".to_string()]);
+
+        // Only the edited entry differs from GitHub, so only it is pushed
+        issues[1].state = "closed".to_string();
+        fs::write(&data_path, serde_json::to_string(&issues).unwrap()).unwrap();
+        let updated = format::push_data_file(&config).await.unwrap();
+        assert_eq!(updated, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_reordered_labels_do_not_trigger_a_push() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Issue", "open", &["ui", "bug"], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Issue", "open", &[], "Body")))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let file_path = dir.path().join("issue-1.md");
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(content.contains("labels: [bug, ui]\n"));
+
+        fs::write(&file_path, content.replace("[bug, ui]", "[ui, bug]")).unwrap();
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_ignore_whitespace_treats_cosmetic_body_changes_as_unchanged() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Issue", "open", &[], "First  \n\n\n\nSecond\t\n- item"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Issue", "open", &[], "Body")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        sync_github_to_local(&config, None).await.unwrap();
+
+        // Trailing spaces dropped and blank lines collapsed by an editor,
+        // with the flag turned on only after the pull
+        config.ignore_whitespace = true;
+        let file_path = dir.path().join("issue-1.md");
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(content.ends_with("First  \n\n\n\nSecond\t\n- item"), "{}", content);
+        fs::write(&file_path, content.replace("First  \n\n\n\nSecond\t\n", "First\n\nSecond\n")).unwrap();
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), None);
+        assert_eq!(plan::compute_sync_plan(&config).await.unwrap().in_sync, 1);
+
+        // Without the flag, or with a real edit, the body counts as changed
+        let remote = issue_from_remote(&config, serde_json::from_value(issue_json(1, "Issue", "open", &[], "A  \n\n\nB")).unwrap());
+        let local = Issue { body: Some("A\n\nB".to_string()), ..remote.clone() };
+        assert!(!issues_differ(&config, &local, &remote));
+        assert!(issues_differ(&config, &Issue { body: Some("A\nB".to_string()), ..remote.clone() }, &remote));
+        config.ignore_whitespace = false;
+        assert!(issues_differ(&config, &local, &remote));
+
+        config.ignore_whitespace = true;
+        fs::write(&file_path, content.replace("- item", "- item\n- another")).unwrap();
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_metadata_is_written_apart_and_pushed_with_the_body() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Issue", "open", &["bug"], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Renamed", "open", &["bug"], "Body")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.metadata = sidecar::Metadata::Sidecar;
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert_eq!(summary.created, vec![1]);
+
+        let file_path = dir.path().join("issue-1.md");
+        let sidecar_path = dir.path().join("issue-1.json");
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "Body");
+        let metadata: serde_json::Value = serde_json::from_str(&fs::read_to_string(&sidecar_path).unwrap()).unwrap();
+        assert_eq!(metadata["number"], 1);
+        assert_eq!(metadata["labels"], serde_json::json!(["bug"]));
+
+        // Pulling again finds nothing to change, and an unedited file nothing to push
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert!(summary.created.is_empty() && summary.updated.is_empty());
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), None);
+
+        // An edit to the sidecar alone is pushed through the markdown file
+        let edited = fs::read_to_string(&sidecar_path).unwrap().replace("\"Issue\"", "\"Renamed\"");
+        fs::write(&sidecar_path, edited).unwrap();
+        assert_eq!(config.metadata.issue_file(&sidecar_path), Some(file_path.clone()));
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), Some(1));
+
+        let requests = server.received_requests().await.unwrap();
+        let patch: serde_json::Value = requests.iter().find(|r| r.method.as_str() == "PATCH").unwrap().body_json().unwrap();
+        assert_eq!(patch["title"], "Renamed");
+        assert_eq!(patch["body"], "Body");
+    }
+
+    #[tokio::test]
+    async fn test_readonly_closed_ignores_edits_to_closed_issues() {
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(3, "Old", "closed", &[], "Edited")))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.readonly_closed = true;
+        let file_path = dir.path().join("issue-3.md");
+        fs::write(&file_path, "---\nnumber: 3\ntitle: Old\nstate: closed\n---\n\nStray edit").unwrap();
+
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_push_sends_frontmatter_to_mock_server() {
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/42"))
+            .and(body_json(serde_json::json!({
+                "title": "Edited title",
+                "body": "Edited body",
+                "state": "closed",
+                "labels": ["bug", "priority, high"],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(42, "Edited title", "closed", &["bug"], "Edited body")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        let file_path = dir.path().join("issue-42.md");
+        fs::write(
+            &file_path,
+            "---\nnumber: 42\ntitle: Edited title\nstate: closed\nlabels: [bug, \"priority, high\"]\n---\n\nEdited body",
+        )
+        .unwrap();
+
+        let pushed = sync_local_to_github(&config, &file_path).await.unwrap();
+        assert_eq!(pushed, Some(42));
+    }
+
+    #[tokio::test]
+    async fn test_new_issue_is_prefilled_from_template() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/contents/.github/ISSUE_TEMPLATE"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "_links": {}, "type": "file", "name": "bug_report.md" },
+                { "_links": {}, "type": "file", "name": "config.yml" },
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/contents/.github/ISSUE_TEMPLATE/bug_report.md"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "_links": {},
+                "encoding": "base64",
+                // "---\nname: Bug report\ntitle: '[BUG] '\nlabels: bug\n---\n\nSteps to reproduce\n"
+                "content": "LS0tCm5hbWU6IEJ1ZyByZXBvcnQKdGl0bGU6ICdbQlVHXSAnCmxhYmVsczog\nYnVnCi0tLQoKU3RlcHMgdG8gcmVwcm9kdWNlCg==\n",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(issue_json(9, "[BUG] Crash on start", "open", &["bug"], "Steps to reproduce")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+
+        let template = template::find_template(&config, "Bug Report").await.unwrap();
+        create_issue(&config, Some("Crash on start"), Some(&template)).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let sent: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+        assert_eq!(sent["title"], "[BUG] Crash on start");
+        assert_eq!(sent["labels"], serde_json::json!(["bug"]));
+        assert!(sent["body"].as_str().unwrap().starts_with("Steps to reproduce\n\n<!-- retasks-create-id: "));
+
+        let content = fs::read_to_string(dir.path().join("issue-9.md")).unwrap();
+        assert!(content.contains("\ntitle: [BUG] Crash on start\nstate: open\nlabels: [bug]\n"));
+        assert!(content.ends_with("---\n\nSteps to reproduce"));
+        assert!(config.state.get().pending_creates.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_no_body_on_create_sends_only_title_and_labels() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(issue_json(9, "Crash", "open", &["bug"], "")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.no_body_on_create = true;
+        let template = template::IssueTemplate {
+            title: "Crash".to_string(),
+            labels: vec!["bug".to_string()],
+            body: "A huge placeholder".to_string(),
+            ..Default::default()
+        };
+        create_issue(&config, None, Some(&template)).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let sent: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+        assert_eq!(sent["title"], "Crash");
+        assert_eq!(sent["labels"], serde_json::json!(["bug"]));
+        // Only the hidden create id, which lets a retry find the issue
+        assert!(sent["body"].as_str().unwrap().starts_with("<!-- retasks-create-id: "));
+    }
+
+    #[tokio::test]
+    async fn test_new_issue_adopts_issue_from_interrupted_attempt() {
+        let server = MockServer::start().await;
+        let body = "Details\n\n<!-- retasks-create-id: 1234 -->";
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([issue_json(6, "Crash", "open", &[], body)])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/6"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(6, "Crash", "open", &[], body)))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(issue_json(7, "Crash", "open", &[], body)))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        config.state.update(|state| {
+            state.pending_creates.insert("1234".to_string(), "Crash".to_string());
+        }).unwrap();
+
+        create_issue(&config, Some("Crash"), None).await.unwrap();
+        assert!(dir.path().join("issue-6.md").exists());
+        assert!(config.state.get().pending_creates.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_push_sends_state_reason_on_close() {
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/5"))
+            .and(body_json(serde_json::json!({ "state": "closed", "state_reason": "not_planned" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(5, "Idea", "closed", &[], "Body")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/5"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(5, "Idea", "closed", &[], "Body")))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        let file_path = dir.path().join("issue-5.md");
+        fs::write(&file_path, "---\nnumber: 5\ntitle: Idea\nstate: closed\nstate_reason: not_planned\n---\n\nBody").unwrap();
+
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_pull_lists_issues_of_the_configured_repository_only() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Issue", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(!requests.is_empty());
+        assert!(requests.iter().all(|request| request.url.path() == "/repos/owner/repo/issues"));
+        assert!(dir.path().join("issue-1.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_pull_records_state_reason_of_closed_issues() {
+        let server = MockServer::start().await;
+        let mut dropped = issue_json(2, "Dropped", "closed", &[], "Body");
+        dropped["state_reason"] = serde_json::json!("not_planned");
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("state", "all"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Open", "open", &[], "Body"),
+                dropped,
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // Read from the listing, without listing the closed issues again
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(query_param("state", "closed"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let closed = fs::read_to_string(dir.path().join("issue-2.md")).unwrap();
+        assert!(closed.contains("\nstate: closed\nstate_reason: not_planned\nlabels: []\n"));
+        let open = fs::read_to_string(dir.path().join("issue-1.md")).unwrap();
+        assert!(!open.contains("state_reason"));
+
+        // The pulled reason is part of the fingerprint, so the file isn't pushed back
+        let file_path = dir.path().join("issue-2.md");
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_push_skips_non_utf8_file_unless_encoding_is_given() {
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/4"))
+            .and(body_json(serde_json::json!({ "title": "Задача", "body": "Привет" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(4, "Задача", "open", &[], "Привет")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("issue-4.md");
+        let (encoded, _, _) = encoding_rs::WINDOWS_1251.encode("---\nnumber: 4\ntitle: Задача\n---\n\nПривет");
+        fs::write(&file_path, &encoded).unwrap();
+
+        let mut config = test_config(&server, dir.path());
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), None);
+
+        config.input_encoding = Some(encoding_rs::WINDOWS_1251);
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_missing_local_issues_are_found_for_push() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Pulled", "open", &[], "Body"),
+                issue_json(2, "Not pulled", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        fs::write(
+            dir.path().join("issue-1.md"),
+            render_issue_file(&issue_from_remote(&config, serde_json::from_value(issue_json(1, "Pulled", "open", &[], "Body")).unwrap())),
+        )
+        .unwrap();
+
+        assert_eq!(missing_local_issues(&config).await.unwrap(), vec![2]);
+        // Out of range, so it isn't expected locally
+        config.number_max = Some(1);
+        assert!(missing_local_issues(&config).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_push_all_files_pushes_only_changed_files() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "One", "open", &[], "Body"),
+                issue_json(2, "Two", "open", &[], "Body"),
+                issue_json(3, "Three", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+        for number in [1, 3] {
+            Mock::given(method("PATCH"))
+                .and(path(format!("/repos/owner/repo/issues/{}", number)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(number, "Edited", "open", &[], "Body")))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.push_concurrency = 2;
+        sync_github_to_local(&config, None).await.unwrap();
+
+        for number in [1, 3] {
+            let file_path = dir.path().join(format!("issue-{}.md", number));
+            let edited = fs::read_to_string(&file_path).unwrap().replace("Body", "Edited body");
+            fs::write(&file_path, edited).unwrap();
+        }
+
+        assert_eq!(push_all_files(&config).await.unwrap(), vec![1, 3]);
+        let fingerprints = state::StateStore::load(dir.path()).unwrap().get().fingerprints;
+        assert_eq!(fingerprints.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_push_on_startup_pushes_offline_edits_following_the_conflict_strategy() {
+        let server = MockServer::start().await;
+        let mut changed_on_github = issue_json(2, "Two", "open", &[], "Body");
+        changed_on_github["updated_at"] = serde_json::json!("2030-01-01T00:00:00Z");
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "One", "open", &[], "Body"),
+                issue_json(2, "Two", "open", &[], "Body"),
+                issue_json(3, "Three", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "One", "open", &[], "Body")))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(changed_on_github))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "One", "open", &[], "Offline edit")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(2, "Two", "open", &[], "Offline edit")))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.push_on_startup = true;
+        sync_github_to_local(&config, None).await.unwrap();
+
+        // Edited after the last sync, as if retasks had been stopped meanwhile
+        for number in [1, 2] {
+            let file_path = dir.path().join(format!("issue-{}.md", number));
+            let edited = fs::read_to_string(&file_path).unwrap().replace("Body", "Offline edit");
+            fs::write(&file_path, edited).unwrap();
+            let later = std::time::SystemTime::now() + Duration::from_secs(60);
+            fs::File::options().write(true).open(&file_path).unwrap().set_modified(later).unwrap();
+        }
+
+        // Only touched, so it isn't looked up on GitHub
+        let untouched = dir.path().join("issue-3.md");
+        let later = std::time::SystemTime::now() + Duration::from_secs(60);
+        fs::File::options().write(true).open(&untouched).unwrap().set_modified(later).unwrap();
+
+        // Two edited files are too many for a limit of one
+        config.bulk_max_files = 1;
+        assert!(push_offline_edits(&config).await.unwrap_err().to_string().contains("--bulk-max-files (1)"));
+        config.bulk_max_files = bulk::DEFAULT_MAX_FILES;
+
+        // #2 also changed on GitHub, and remote-wins leaves it to the pull
+        assert_eq!(push_offline_edits(&config).await.unwrap(), vec![1]);
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests.iter().all(|request| request.url.path() != "/repos/owner/repo/issues/3"));
+    }
+
+    #[tokio::test]
+    async fn test_push_rejects_unknown_state() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        let file_path = dir.path().join("issue-5.md");
+        fs::write(&file_path, "---\nnumber: 5\ntitle: Idea\nstate: clsoed\n---\n\nBody").unwrap();
+
+        let error = format!("{:#}", sync_local_to_github(&config, &file_path).await.unwrap_err());
+        assert!(error.contains("issue-5.md"));
+        assert!(error.contains("\"clsoed\""));
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dump_config_redacts_tokens() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+
+        let dump = dump_config(&config);
+        assert_eq!(dump["repo"], "owner/repo");
+        assert_eq!(dump["api_url"], server.uri());
+        assert_eq!(dump["conflict_strategy"], "remote-wins");
+        assert!(!dump.to_string().contains("test-token"));
+    }
+
+    #[tokio::test]
+    async fn test_initial_sync_timeout_fails_or_warns_in_watch_mode() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!([issue_json(1, "Slow", "open", &[], "Body")]))
+                    .set_delay(Duration::from_secs(5)),
+            )
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.initial_sync_timeout = Some(Duration::from_millis(100));
+        let error = initial_sync(&config, None).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("--initial-sync-timeout"));
+
+        config.watch = true;
+        assert!(initial_sync(&config, None).await.unwrap().is_none());
+        assert_eq!(config.state.get().last_pull_at, None);
+    }
+
+    #[test]
+    fn test_issues_dir_that_is_a_file_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("issues");
+        fs::write(&file, "not a directory").unwrap();
+
+        let error = create_issues_dir(&file).unwrap_err().to_string();
+        assert!(error.contains("is a file, expected a directory"), "{}", error);
+        assert!(check_issues_dir(&dir.path().join("missing")).is_ok());
+        create_issues_dir(&dir.path().join("missing")).unwrap();
+        assert!(dir.path().join("missing").is_dir());
+    }
+
+    #[tokio::test]
+    async fn test_errors_carry_their_kind() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({ "message": "Bad credentials" })))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+
+        let error = sync_github_to_local(&config, None).await.unwrap_err();
+        let kind = error.chain().find_map(|cause| cause.downcast_ref::<error::SyncError>());
+        assert!(matches!(kind, Some(error::SyncError::Auth { status: 401, .. })), "{:?}", kind);
+
+        let file_path = dir.path().join("issue-1.md");
+        fs::write(&file_path, "---\nnumber: one\n---\n\nBody").unwrap();
+        let error = sync_local_to_github(&config, &file_path).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<error::SyncError>(), Some(error::SyncError::Parse { path, .. }) if path == &file_path));
+    }
+
+    #[test]
+    fn test_parse_markdown_file() {
+        let content = r#"---
+number: 42
+title: Test Issue
+state: open
+labels: [bug, enhancement]
+---
+
+This is the body of the issue.
+
+It has multiple lines."#;
+
+        let result = parse_markdown_file(content).unwrap();
+        let (frontmatter, body) = result;
+
+        assert_eq!(frontmatter.get("number"), Some(&"42".to_string()));
+        assert_eq!(frontmatter.get("title"), Some(&"Test Issue".to_string()));
+        assert_eq!(frontmatter.get("state"), Some(&"open".to_string()));
+        assert_eq!(frontmatter.get("labels"), Some(&"[bug, enhancement]".to_string()));
+        assert_eq!(body, "This is the body of the issue.\n\nIt has multiple lines.");
+    }
+
+    #[test]
+    fn test_parse_markdown_file_with_byte_order_mark() {
+        let (frontmatter, body) = parse_markdown_file("\u{feff}---\r\nnumber: 42\r\ntitle: Saved on Windows\r\n---\r\n\r\nBody").unwrap();
+        assert_eq!(frontmatter.get("number"), Some(&"42".to_string()));
+        assert_eq!(frontmatter.get("title"), Some(&"Saved on Windows".to_string()));
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn test_body_that_looks_like_frontmatter_stays_in_the_body() {
+        let issue = Issue {
+            number: 4,
+            title: "Config --- example".to_string(),
+            body: Some("---\ntitle: Pasted\nstate: closed\n---".to_string()),
+            state: "open".to_string(),
+            state_reason: None,
+            labels: vec![],
+            assignees: vec![],
+            html_url: String::new(),
+            node_id: String::new(),
+            locked: false,
+            comments: 0,
+            participants: None,
+            attachments: None,
+            tasks: None,
+            created_at: None,
+            updated_at: None,
+        };
+        let content = render_issue_file(&issue);
+
+        let (frontmatter, body) = parse_markdown_file(&content).unwrap();
+        assert_eq!(frontmatter.get("title"), Some(&"Config --- example".to_string()));
+        assert_eq!(frontmatter.get("state"), Some(&"open".to_string()));
+        assert_eq!(body, "---\ntitle: Pasted\nstate: closed\n---");
+    }
+
+    #[test]
+    fn test_labels_with_commas_round_trip() {
+        let labels = vec!["bug".to_string(), "priority, high".to_string()];
+        let formatted = format_list(&labels);
+
+        assert_eq!(formatted, r#"[bug, "priority, high"]"#);
+        assert_eq!(parse_list(&formatted).unwrap(), labels);
+    }
+
+    #[test]
+    fn test_whitespace_only_title_is_quoted_and_refused_on_push() {
+        let issue = Issue {
+            number: 7,
+            title: "   ".to_string(),
+            body: Some("Body".to_string()),
+            state: "open".to_string(),
+            state_reason: None,
+            labels: vec![],
+            assignees: vec![],
+            html_url: String::new(),
+            node_id: String::new(),
+            locked: false,
+            comments: 0,
+            participants: None,
+            attachments: None,
+            tasks: None,
+            created_at: None,
+            updated_at: None,
+        };
+
+        let content = render_issue_file(&issue);
+        assert!(content.contains("\ntitle: \"   \"\n"));
+
+        let (frontmatter, body) = parse_markdown_file(&content).unwrap();
+        assert_eq!(unquote_scalar(&frontmatter["title"]), "   ");
+
+        let error = build_update_request(&frontmatter, body).unwrap_err();
+        assert!(error.to_string().contains("Title is empty"));
+    }
+
+    #[test]
+    fn test_render_issue_file_includes_html_url() {
+        let issue = Issue {
+            number: 42,
+            title: "Test Issue".to_string(),
+            body: Some("Body".to_string()),
+            state: "open".to_string(),
+            state_reason: None,
+            labels: vec![],
+            assignees: vec![],
+            html_url: "https://github.com/owner/repo/issues/42".to_string(),
+            node_id: String::new(),
+            locked: false,
+            comments: 0,
+            participants: None,
+            attachments: None,
+            tasks: None,
+            created_at: None,
+            updated_at: None,
+        };
+
+        let content = render_issue_file(&issue);
+        assert!(content.contains("\nhtml_url: https://github.com/owner/repo/issues/42\nlocked: false\ncomments: 0\nschema_version: 1\n---\n"));
+
+        // The URL is not part of what gets pushed back
+        let (frontmatter, body) = parse_markdown_file(&content).unwrap();
+        let payload = serde_json::to_value(build_update_request(&frontmatter, body).unwrap()).unwrap();
+        assert!(payload.get("html_url").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_node_id_round_trips_and_is_not_pushed() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(5, "Issue", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/5"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(5, "Issue", "open", &[], "Edited")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let file_path = dir.path().join("issue-5.md");
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(content.contains("\nnode_id: I_kwDOtest5\n"));
+
+        // Pulling again leaves it as it was
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        assert!(summary.updated.is_empty());
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), content);
+
+        // Editing it locally changes nothing on GitHub
+        fs::write(&file_path, content.replace("I_kwDOtest5", "I_edited").replace("Body", "Edited")).unwrap();
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), Some(5));
+        let requests = server.received_requests().await.unwrap();
+        let patch: serde_json::Value = requests.iter().find(|r| r.method.as_str() == "PATCH").unwrap().body_json().unwrap();
+        assert!(patch.get("node_id").is_none());
+        assert_eq!(patch["body"], "Edited");
+    }
+
+    #[test]
+    fn test_write_atomically_replaces_file_without_leftovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("issue-1.md");
+        fs::write(&path, "old content that is longer than the new one").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        write_file_with_retry(&path, "new", 0).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        }
+
+        // A failed write leaves neither a partial file nor the temporary one
+        assert!(write_file_with_retry(&dir.path().join("missing/issue-2.md"), "x", 0).is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_retry_io_retries_transient_errors() {
+        let mut calls = 0;
+        let result = retry_io(3, || {
+            calls += 1;
+            if calls < 3 {
+                Err(io::Error::from(io::ErrorKind::WouldBlock))
+            } else {
+                Ok(calls)
+            }
+        });
+
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_retry_io_gives_up_on_permanent_errors() {
+        let mut calls = 0;
+        let result: io::Result<()> = retry_io(3, || {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        });
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_state_change_request_sends_only_state() {
+        let update = state_change_request(types::State::Closed);
+        let payload = serde_json::to_value(&update).unwrap();
+
+        // Title and labels must be absent so closing keeps the existing remote values
+        assert_eq!(payload, serde_json::json!({ "state": "closed" }));
+    }
+
+    #[test]
+    fn test_state_reason_must_match_state() {
+        let mut frontmatter = HashMap::new();
+        frontmatter.insert("state".to_string(), "open".to_string());
+        frontmatter.insert("state_reason".to_string(), "not_planned".to_string());
+        assert!(parse_state_reason(&frontmatter).is_err());
+
+        frontmatter.insert("state_reason".to_string(), "wontfix".to_string());
+        assert!(parse_state_reason(&frontmatter).unwrap_err().to_string().contains("wontfix"));
+
+        frontmatter.insert("state".to_string(), "Closed".to_string());
+        frontmatter.insert("state_reason".to_string(), "completed".to_string());
+        assert_eq!(parse_state_reason(&frontmatter).unwrap(), Some("completed"));
+    }
+
+    #[test]
+    fn test_build_update_request_skips_absent_fields() {
+        let mut frontmatter = HashMap::new();
+        frontmatter.insert("number".to_string(), "42".to_string());
+        frontmatter.insert("state".to_string(), "closed".to_string());
+
+        let update = build_update_request(&frontmatter, String::new()).unwrap();
+        let payload = serde_json::to_value(&update).unwrap();
+
+        assert_eq!(payload, serde_json::json!({ "state": "closed" }));
     }
 }
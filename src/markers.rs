@@ -0,0 +1,91 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::{scan_issue_files, sidecar, Config};
+
+/// The line numbers, from 1, of the merge conflict markers in `content`.
+/// A `=======` line only counts inside a conflict, since on its own it
+/// underlines a markdown heading.
+pub fn find(content: &str) -> Vec<usize> {
+    let mut lines = Vec::new();
+    let mut in_conflict = false;
+    for (index, line) in content.lines().enumerate() {
+        let is_marker = if is_marker(line, "<<<<<<<") {
+            in_conflict = true;
+            true
+        } else if is_marker(line, ">>>>>>>") {
+            in_conflict = false;
+            true
+        } else {
+            in_conflict && (is_marker(line, "|||||||") || line.trim_end() == "=======")
+        };
+        if is_marker {
+            lines.push(index + 1);
+        }
+    }
+    lines
+}
+
+// The marker alone, or followed by a space and a label such as the branch
+fn is_marker(line: &str, marker: &str) -> bool {
+    line.strip_prefix(marker).is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+/// Every issue file, or sidecar, with conflict markers, and the lines they
+/// are on. Files are read as they are on disk, so the line numbers match
+/// what an editor shows.
+pub fn scan(config: &Config) -> Result<Vec<(PathBuf, Vec<usize>)>> {
+    let mut found = Vec::new();
+    for file in scan_issue_files(config)? {
+        let mut paths = vec![file.path.clone()];
+        if config.metadata == sidecar::Metadata::Sidecar {
+            paths.push(sidecar::sidecar_path(&file.path));
+        }
+        for path in paths {
+            // Unreadable and non-UTF-8 files are reported by the other checks
+            let Ok(content) = fs::read_to_string(&path) else { continue };
+            let lines = find(&content);
+            if !lines.is_empty() {
+                found.push((path, lines));
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use crate::{plan, sync_local_to_github};
+    use wiremock::MockServer;
+
+    #[test]
+    fn test_conflict_markers_are_found_but_heading_underlines_are_not() {
+        let content = "---\nnumber: 1\n<<<<<<< HEAD\ntitle: Ours\n=======\ntitle: Theirs\n>>>>>>> main\n---\n\nTitle\n=======\n";
+        assert_eq!(find(content), vec![3, 5, 7]);
+        assert!(find("Heading\n=======\n\n<<<<<<<<< not a marker").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_conflicted_files_fail_verify_and_are_not_pushed() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let conflicted = "---\nnumber: 1\ntitle: Issue\n---\n\n<<<<<<< HEAD\nOurs\n=======\nTheirs\n>>>>>>> main\n";
+        fs::write(dir.path().join("issue-1.md"), conflicted).unwrap();
+        fs::write(dir.path().join("issue-2.md"), conflicted).unwrap();
+        let config = test_config(&server, dir.path());
+
+        let found = scan(&config).unwrap();
+        assert_eq!(found, vec![(dir.path().join("issue-1.md"), vec![6, 8, 10]), (dir.path().join("issue-2.md"), vec![6, 8, 10])]);
+        let error = plan::verify(&config).await.unwrap_err();
+        assert!(error.to_string().contains("2 file(s) have merge conflict markers"));
+
+        let error = sync_local_to_github(&config, &dir.path().join("issue-1.md")).await.unwrap_err();
+        assert!(error.to_string().contains("merge conflict markers"));
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+}
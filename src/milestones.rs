@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use octorust::types;
+
+use crate::{
+    format_scalar, normalize_body, parse_markdown_file, parse_state, plan, read_issue_text, stable_hash, unquote_scalar,
+    write_atomically, Config,
+};
+
+/// Where `--sync-milestones` keeps one file per milestone, in the issues
+/// directory. Issue scans skip it, so its files are never taken for issues.
+pub const MILESTONES_DIR: &str = "milestones";
+
+/// The fields of a milestone that can be edited locally and pushed.
+#[derive(Debug, Clone, PartialEq)]
+struct Milestone {
+    number: i64,
+    title: String,
+    state: types::State,
+    due_on: Option<DateTime<Utc>>,
+    description: String,
+}
+
+impl Milestone {
+    fn from_remote(milestone: &types::Milestone) -> Self {
+        Milestone {
+            number: milestone.number,
+            title: milestone.title.clone(),
+            state: milestone.state.clone(),
+            due_on: milestone.due_on,
+            description: normalize_body(&milestone.description),
+        }
+    }
+
+    fn from_file(content: &str) -> Result<Self> {
+        let (frontmatter, description) = parse_markdown_file(content)?;
+        let value = |key: &str| frontmatter.get(key).map(|value| unquote_scalar(value));
+        let number = value("milestone")
+            .ok_or_else(|| anyhow::anyhow!("no `milestone` key"))?
+            .parse()
+            .context("Invalid `milestone` in frontmatter")?;
+        Ok(Milestone {
+            number,
+            title: value("title").unwrap_or_default(),
+            state: parse_state(&value("state").unwrap_or_else(|| "open".to_string()))?,
+            due_on: value("due_on").filter(|due_on| !due_on.trim().is_empty()).map(|due_on| parse_due_on(&due_on)).transpose()?,
+            description,
+        })
+    }
+
+    /// Stored per milestone in the state file, so only files edited since
+    /// the last sync are pushed.
+    fn fingerprint(&self) -> String {
+        let due_on = self.due_on.map(|due_on| due_on.to_rfc3339_opts(SecondsFormat::Secs, true)).unwrap_or_default();
+        stable_hash(&[self.title.as_str(), &self.state.to_string(), &due_on, &self.description].join("\0"))
+    }
+}
+
+// When the file was last saved
+fn modified(path: &Path) -> Result<DateTime<Utc>> {
+    Ok(fs::metadata(path).and_then(|metadata| metadata.modified()).map(DateTime::<Utc>::from)?)
+}
+
+// A full timestamp, as pulls write it, or just a date
+fn parse_due_on(value: &str) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|due_on| due_on.with_timezone(&Utc))
+        .context(format!("Invalid `due_on` in frontmatter: {:?} (expected a date like 2024-06-30)", value))
+}
+
+pub fn dir(config: &Config) -> PathBuf {
+    config.issues_dir.join(MILESTONES_DIR)
+}
+
+pub fn is_milestone_file(config: &Config, path: &Path) -> bool {
+    config.sync_milestones && path.parent() == Some(dir(config).as_path())
+}
+
+fn file_path(config: &Config, number: i64) -> PathBuf {
+    dir(config).join(format!("milestone-{}.md", number))
+}
+
+fn render(milestone: &types::Milestone) -> String {
+    let mut content = format!(
+        "---\nmilestone: {}\ntitle: {}\nstate: {}\n",
+        milestone.number,
+        format_scalar(&milestone.title),
+        milestone.state
+    );
+    if let Some(due_on) = milestone.due_on {
+        content.push_str(&format!("due_on: {}\n", due_on.to_rfc3339_opts(SecondsFormat::Secs, true)));
+    }
+    // Read-only, ignored on push
+    content.push_str(&format!("html_url: {}\n", milestone.html_url));
+    content.push_str(&format!("open_issues: {}\n", milestone.open_issues));
+    content.push_str(&format!("closed_issues: {}\n", milestone.closed_issues));
+    content.push_str("---\n\n");
+    content.push_str(&normalize_body(&milestone.description));
+    content
+}
+
+async fn list(config: &Config) -> Result<Vec<types::Milestone>> {
+    let response = config
+        .github
+        .with_rotation(|client| async move {
+            client
+                .issues()
+                .list_all_milestones(
+                    &config.repo_owner,
+                    &config.repo_name,
+                    types::IssuesListState::All,
+                    types::IssuesListMilestonesSort::DueOn,
+                    types::Order::Asc,
+                )
+                .await
+        })
+        .await
+        .context("Failed to list milestones from GitHub")?;
+    Ok(response.body)
+}
+
+/// Writes `milestones/milestone-N.md` for every milestone of the
+/// repository. Like issue files, a file edited since the last sync is kept
+/// unless the milestone also changed on GitHub and `--conflict-strategy`
+/// is `remote-wins`. Files of deleted milestones are left alone.
+pub async fn pull(config: &Config) -> Result<()> {
+    let milestones = list(config).await?;
+    if !config.dry_run {
+        fs::create_dir_all(dir(config)).context(format!("Failed to create {}", dir(config).display()))?;
+    }
+
+    let state = config.state.get();
+    let mut fingerprints = Vec::new();
+    for milestone in &milestones {
+        let path = file_path(config, milestone.number);
+        let content = render(milestone);
+        let synced = (milestone.number, Milestone::from_remote(milestone).fingerprint());
+        let existing = read_issue_text(config, &path).ok();
+        if existing.as_deref() == Some(content.as_str()) {
+            fingerprints.push(synced);
+            continue;
+        }
+
+        if existing.is_some() {
+            let modified = modified(&path)?;
+            let keep_local = match plan::resolve_difference(&state, modified, milestone.updated_at.map(|at| at.fixed_offset())) {
+                plan::Change::Pull => false,
+                plan::Change::Push => true,
+                plan::Change::Conflict => config.conflict_strategy != plan::ConflictStrategy::RemoteWins,
+            };
+            if keep_local {
+                println!("Keeping local edits to {}; push them with `retasks push`", path.display());
+                continue;
+            }
+        }
+
+        if config.dry_run {
+            println!("Would write milestone {} ({})", milestone.number, path.display());
+            continue;
+        }
+        write_atomically(&path, content.as_bytes()).context(format!("Failed to write {}", path.display()))?;
+        println!("Wrote milestone {} ({})", milestone.number, path.display());
+        fingerprints.push(synced);
+    }
+    if !config.dry_run {
+        config.state.update(|state| state.milestone_fingerprints.extend(fingerprints))?;
+    }
+    Ok(())
+}
+
+/// Sends the edits made to milestone files since the last sync: title,
+/// state, due date and description. Files that still match what was last
+/// pulled or pushed are skipped, so changes made on GitHub since then
+/// aren't reverted. Returns the numbers of the milestones updated.
+pub async fn push(config: &Config) -> Result<Vec<i64>> {
+    let dir = dir(config);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let remote: HashMap<i64, Milestone> =
+        list(config).await?.iter().map(|milestone| (milestone.number, Milestone::from_remote(milestone))).collect();
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+        .context(format!("Failed to read directory: {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+    paths.sort();
+
+    let state = config.state.get();
+    let mut updated = Vec::new();
+    for path in paths {
+        let content = read_issue_text(config, &path).context(format!("Failed to read {}", path.display()))?;
+        let local = Milestone::from_file(&content).context(format!("Failed to parse {}", path.display()))?;
+        let fingerprint = local.fingerprint();
+        let edited = match state.milestone_fingerprints.get(&local.number) {
+            Some(synced) => *synced != fingerprint,
+            // Not synced since fingerprints were kept: edited if saved since the last sync
+            None => state.last_event_at.is_some_and(|at| modified(&path).is_ok_and(|modified| modified > at)),
+        };
+        if !edited {
+            continue;
+        }
+        match remote.get(&local.number) {
+            None => {
+                eprintln!(
+                    "{}",
+                    config.colors.warning(&format!(
+                        "Warning: milestone {} ({}) is not on GitHub; skipping it",
+                        local.number,
+                        path.display()
+                    ))
+                );
+                continue;
+            }
+            Some(remote) if *remote == local => {
+                if !config.dry_run {
+                    config.state.update(|state| {
+                        state.milestone_fingerprints.insert(local.number, fingerprint);
+                    })?;
+                }
+                continue;
+            }
+            Some(_) => {}
+        }
+
+        if local.title.trim().is_empty() {
+            return Err(anyhow::anyhow!("{} has an empty title; GitHub does not allow milestones without one", path.display()));
+        }
+        let payload = serde_json::json!({
+            "title": local.title,
+            "state": local.state.to_string(),
+            "due_on": local.due_on.map(|due_on| due_on.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            "description": local.description,
+        });
+        if config.dry_run {
+            println!("Would update milestone {} with {}", local.number, payload);
+            continue;
+        }
+        config
+            .github
+            .update_milestone(&config.repo_owner, &config.repo_name, local.number, &payload)
+            .await
+            .context(format!("Failed to update milestone {} on GitHub", local.number))?;
+        println!("{}", config.colors.success(&format!("Updated milestone {} from {}", local.number, path.display())));
+        config.state.update(|state| {
+            state.last_event_at = Some(Utc::now());
+            state.milestone_fingerprints.insert(local.number, fingerprint);
+        })?;
+        updated.push(local.number);
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use crate::{push_all_files, scan_issue_files, sync_github_to_local};
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn milestone_json(number: i64, title: &str, due_on: Option<&str>, description: &str) -> serde_json::Value {
+        serde_json::json!({
+            "number": number,
+            "title": title,
+            "state": "open",
+            "description": description,
+            "due_on": due_on,
+            "html_url": format!("https://github.com/owner/repo/milestone/{}", number),
+            "open_issues": 3,
+            "closed_issues": 1,
+            "updated_at": "2020-01-01T00:00:00Z",
+        })
+    }
+
+    #[tokio::test]
+    async fn test_milestones_are_pulled_to_files_and_edits_pushed() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([issue_json(1, "Issue", "open", &[], "Body")])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/milestones"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                milestone_json(1, "v1.0", Some("2024-06-30T07:00:00Z"), "First release"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/milestones/1"))
+            .and(body_json(serde_json::json!({
+                "title": "v1.0",
+                "state": "open",
+                "due_on": "2024-07-15T00:00:00Z",
+                "description": "",
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(milestone_json(1, "v1.0", Some("2024-07-15T00:00:00Z"), "")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.sync_milestones = true;
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let file = dir.path().join(MILESTONES_DIR).join("milestone-1.md");
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            "---\nmilestone: 1\ntitle: v1.0\nstate: open\ndue_on: 2024-06-30T07:00:00Z\nhtml_url: https://github.com/owner/repo/milestone/1\nopen_issues: 3\nclosed_issues: 1\n---\n\nFirst release"
+        );
+        // Never taken for an issue file
+        assert_eq!(scan_issue_files(&config).unwrap().len(), 1);
+
+        // Untouched files send nothing; a new date and a cleared description do
+        assert!(push(&config).await.unwrap().is_empty());
+        fs::write(&file, "---\nmilestone: 1\ntitle: v1.0\nstate: open\ndue_on: 2024-07-15\n---\n\n").unwrap();
+        push_all_files(&config).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_push_leaves_milestones_changed_only_on_github_alone() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/milestones"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([milestone_json(1, "v1.0", None, "First release")])))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/milestones"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([milestone_json(1, "v1.0 final", None, "Renamed on GitHub")])))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/milestones/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(milestone_json(1, "v1.0", None, "First release")))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.sync_milestones = true;
+        pull(&config).await.unwrap();
+        assert!(config.state.get().milestone_fingerprints.contains_key(&1));
+
+        // The local file is as pulled, so the rename on GitHub stays
+        assert!(push(&config).await.unwrap().is_empty());
+    }
+}
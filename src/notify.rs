@@ -0,0 +1,139 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::hook::Direction;
+use crate::{Config, SyncSummary};
+
+/// At most one notification is sent per this interval; the rest are counted
+/// and the count goes out with the next one.
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Posts a JSON summary of each sync to the `--notify` URL.
+pub struct Notifier {
+    url: String,
+    min_interval: Duration,
+    sent: Mutex<Sent>,
+}
+
+#[derive(Default)]
+struct Sent {
+    last: Option<Instant>,
+    suppressed: usize,
+}
+
+impl Notifier {
+    pub fn new(url: &str) -> Self {
+        Notifier { url: url.to_string(), min_interval: MIN_INTERVAL, sent: Mutex::default() }
+    }
+
+    // Whether a notification may go out now; if not, it is counted as
+    // suppressed. Returns how many were suppressed before this one.
+    fn admit(&self) -> Option<usize> {
+        let mut sent = self.sent.lock().unwrap();
+        if sent.last.is_some_and(|last| last.elapsed() < self.min_interval) {
+            sent.suppressed += 1;
+            return None;
+        }
+        sent.last = Some(Instant::now());
+        Some(std::mem::take(&mut sent.suppressed))
+    }
+}
+
+/// Notifies `--notify` of a sync that changed something, or of one that
+/// failed. Syncs that changed nothing stay quiet. A notification that can't
+/// be delivered is only reported, like a failing post-sync hook.
+pub async fn notify(config: &Config, direction: Direction, outcome: Result<&SyncSummary, &anyhow::Error>) {
+    let Some(notifier) = &config.notify else {
+        return;
+    };
+    if config.dry_run {
+        return;
+    }
+    if outcome.is_ok_and(|summary| summary.created.is_empty() && summary.updated.is_empty() && summary.pruned.is_empty()) {
+        return;
+    }
+    let Some(suppressed) = notifier.admit() else {
+        return;
+    };
+
+    let repo = format!("{}/{}", config.repo_owner, config.repo_name);
+    let payload = match outcome {
+        Ok(summary) => serde_json::json!({
+            "event": "sync",
+            "direction": direction.as_str(),
+            "repo": repo,
+            "created": summary.created,
+            "updated": summary.updated,
+            "pruned": summary.pruned,
+            "suppressed": suppressed,
+            "text": format!(
+                "retasks {} {}: {} created, {} updated, {} pruned",
+                direction.as_str(),
+                repo,
+                summary.created.len(),
+                summary.updated.len(),
+                summary.pruned.len()
+            ),
+        }),
+        Err(error) => serde_json::json!({
+            "event": "error",
+            "direction": direction.as_str(),
+            "repo": repo,
+            "error": format!("{:#}", error),
+            "suppressed": suppressed,
+            "text": format!("retasks {} {} failed: {:#}", direction.as_str(), repo, error),
+        }),
+    };
+
+    let result = reqwest::Client::new()
+        .post(&notifier.url)
+        .header("Content-Type", "application/json")
+        .body(payload.to_string())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    if let Err(e) = result {
+        eprintln!("{}", config.colors.warning(&format!("Warning: failed to send --notify notification: {}", e)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_notifications_are_sent_for_changes_and_rate_limited() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(body_partial_json(serde_json::json!({ "event": "sync", "direction": "pull", "created": [3], "suppressed": 0 })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(body_partial_json(serde_json::json!({ "event": "error", "direction": "push", "suppressed": 1 })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.notify = Some(Notifier::new(&format!("{}/hook", server.uri())));
+
+        let changed = SyncSummary { created: vec![3], ..Default::default() };
+        notify(&config, Direction::Pull, Ok(&SyncSummary::default())).await;
+        notify(&config, Direction::Pull, Ok(&changed)).await;
+        notify(&config, Direction::Pull, Ok(&changed)).await;
+
+        // As if the interval had passed since the last one went out
+        let sent = &config.notify.as_ref().unwrap().sent;
+        sent.lock().unwrap().last = Instant::now().checked_sub(MIN_INTERVAL);
+        notify(&config, Direction::Push, Err(&anyhow::anyhow!("GitHub is down"))).await;
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use futures::StreamExt;
+
+use crate::{Config, Issue};
+
+/// Fills in `participants`, the logins of everyone who commented on each
+/// issue, sorted. Listing comments takes a request per issue, so issues
+/// without comments are skipped and up to `--comment-concurrency` are
+/// listed at once. An issue whose comments can't be listed is written
+/// without participants, with a warning.
+pub async fn fill(config: &Config, issues: &mut [Issue]) {
+    let commented: Vec<i64> = issues.iter().filter(|issue| issue.comments > 0).map(|issue| issue.number).collect();
+    let mut listed: HashMap<i64, Option<Vec<String>>> = futures::stream::iter(commented)
+        .map(|number| async move { (number, commenters(config, number).await) })
+        .buffer_unordered(config.comment_concurrency)
+        .collect()
+        .await;
+    for issue in issues.iter_mut() {
+        issue.participants = listed.remove(&issue.number).unwrap_or_else(|| Some(Vec::new()));
+    }
+}
+
+// Rate limits are handled by with_rotation, as for every other request
+async fn commenters(config: &Config, number: i64) -> Option<Vec<String>> {
+    let result = config
+        .github
+        .with_rotation(|client| async move {
+            client.issues().list_all_comments(&config.repo_owner, &config.repo_name, number, None).await
+        })
+        .await;
+    let comments = match result {
+        Ok(response) => response.body,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                config.colors.warning(&format!("Warning: failed to list the comments of issue #{}: {:#}", number, e))
+            );
+            return None;
+        }
+    };
+
+    let mut logins: Vec<String> = comments.into_iter().filter_map(|comment| comment.user.map(|user| user.login)).collect();
+    logins.sort();
+    logins.dedup();
+    Some(logins)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{issue_json, test_config};
+    use crate::{parse_markdown_file, sync_github_to_local};
+    use std::fs;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_comment_count_and_participants_are_written_on_pull() {
+        let server = MockServer::start().await;
+        let mut commented = issue_json(1, "Commented", "open", &[], "Body");
+        commented["comments"] = serde_json::json!(3);
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                commented,
+                issue_json(2, "Quiet", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1/comments"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "id": 1, "body": "a", "author_association": "NONE", "user": { "login": "zoe" } },
+                { "id": 2, "body": "b", "author_association": "NONE", "user": { "login": "alice" } },
+                { "id": 3, "body": "c", "author_association": "NONE", "user": { "login": "zoe" } },
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.fetch_participants = true;
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let (frontmatter, _) = parse_markdown_file(&fs::read_to_string(dir.path().join("issue-1.md")).unwrap()).unwrap();
+        assert_eq!(frontmatter["comments"], "3");
+        assert_eq!(frontmatter["participants"], "[alice, zoe]");
+        let (frontmatter, _) = parse_markdown_file(&fs::read_to_string(dir.path().join("issue-2.md")).unwrap()).unwrap();
+        assert_eq!(frontmatter["comments"], "0");
+        assert_eq!(frontmatter["participants"], "[]");
+    }
+
+    #[tokio::test]
+    async fn test_comments_are_listed_concurrently() {
+        let server = MockServer::start().await;
+        let issues: Vec<serde_json::Value> = (1..=4)
+            .map(|number| {
+                let mut issue = issue_json(number, "Commented", "open", &[], "Body");
+                issue["comments"] = serde_json::json!(1);
+                issue
+            })
+            .collect();
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!(issues)))
+            .mount(&server)
+            .await;
+        // Never answered, so listing them one at a time would stop at the first
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/repos/owner/repo/issues/\d+/comments$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])).set_delay(Duration::from_secs(3600)))
+            .expect(4)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.fetch_participants = true;
+        config.comment_concurrency = 4;
+        let pull = tokio::spawn(async move { sync_github_to_local(&config, None).await });
+
+        let all_requested = async {
+            loop {
+                let requests = server.received_requests().await.unwrap();
+                if requests.iter().filter(|request| request.url.path().ends_with("/comments")).count() == 4 {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(30), all_requested).await.expect("comments were not listed concurrently");
+        pull.abort();
+    }
+}
@@ -0,0 +1,245 @@
+use std::io;
+
+use anyhow::{Context, Result};
+
+use crate::error::SyncError;
+use crate::{
+    format_list, frontmatter, issue_from_file, parse_list, parse_markdown_file, parse_state, read_issue_text, scan_issue_files, schema,
+    sync_local_to_github, write_issue_text, Config, Issue,
+};
+
+/// Frontmatter changes to make to every local file matching all `filters`.
+#[derive(Debug, Default)]
+pub struct PatchOptions {
+    pub filters: Vec<Filter>,
+    pub add_labels: Vec<String>,
+    pub remove_labels: Vec<String>,
+    pub add_assignees: Vec<String>,
+    pub remove_assignees: Vec<String>,
+    /// `open` or `closed`.
+    pub state: Option<String>,
+    /// Push the patched files afterwards, like saving them in watch mode.
+    pub push: bool,
+}
+
+/// A `--filter key=value` condition on a local file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Label(String),
+    Assignee(String),
+    State(String),
+    Number(i64),
+}
+
+impl Filter {
+    pub fn parse(value: &str) -> Result<Self> {
+        let (key, expected) = value
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid filter {:?} (expected key=value)", value))?;
+        match key.trim() {
+            "label" => Ok(Filter::Label(expected.to_string())),
+            "assignee" => Ok(Filter::Assignee(expected.to_string())),
+            "state" => Ok(Filter::State(expected.to_string())),
+            "number" => expected
+                .parse()
+                .map(Filter::Number)
+                .map_err(|_| anyhow::anyhow!("Invalid issue number in filter: {}", expected)),
+            _ => Err(anyhow::anyhow!("Unknown filter key {:?} (expected label, assignee, state or number)", key)),
+        }
+    }
+
+    pub fn matches(&self, issue: &Issue) -> bool {
+        match self {
+            Filter::Label(label) => issue.labels.contains(label),
+            Filter::Assignee(login) => issue.assignees.iter().any(|a| a.eq_ignore_ascii_case(login)),
+            Filter::State(state) => issue.state.trim().eq_ignore_ascii_case(state),
+            Filter::Number(number) => issue.number == *number,
+        }
+    }
+}
+
+/// Edits the frontmatter of the matching local files in place, leaving
+/// their bodies and other keys as they are. Returns the numbers of the
+/// files that changed. Files are the source of truth here: nothing is read
+/// from GitHub, and changes only reach it with `push`.
+pub async fn patch(config: &Config, options: &PatchOptions) -> Result<Vec<i64>> {
+    if config.format.data_file_name().is_some() {
+        return Err(anyhow::anyhow!("patch edits issue files, so it needs --format markdown"));
+    }
+    if let Some(state) = &options.state {
+        parse_state(state)?;
+    }
+
+    let mut patched = Vec::new();
+    for file in scan_issue_files(config)? {
+        let Some(number) = file.number else { continue };
+        if config.only.is_some_and(|only| only != number) {
+            continue;
+        }
+
+        let content = match read_issue_text(config, &file.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                eprintln!("{}", config.colors.warning(&format!("Warning: not patching {}: {}", file.path.display(), e)));
+                continue;
+            }
+            Err(e) => return Err(e).context(format!("Failed to read {}", file.path.display())),
+        };
+        let (mut frontmatter, body) = parse_markdown_file(&content)?;
+        config.state_map.apply(&mut frontmatter).map_err(|e| SyncError::parse(&file.path, e))?;
+        if let schema::Status::Future(version) = schema::status(&frontmatter)? {
+            eprintln!(
+                "{}",
+                config.colors.warning(&format!(
+                    "Warning: {} has schema_version {}, newer than this retasks supports; not patching it",
+                    file.path.display(),
+                    version
+                ))
+            );
+            continue;
+        }
+
+        let issue = issue_from_file(number, &frontmatter, body)
+            .map_err(|e| SyncError::parse(&file.path, e))?;
+        if !options.filters.iter().all(|filter| filter.matches(&issue)) {
+            continue;
+        }
+
+        let updated = patch_content(&content, options).context(format!("Failed to patch {}", file.path.display()))?;
+        if updated == content {
+            continue;
+        }
+
+        if config.dry_run {
+            println!("Would patch issue #{} ({})", number, file.path.display());
+            continue;
+        }
+        write_issue_text(config, &file.path, &updated)
+            .context(format!("Failed to write {}", file.path.display()))?;
+        println!("Patched issue #{} ({})", number, file.path.display());
+        patched.push((number, file.path));
+    }
+
+    if options.push {
+        let mut failures = Vec::new();
+        for (number, path) in &patched {
+            if let Err(e) = sync_local_to_github(config, path).await {
+                eprintln!("{}", config.colors.error(&format!("Failed to push issue #{}: {:#}", number, e)));
+                failures.push(format!("#{}", number));
+            }
+        }
+        if !failures.is_empty() {
+            return Err(anyhow::anyhow!("Failed to push {} patched issue(s): {}", failures.len(), failures.join(", ")));
+        }
+    }
+
+    Ok(patched.into_iter().map(|(number, _)| number).collect())
+}
+
+// Rewrites only the frontmatter lines the patch touches; keys it doesn't
+// mention, and the body, are kept byte for byte.
+fn patch_content(content: &str, options: &PatchOptions) -> Result<String> {
+    if !content.starts_with("---") {
+        return Err(anyhow::anyhow!("File has no frontmatter"));
+    }
+    let (block, end) = frontmatter::block(content).ok_or_else(|| anyhow::anyhow!("Frontmatter is not closed"))?;
+    let mut lines: Vec<String> = block.lines().map(str::to_string).collect();
+    let original = lines.clone();
+
+    edit_list(&mut lines, "labels", &options.add_labels, &options.remove_labels)?;
+    edit_list(&mut lines, "assignees", &options.add_assignees, &options.remove_assignees)?;
+
+    if let Some(state) = &options.state {
+        let state = state.trim().to_lowercase();
+        match lines.iter().position(|line| key_of(line) == Some("state")) {
+            Some(index) if value_of(&lines[index]).eq_ignore_ascii_case(&state) => {}
+            Some(index) => {
+                lines[index] = format!("state: {}", state);
+                // The old reason belongs to the old state
+                lines.retain(|line| key_of(line) != Some("state_reason"));
+            }
+            None => lines.push(format!("state: {}", state)),
+        }
+    }
+
+    if lines == original {
+        return Ok(content.to_string());
+    }
+    Ok(format!("---\n{}\n{}", lines.join("\n"), &content[end..]))
+}
+
+fn edit_list(lines: &mut Vec<String>, key: &str, add: &[String], remove: &[String]) -> Result<()> {
+    if add.is_empty() && remove.is_empty() {
+        return Ok(());
+    }
+
+    let index = lines.iter().position(|line| key_of(line) == Some(key));
+    let current = match index {
+        Some(index) => parse_list(value_of(&lines[index]))?,
+        None => Vec::new(),
+    };
+    let mut items: Vec<String> = current.iter().filter(|item| !remove.contains(item)).cloned().collect();
+    for item in add {
+        if !items.contains(item) {
+            items.push(item.clone());
+        }
+    }
+    if items == current {
+        return Ok(());
+    }
+
+    let line = format!("{}: {}", key, format_list(&items));
+    match index {
+        Some(index) => lines[index] = line,
+        None => lines.push(line),
+    }
+    Ok(())
+}
+
+fn key_of(line: &str) -> Option<&str> {
+    line.find(':').map(|index| line[..index].trim())
+}
+
+fn value_of(line: &str) -> &str {
+    line.find(':').map(|index| line[index + 1..].trim()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use std::fs;
+    use wiremock::MockServer;
+
+    #[tokio::test]
+    async fn test_patch_edits_matching_frontmatter_only() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let matching = "---\nnumber: 1\ntitle: Old\nstate: closed\nstate_reason: not_planned\nlabels: [old, ui]\ncustom: kept\n---\n\nBody with\nlabels: [old]\n";
+        fs::write(dir.path().join("issue-1.md"), matching).unwrap();
+        fs::write(dir.path().join("issue-2.md"), "---\nnumber: 2\ntitle: Other\nlabels: [ui]\n---\n\nBody").unwrap();
+        let config = test_config(&server, dir.path());
+
+        let options = PatchOptions {
+            filters: vec![Filter::parse("label=old").unwrap()],
+            add_labels: vec!["triaged".to_string()],
+            remove_labels: vec!["old".to_string()],
+            state: Some("open".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(patch(&config, &options).await.unwrap(), vec![1]);
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("issue-1.md")).unwrap(),
+            "---\nnumber: 1\ntitle: Old\nstate: open\nlabels: [ui, triaged]\ncustom: kept\n---\n\nBody with\nlabels: [old]\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("issue-2.md")).unwrap(),
+            "---\nnumber: 2\ntitle: Other\nlabels: [ui]\n---\n\nBody"
+        );
+
+        // Nothing left to change the second time
+        assert!(patch(&config, &options).await.unwrap().is_empty());
+        assert!(Filter::parse("milestone=1").is_err());
+    }
+}
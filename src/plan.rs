@@ -0,0 +1,293 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::Serialize;
+
+use crate::error::SyncError;
+use crate::frontmatter::Frontmatter;
+use crate::state::SyncState;
+use crate::{
+    assignee_login, attachments, fetch_issues, in_number_range, include, is_assigned, issue_from_remote, issues_differ, lists_every_issue, markers, new_issue_path,
+    parse_markdown_file, read_issue_text, refs, scan_issue_files, sidecar, timeline, wrap, Config, Issue,
+};
+
+/// What to do when an issue changed both locally and on GitHub since the
+/// last sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictStrategy {
+    RemoteWins,
+    LocalWins,
+    Skip,
+}
+
+impl ConflictStrategy {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "remote-wins" => Ok(ConflictStrategy::RemoteWins),
+            "local-wins" => Ok(ConflictStrategy::LocalWins),
+            "skip" => Ok(ConflictStrategy::Skip),
+            _ => Err(anyhow::anyhow!(
+                "Invalid --conflict-strategy value: {} (expected remote-wins, local-wins or skip)",
+                value
+            )),
+        }
+    }
+}
+
+/// Which side changed, for an issue whose local file and remote differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Pull,
+    Push,
+    Conflict,
+}
+
+/// Uses the sync cursor to work out which side changed. Without a cursor
+/// (first run) GitHub is taken as the source of truth.
+pub fn resolve_difference(
+    state: &SyncState,
+    local_modified: DateTime<Utc>,
+    remote_updated: Option<DateTime<FixedOffset>>,
+) -> Change {
+    let local_changed = state.last_event_at.is_some_and(|at| local_modified > at);
+    let remote_changed = match (state.last_pull_at, remote_updated) {
+        (Some(at), Some(updated)) => updated > at,
+        _ => true,
+    };
+
+    match (local_changed, remote_changed) {
+        (true, false) => Change::Push,
+        (true, true) => Change::Conflict,
+        (false, _) => Change::Pull,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// No local file yet
+    PullCreate,
+    PullUpdate,
+    Push,
+    /// Changed on both sides; carries the strategy that decided it
+    Conflict(ConflictStrategy),
+    /// Local file whose issue no longer exists on GitHub
+    Prune,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedChange {
+    pub number: i64,
+    pub path: PathBuf,
+    pub action: Action,
+}
+
+/// The bidirectional plan: everything a pull and a push would change,
+/// with the conflict strategy applied.
+#[derive(Debug, Default)]
+pub struct SyncPlan {
+    pub changes: Vec<PlannedChange>,
+    pub in_sync: usize,
+}
+
+/// Compares every local issue file with GitHub without changing anything.
+pub async fn compute_sync_plan(config: &Config) -> Result<SyncPlan> {
+    compute_plan(config, config.prune).await
+}
+
+// With `include_orphans`, local files whose issue is gone from GitHub are
+// planned as prunes whether or not --prune is set
+async fn compute_plan(config: &Config, include_orphans: bool) -> Result<SyncPlan> {
+    if config.format.data_file_name().is_some() {
+        return Err(anyhow::anyhow!("Planning is only supported with --format markdown"));
+    }
+
+    let state = config.state.get();
+    let mut remote: BTreeMap<i64, Issue> = fetch_issues(config, None)
+        .await?
+        .into_iter()
+        .map(|issue| (issue.number, issue_from_remote(config, issue)))
+        .collect();
+
+    let mut plan = SyncPlan::default();
+    let mut seen = HashSet::new();
+
+    for file in scan_issue_files(config)? {
+        let Some(number) = file.number else { continue };
+        seen.insert(number);
+
+        let Some(remote_issue) = remote.remove(&number) else {
+            // A filtered listing leaves out issues that still exist
+            if include_orphans && config.only.is_none() && lists_every_issue(config) {
+                plan.changes.push(PlannedChange { number, path: file.path, action: Action::Prune });
+            }
+            continue;
+        };
+
+        let content = match read_issue_text(config, &file.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                eprintln!("{}", config.colors.warning(&format!("Warning: skipping {}: {}", file.path.display(), e)));
+                continue;
+            }
+            Err(e) => return Err(e).context(format!("Failed to read {}", file.path.display())),
+        };
+        let (mut frontmatter, body) = parse_markdown_file(&content)?;
+        config.state_map.apply(&mut frontmatter).map_err(|e| SyncError::parse(&file.path, e))?;
+        let body = include::expand(config, &file.path, &body).map_err(|e| SyncError::parse(&file.path, e))?;
+        let body = if config.include_timeline { timeline::strip(&body) } else { body };
+        let body = match config.wrap_body {
+            Some(width) => wrap::unwrap(&body, width),
+            None => body,
+        };
+        let body = attachments::restore_remote_urls(config, &body)?;
+        let body = if config.rewrite_refs { refs::restore(&body) } else { body };
+
+        // Keys missing from the frontmatter aren't pushed, so they can't differ
+        let frontmatter = Frontmatter::parse(&frontmatter).map_err(|e| SyncError::parse(&file.path, e))?;
+        // Nor can fields excluded from pushes
+        let pushed = &config.push_fields;
+        let mut local_issue = remote_issue.clone();
+        if let Some(title) = frontmatter.title.filter(|_| pushed.includes("title")) {
+            local_issue.title = title;
+        }
+        if let Some(state) = frontmatter.state.filter(|_| pushed.includes("state")) {
+            local_issue.state = state.to_string();
+        }
+        if let Some(labels) = frontmatter.labels.filter(|_| pushed.includes("labels")) {
+            local_issue.labels = labels;
+        }
+        if let Some(assignees) = frontmatter.assignees.filter(|_| pushed.includes("assignees")) {
+            local_issue.assignees = assignees;
+        }
+        if pushed.includes("body") {
+            local_issue.body = Some(body);
+        }
+
+        if !issues_differ(config, &local_issue, &remote_issue) {
+            plan.in_sync += 1;
+            continue;
+        }
+
+        let modified = sidecar::modified(config.metadata, &file.path)?;
+        let action = match resolve_difference(&state, modified, remote_issue.updated_at) {
+            Change::Pull => Action::PullUpdate,
+            Change::Push => Action::Push,
+            Change::Conflict => Action::Conflict(config.conflict_strategy),
+        };
+        plan.changes.push(PlannedChange { number, path: file.path, action });
+    }
+
+    // A pull with --assignee doesn't create files for anyone else's issues
+    let login = assignee_login(config).await?;
+    for (number, issue) in remote {
+        if seen.contains(&number) || login.as_deref().is_some_and(|login| !is_assigned(&issue, login)) {
+            continue;
+        }
+        let path = new_issue_path(config, &issue);
+        plan.changes.push(PlannedChange { number, path, action: Action::PullCreate });
+    }
+
+    if let Some(only) = config.only {
+        plan.changes.retain(|change| change.number == only);
+    }
+    // Pulls and prunes stay within --number-min/--number-max; pushes don't
+    plan.changes.retain(|change| change.action == Action::Push || in_number_range(config, change.number));
+    plan.changes.sort_by_key(|change| change.number);
+    Ok(plan)
+}
+
+/// Prints the plan for the `plan` subcommand.
+pub fn print_plan(config: &Config, plan: &SyncPlan) {
+    for change in &plan.changes {
+        let line = format!("#{:<6} {}", change.number, change.path.display());
+        match &change.action {
+            Action::PullCreate => println!("{}", config.colors.success(&format!("pull   create  {}", line))),
+            Action::PullUpdate => println!("{}", config.colors.success(&format!("pull   update  {}", line))),
+            Action::Push => println!("{}", config.colors.success(&format!("push   update  {}", line))),
+            Action::Prune => eprintln!("{}", config.colors.warning(&format!("prune          {}", line))),
+            Action::Conflict(strategy) => {
+                let resolution = match strategy {
+                    ConflictStrategy::RemoteWins => "pull (remote wins)",
+                    ConflictStrategy::LocalWins => "push (local wins)",
+                    ConflictStrategy::Skip => "skipped",
+                };
+                eprintln!("{}", config.colors.warning(&format!("conflict       {} -> {}", line, resolution)));
+            }
+        }
+    }
+
+    println!(
+        "{} change(s) planned, {} issue(s) in sync",
+        plan.changes.len(),
+        plan.in_sync
+    );
+}
+
+/// How the local files differ from GitHub, for the `verify` subcommand.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Divergence {
+    pub local_ahead: Vec<i64>,
+    pub remote_ahead: Vec<i64>,
+    pub conflicting: Vec<i64>,
+    /// Local files whose issue no longer exists on GitHub.
+    pub missing_remote: Vec<i64>,
+}
+
+impl Divergence {
+    pub fn is_empty(&self) -> bool {
+        self.local_ahead.is_empty()
+            && self.remote_ahead.is_empty()
+            && self.conflicting.is_empty()
+            && self.missing_remote.is_empty()
+    }
+}
+
+/// Checks that the local files match GitHub, for use as a CI gate. Nothing
+/// is written locally or remotely; the error lists which side is ahead.
+pub async fn verify(config: &Config) -> Result<()> {
+    // Checked first and on their own, so a bad merge fails the gate even
+    // when GitHub can't be reached
+    let conflicted = markers::scan(config)?;
+    if !conflicted.is_empty() {
+        for (path, lines) in &conflicted {
+            for line in lines {
+                eprintln!("{}", config.colors.error(&format!("{}:{}: merge conflict marker", path.display(), line)));
+            }
+        }
+        return Err(anyhow::anyhow!(
+            "{} file(s) have merge conflict markers; resolve them before syncing",
+            conflicted.len()
+        ));
+    }
+
+    let plan = compute_plan(config, true).await?;
+
+    let mut divergence = Divergence::default();
+    for change in &plan.changes {
+        let (list, side) = match change.action {
+            Action::Push => (&mut divergence.local_ahead, "local ahead"),
+            Action::PullCreate | Action::PullUpdate => (&mut divergence.remote_ahead, "remote ahead"),
+            Action::Conflict(_) => (&mut divergence.conflicting, "changed on both sides"),
+            Action::Prune => (&mut divergence.missing_remote, "not on GitHub"),
+        };
+        list.push(change.number);
+        eprintln!("{}", config.colors.warning(&format!("#{:<6} {:<22} {}", change.number, side, change.path.display())));
+    }
+
+    if divergence.is_empty() {
+        println!("{}", config.colors.success(&format!("All {} issue(s) are in sync with GitHub", plan.in_sync)));
+        return Ok(());
+    }
+
+    Err(anyhow::anyhow!(
+        "Issues are out of sync with GitHub: {} local ahead, {} remote ahead, {} changed on both sides, {} not on GitHub",
+        divergence.local_ahead.len(),
+        divergence.remote_ahead.len(),
+        divergence.conflicting.len(),
+        divergence.missing_remote.len()
+    ))
+}
@@ -0,0 +1,84 @@
+use std::io::{self, IsTerminal, Write};
+
+/// Pulls writing fewer issues than this finish too quickly to need a bar.
+const MIN_ISSUES: usize = 50;
+/// Width of the bar itself, between the brackets.
+const WIDTH: usize = 30;
+
+/// A progress bar on stderr for the files of a long pull, shown only when
+/// stderr is a terminal. Each step clears the bar before it runs and draws
+/// it again once it is done, so lines printed during a step go above it.
+pub struct Progress {
+    total: usize,
+    done: usize,
+    enabled: bool,
+    drawn: bool,
+}
+
+impl Progress {
+    pub fn new(total: usize) -> Self {
+        Progress { total, done: 0, enabled: total >= MIN_ISSUES && io::stderr().is_terminal(), drawn: false }
+    }
+
+    /// Starts the next step; it counts as done when the guard is dropped.
+    pub fn step(&mut self) -> Step<'_> {
+        self.clear();
+        Step(self)
+    }
+
+    /// Removes the bar once every step is done.
+    pub fn finish(&mut self) {
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        if self.drawn {
+            eprint!("\r\x1b[K");
+            self.drawn = false;
+        }
+    }
+
+    fn draw(&mut self) {
+        eprint!("\r{}", render(self.done, self.total));
+        let _ = io::stderr().flush();
+        self.drawn = true;
+    }
+}
+
+/// One step of a [`Progress`], counted and drawn when dropped.
+pub struct Step<'a>(&'a mut Progress);
+
+impl Drop for Step<'_> {
+    fn drop(&mut self) {
+        let progress = &mut *self.0;
+        progress.done += 1;
+        if progress.enabled {
+            progress.draw();
+        }
+    }
+}
+
+fn render(done: usize, total: usize) -> String {
+    let filled = (WIDTH * done).checked_div(total).unwrap_or(WIDTH).min(WIDTH);
+    format!("[{}{}] {}/{} issues", "#".repeat(filled), " ".repeat(WIDTH - filled), done, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar_fills_with_the_issues_written() {
+        assert_eq!(render(0, 120), format!("[{}] 0/120 issues", " ".repeat(WIDTH)));
+        assert_eq!(render(60, 120), format!("[{}{}] 60/120 issues", "#".repeat(15), " ".repeat(15)));
+        assert_eq!(render(120, 120), format!("[{}] 120/120 issues", "#".repeat(WIDTH)));
+
+        let mut progress = Progress::new(3);
+        for _ in 0..3 {
+            let _step = progress.step();
+        }
+        progress.finish();
+        assert_eq!(progress.done, 3);
+        assert!(!progress.drawn);
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::fs;
+
+use anyhow::{Context, Result};
+
+use crate::{types, write_atomically, Config, Issue};
+
+/// Where `--keep-raw` writes each pulled issue as the API returned it, in
+/// the issues directory. Hidden, so scans and pushes never read it.
+pub const RAW_DIR: &str = ".raw";
+
+/// The API's JSON for each fetched issue, by number, to be written once the
+/// pull knows which issues it writes.
+pub fn collect(fetched: &[types::Issue]) -> Result<HashMap<i64, serde_json::Value>> {
+    fetched
+        .iter()
+        .map(|issue| Ok((issue.number, serde_json::to_value(issue).context("Failed to serialize an issue from GitHub")?)))
+        .collect()
+}
+
+/// Writes `.raw/issue-N.json` for each of `issues`, pretty-printed so it can
+/// be diffed against the markdown file.
+pub fn save(config: &Config, issues: &[Issue], raw: &HashMap<i64, serde_json::Value>) -> Result<()> {
+    let dir = config.issues_dir.join(RAW_DIR);
+    fs::create_dir_all(&dir).context(format!("Failed to create {}", dir.display()))?;
+    for issue in issues {
+        let Some(json) = raw.get(&issue.number) else { continue };
+        let path = dir.join(format!("issue-{}.json", issue.number));
+        let mut content = serde_json::to_string_pretty(json)?;
+        content.push('\n');
+        write_atomically(&path, content.as_bytes()).context(format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use crate::{scan_issue_files, sync_github_to_local};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_keep_raw_writes_the_api_json_of_each_pulled_issue() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "First", "open", &["bug"], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        sync_github_to_local(&config, None).await.unwrap();
+        assert!(!dir.path().join(RAW_DIR).exists());
+
+        config.keep_raw = true;
+        sync_github_to_local(&config, None).await.unwrap();
+        let raw: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.path().join(RAW_DIR).join("issue-1.json")).unwrap()).unwrap();
+        assert_eq!(raw["number"], 1);
+        assert_eq!(raw["labels"][0]["name"], "bug");
+        assert_eq!(raw["node_id"], "I_kwDOtest1");
+        assert_eq!(scan_issue_files(&config).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,99 @@
+use regex::{Captures, Regex};
+
+use crate::{issue_paths, relative_link, Config, Issue};
+
+// `#12` or `owner/repo#12`, not inside a word, URL, HTML entity or existing link text
+const REFERENCE_PATTERN: &str = r"(^|[^\w/#&\[\]])((?:([A-Za-z0-9][\w.-]*)/([\w.-]+))?#(\d+))\b";
+// A link written by `localize`: the reference as link text, pointing at its
+// file, which may be in another folder
+const LINK_PATTERN: &str = r"\[((?:[\w.-]+/[\w.-]+)?#(\d+))\]\((?:[^\s()]*/)?issue-(\d+)\.md\)";
+
+/// Points `#N` and `owner/repo#N` references to issues of this repository at
+/// their local files, for browsing offline. References to issues without a
+/// local file, and to other repositories, are left for GitHub to link.
+/// Links are relative to the referring file, as files may be in folders.
+pub fn localize(config: &Config, issues: &mut [Issue]) {
+    let Ok(paths) = issue_paths(config, issues) else { return };
+
+    for issue in issues.iter_mut() {
+        let (Some(body), Some(from)) = (&issue.body, paths.get(&issue.number)) else { continue };
+        let link = |number| paths.get(&number).map(|to| relative_link(config, from, to));
+        issue.body = Some(localize_body(body, &config.repo_owner, &config.repo_name, link));
+    }
+}
+
+/// Turns links written by [`localize`] back into plain references before a
+/// push. Other links to issue files are left alone.
+pub fn restore(body: &str) -> String {
+    let pattern = Regex::new(LINK_PATTERN).unwrap();
+    pattern
+        .replace_all(body, |captures: &Captures| {
+            if captures[2] == captures[3] {
+                captures[1].to_string()
+            } else {
+                captures[0].to_string()
+            }
+        })
+        .into_owned()
+}
+
+// `link` gives the link to an issue's file, if it has one
+fn localize_body(body: &str, owner: &str, repo: &str, link: impl Fn(i64) -> Option<String>) -> String {
+    let pattern = Regex::new(REFERENCE_PATTERN).unwrap();
+    let rewrite_line = |line: &str| {
+        pattern
+            .replace_all(line, |captures: &Captures| {
+                let same_repo = match (captures.get(3), captures.get(4)) {
+                    (Some(o), Some(r)) => o.as_str().eq_ignore_ascii_case(owner) && r.as_str().eq_ignore_ascii_case(repo),
+                    _ => true,
+                };
+                match captures[5].parse::<i64>().ok().filter(|_| same_repo).and_then(&link) {
+                    Some(link) => format!("{}[{}]({})", &captures[1], &captures[2], link),
+                    None => captures[0].to_string(),
+                }
+            })
+            .into_owned()
+    };
+
+    // Code is left as written: fenced blocks entirely, and `inline code` spans
+    let mut in_fence = false;
+    let mut lines = Vec::new();
+    for line in body.split('\n') {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            lines.push(line.to_string());
+        } else if in_fence {
+            lines.push(line.to_string());
+        } else {
+            let rewritten: Vec<String> = line
+                .split('`')
+                .enumerate()
+                .map(|(i, part)| if i % 2 == 0 { rewrite_line(part) } else { part.to_string() })
+                .collect();
+            lines.push(rewritten.join("`"));
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_round_trip_through_local_links() {
+        let link = |number| match number {
+            3 => Some("issue-3.md".to_string()),
+            4 => Some("../bugs/issue-4.md".to_string()),
+            _ => None,
+        };
+        let body = "Fixes #3, see owner/repo#4 and other/repo#3.\n#9 is not local, nor is `#3` or https://example.com/#3\n```\n#4\n```";
+
+        let localized = localize_body(body, "owner", "repo", link);
+        assert_eq!(
+            localized,
+            "Fixes [#3](issue-3.md), see [owner/repo#4](../bugs/issue-4.md) and other/repo#3.\n#9 is not local, nor is `#3` or https://example.com/#3\n```\n#4\n```"
+        );
+        assert_eq!(restore(&localized), body);
+    }
+}
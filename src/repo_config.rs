@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Settings that `--repo-config` can provide. Each one falls back to the
+/// command line flag of the same name, which always wins when given.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub issues_dir: Option<PathBuf>,
+    pub format: Option<String>,
+    pub interval: Option<u64>,
+    pub conflict_strategy: Option<String>,
+    pub prune: Option<bool>,
+    pub download_attachments: Option<bool>,
+    pub rewrite_refs: Option<bool>,
+    pub no_body_on_create: Option<bool>,
+}
+
+/// The `--repo-config` file: settings for every repository under
+/// `defaults`, and overrides per repository under `repos`, keyed by
+/// `owner/repo`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct RepoConfigFile {
+    #[serde(default)]
+    defaults: Settings,
+    #[serde(default)]
+    repos: BTreeMap<String, Settings>,
+}
+
+impl Settings {
+    // Keeps the values set here and fills the rest from `fallback`
+    fn or(self, fallback: Settings) -> Settings {
+        Settings {
+            issues_dir: self.issues_dir.or(fallback.issues_dir),
+            format: self.format.or(fallback.format),
+            interval: self.interval.or(fallback.interval),
+            conflict_strategy: self.conflict_strategy.or(fallback.conflict_strategy),
+            prune: self.prune.or(fallback.prune),
+            download_attachments: self.download_attachments.or(fallback.download_attachments),
+            rewrite_refs: self.rewrite_refs.or(fallback.rewrite_refs),
+            no_body_on_create: self.no_body_on_create.or(fallback.no_body_on_create),
+        }
+    }
+}
+
+/// Reads the settings for `repo` (`owner/repo`, matched ignoring case):
+/// its own section over `defaults`.
+pub fn load(path: &Path, repo: &str) -> Result<Settings> {
+    let content = fs::read_to_string(path).context(format!("Failed to read repo config: {}", path.display()))?;
+    let file: RepoConfigFile =
+        serde_yaml::from_str(&content).context(format!("Failed to parse repo config: {}", path.display()))?;
+    Ok(settings_for(file, repo))
+}
+
+fn settings_for(mut file: RepoConfigFile, repo: &str) -> Settings {
+    let key = file.repos.keys().find(|key| key.eq_ignore_ascii_case(repo)).cloned();
+    match key.and_then(|key| file.repos.remove(&key)) {
+        Some(settings) => settings.or(file.defaults),
+        None => file.defaults,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_section_overrides_defaults() {
+        let file: RepoConfigFile = serde_yaml::from_str(
+            "defaults:\n  interval: 600\n  format: json\nrepos:\n  Owner/Repo:\n    issues_dir: ./mirror/repo\n    format: markdown\n",
+        )
+        .unwrap();
+
+        let settings = settings_for(file, "owner/repo");
+        assert_eq!(settings.issues_dir, Some(PathBuf::from("./mirror/repo")));
+        assert_eq!(settings.format.as_deref(), Some("markdown"));
+        assert_eq!(settings.interval, Some(600));
+
+        assert!(serde_yaml::from_str::<RepoConfigFile>("defaults:\n  intervall: 5\n").is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::hook::Direction;
+use crate::{Config, SyncSummary};
+
+/// How `--report-file` entries are written: one JSON object per line, or a
+/// markdown section each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReportFormat {
+    Json,
+    Markdown,
+}
+
+impl ReportFormat {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "json" => Ok(ReportFormat::Json),
+            "markdown" => Ok(ReportFormat::Markdown),
+            _ => Err(anyhow::anyhow!("Invalid --report-format value: {} (expected json or markdown)", value)),
+        }
+    }
+}
+
+/// The durable record of every sync kept with `--report-file`.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub path: PathBuf,
+    pub format: ReportFormat,
+    /// Once an entry would take the file past this size, it is moved to
+    /// `PATH.1` and a new one started.
+    pub max_size: Option<u64>,
+}
+
+impl Report {
+    /// Without `--report-format`, a `.md` file gets markdown and any other
+    /// JSON lines.
+    pub fn new(path: &Path, format: Option<&str>, max_size: Option<&str>) -> Result<Self> {
+        let format = match format {
+            Some(format) => ReportFormat::parse(format)?,
+            None if path.extension().is_some_and(|ext| ext == "md") => ReportFormat::Markdown,
+            None => ReportFormat::Json,
+        };
+        let max_size = max_size.map(parse_size).transpose()?;
+        Ok(Report { path: path.to_path_buf(), format, max_size })
+    }
+}
+
+// A byte count with an optional K or M suffix, e.g. 10M
+fn parse_size(value: &str) -> Result<u64> {
+    let (digits, unit) = match value.trim().to_uppercase() {
+        v if v.ends_with('K') => (v[..v.len() - 1].to_string(), 1024),
+        v if v.ends_with('M') => (v[..v.len() - 1].to_string(), 1024 * 1024),
+        v => (v, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .filter(|size| *size > 0)
+        .and_then(|size| size.checked_mul(unit))
+        .ok_or_else(|| anyhow::anyhow!("Invalid --report-max-size: {} (expected a size like 500K or 10M)", value))
+}
+
+/// Appends an entry for a finished pull or push to the `--report-file`,
+/// whether it changed anything or not, including its error if it failed.
+/// Like the post-sync hook, a report that can't be written is only
+/// reported, so it never stops a sync.
+pub fn record(config: &Config, direction: Direction, outcome: Result<&SyncSummary, &anyhow::Error>) {
+    let Some(report) = &config.report else {
+        return;
+    };
+    let entry = entry(config, direction, outcome);
+    let text = match report.format {
+        ReportFormat::Json => format!("{}\n", entry),
+        ReportFormat::Markdown => markdown(&entry),
+    };
+    if let Err(e) = append(report, &text) {
+        eprintln!(
+            "{}",
+            config.colors.warning(&format!("Warning: failed to write report file {}: {}", report.path.display(), e))
+        );
+    }
+}
+
+fn entry(config: &Config, direction: Direction, outcome: Result<&SyncSummary, &anyhow::Error>) -> serde_json::Value {
+    let at = config.timezone.convert(Utc::now()).to_rfc3339_opts(SecondsFormat::Secs, true);
+    let repo = format!("{}/{}", config.repo_owner, config.repo_name);
+    let empty = SyncSummary::default();
+    let summary = outcome.unwrap_or(&empty);
+    let mut issues = Vec::new();
+    for (action, numbers) in [("created", &summary.created), ("updated", &summary.updated), ("pruned", &summary.pruned)] {
+        for number in numbers {
+            issues.push(serde_json::json!({ "number": number, "action": action, "title": summary.titles.get(number) }));
+        }
+    }
+    serde_json::json!({
+        "at": at,
+        "direction": direction.as_str(),
+        "repo": repo,
+        "dry_run": config.dry_run,
+        "outcome": if outcome.is_ok() { "success" } else { "error" },
+        "counts": { "created": summary.created.len(), "updated": summary.updated.len(), "pruned": summary.pruned.len() },
+        "issues": issues,
+        "error": outcome.err().map(|error| format!("{:#}", error)),
+    })
+}
+
+fn markdown(entry: &serde_json::Value) -> String {
+    let mut text = format!(
+        "## {} {} at {}{}\n\n",
+        entry["direction"].as_str().unwrap_or_default(),
+        entry["repo"].as_str().unwrap_or_default(),
+        entry["at"].as_str().unwrap_or_default(),
+        if entry["dry_run"] == true { " (dry run)" } else { "" }
+    );
+    if let Some(error) = entry["error"].as_str() {
+        text.push_str(&format!("Failed: {}\n\n", error));
+        return text;
+    }
+    let counts = &entry["counts"];
+    text.push_str(&format!("{} created, {} updated, {} pruned\n\n", counts["created"], counts["updated"], counts["pruned"]));
+    let issues = entry["issues"].as_array().map(Vec::as_slice).unwrap_or_default();
+    for issue in issues {
+        text.push_str(&format!("- #{} {}", issue["number"], issue["action"].as_str().unwrap_or_default()));
+        if let Some(title) = issue["title"].as_str() {
+            text.push_str(&format!(": {}", title));
+        }
+        text.push('\n');
+    }
+    if !issues.is_empty() {
+        text.push('\n');
+    }
+    text
+}
+
+fn append(report: &Report, text: &str) -> io::Result<()> {
+    if let Some(max_size) = report.max_size {
+        let size = fs::metadata(&report.path).map(|metadata| metadata.len()).unwrap_or(0);
+        if size > 0 && size + text.len() as u64 > max_size {
+            let mut rotated = report.path.clone().into_os_string();
+            rotated.push(".1");
+            fs::rename(&report.path, rotated)?;
+        }
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&report.path)?;
+    file.write_all(text.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_github_to_local;
+    use crate::tests::{issue_json, test_config};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_parse_size_rejects_sizes_that_overflow() {
+        assert_eq!(parse_size("500k").unwrap(), 500 * 1024);
+        assert_eq!(parse_size("10M").unwrap(), 10 * 1024 * 1024);
+        assert!(parse_size("99999999999999999M").is_err());
+        assert!(parse_size("0").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_each_sync_appends_a_report_entry_and_the_file_rotates() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([issue_json(1, "First", "open", &[], "Body")])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let reports = tempfile::tempdir().unwrap();
+        let report_path = reports.path().join("sync.jsonl");
+        let mut config = test_config(&server, dir.path());
+        config.report = Some(Report::new(&report_path, None, None).unwrap());
+
+        let summary = sync_github_to_local(&config, None).await.unwrap();
+        record(&config, Direction::Pull, Ok(&summary));
+        record(&config, Direction::Push, Err(&anyhow::anyhow!("GitHub is down")));
+
+        let content = fs::read_to_string(&report_path).unwrap();
+        let entries: Vec<serde_json::Value> = content.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["direction"], "pull");
+        assert_eq!(entries[0]["outcome"], "success");
+        assert_eq!(entries[0]["counts"], serde_json::json!({ "created": 1, "updated": 0, "pruned": 0 }));
+        assert_eq!(entries[0]["issues"], serde_json::json!([{ "number": 1, "action": "created", "title": "First" }]));
+        assert_eq!(entries[1]["outcome"], "error");
+        assert_eq!(entries[1]["error"], "GitHub is down");
+
+        // Markdown, rotated once the next entry no longer fits
+        let markdown_path = reports.path().join("sync.md");
+        config.report = Some(Report::new(&markdown_path, None, Some("1K")).unwrap());
+        record(&config, Direction::Pull, Ok(&summary));
+        let first = fs::read_to_string(&markdown_path).unwrap();
+        assert!(first.starts_with("## pull owner/repo at "), "{}", first);
+        assert!(first.contains("1 created, 0 updated, 0 pruned\n\n- #1 created: First\n"), "{}", first);
+        fs::write(&markdown_path, "x".repeat(1000)).unwrap();
+        record(&config, Direction::Pull, Ok(&SyncSummary::default()));
+        assert_eq!(fs::read_to_string(reports.path().join("sync.md.1")).unwrap().len(), 1000);
+        assert!(fs::read_to_string(&markdown_path).unwrap().contains("0 created, 0 updated, 0 pruned"));
+
+        assert!(Report::new(&markdown_path, Some("csv"), None).is_err());
+        assert!(Report::new(&markdown_path, None, Some("lots")).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::{format_list, frontmatter, parse_list};
+
+/// Version of the issue file frontmatter, written as `schema_version` by
+/// every pull. Files without the key predate versioning and count as 0.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How a file's frontmatter relates to the version this build writes.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Status {
+    Current,
+    Outdated(u32),
+    /// Written by a newer retasks. Such files are left alone rather than
+    /// read or rewritten with rules that may no longer apply.
+    Future(u32),
+}
+
+pub fn status(frontmatter: &HashMap<String, String>) -> Result<Status> {
+    let version = match frontmatter.get("schema_version") {
+        Some(value) => value
+            .parse::<u32>()
+            .map_err(|_| anyhow::anyhow!("Invalid schema_version: {}", value))?,
+        None => 0,
+    };
+
+    Ok(match version {
+        v if v == SCHEMA_VERSION => Status::Current,
+        v if v < SCHEMA_VERSION => Status::Outdated(v),
+        v => Status::Future(v),
+    })
+}
+
+/// Upgrades the frontmatter of an outdated file to [`SCHEMA_VERSION`] one
+/// version at a time. Only frontmatter lines that need it are rewritten;
+/// the body and any unknown keys are kept byte for byte.
+pub fn migrate(content: &str, from: u32) -> Result<String> {
+    if !content.starts_with("---") {
+        return Err(anyhow::anyhow!("File has no frontmatter to migrate"));
+    }
+    let (block, end) = frontmatter::block(content).ok_or_else(|| anyhow::anyhow!("Frontmatter is not closed"))?;
+
+    // Between the opening `---` line and the closing `---`
+    let mut lines: Vec<String> = block.lines().map(str::to_string).collect();
+    for version in from..SCHEMA_VERSION {
+        match version {
+            0 => upgrade_from_v0(&mut lines),
+            _ => unreachable!("no migration from schema version {}", version),
+        }
+    }
+
+    lines.retain(|line| key_of(line) != Some("schema_version"));
+    lines.push(format!("schema_version: {}", SCHEMA_VERSION));
+
+    Ok(format!("---\n{}\n{}", lines.join("\n"), &content[end..]))
+}
+
+// Before version 1, lists were read by splitting on commas, so bare values
+// such as `labels: bug, ui` were accepted. They become YAML flow sequences.
+fn upgrade_from_v0(lines: &mut [String]) {
+    for line in lines.iter_mut() {
+        let Some(key @ ("labels" | "assignees")) = key_of(line) else { continue };
+        let value = line[line.find(':').unwrap() + 1..].trim();
+
+        let items = match parse_list(value) {
+            Ok(items) => items,
+            Err(_) => value
+                .split(',')
+                .map(|s| s.trim().trim_matches(|c| c == '[' || c == ']').to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+        };
+        *line = format!("{}: {}", key, format_list(&items));
+    }
+}
+
+fn key_of(line: &str) -> Option<&str> {
+    line.find(':').map(|index| line[..index].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_markdown_file;
+
+    #[test]
+    fn test_migrate_v0_converts_bare_lists_and_keeps_body() {
+        let content = "---\nnumber: 3\ntitle: Old file\nlabels: bug, help wanted\nassignees: [octocat]\ncustom: kept\n---\n\nBody with --- dashes\n";
+        let (frontmatter, _) = parse_markdown_file(content).unwrap();
+        assert_eq!(status(&frontmatter).unwrap(), Status::Outdated(0));
+
+        let migrated = migrate(content, 0).unwrap();
+        assert_eq!(
+            migrated,
+            "---\nnumber: 3\ntitle: Old file\nlabels: [bug, help wanted]\nassignees: [octocat]\ncustom: kept\nschema_version: 1\n---\n\nBody with --- dashes\n"
+        );
+
+        let (frontmatter, _) = parse_markdown_file(&migrated).unwrap();
+        assert_eq!(status(&frontmatter).unwrap(), Status::Current);
+    }
+
+    #[test]
+    fn test_future_version_is_reported() {
+        let mut frontmatter = HashMap::new();
+        frontmatter.insert("schema_version".to_string(), "7".to_string());
+        assert_eq!(status(&frontmatter).unwrap(), Status::Future(7));
+    }
+}
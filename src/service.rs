@@ -0,0 +1,406 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use hotwatch::{Event, Hotwatch};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant, MissedTickBehavior};
+
+use crate::{bulk, cache, format, git, hook, milestones, notify, report, sync_github_to_local, sync_local_to_github, Config, SyncSummary};
+
+/// How many commands may wait while one is being handled.
+const QUEUE_SIZE: usize = 64;
+/// While this file exists in the issues directory, the service neither pulls
+/// nor pushes. Removing it resumes syncing with a catch-up pull.
+pub const PAUSE_FILE: &str = ".retasks-pause";
+
+/// What a running [`SyncService`] can be told to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Pull everything from GitHub now, as the periodic sync does.
+    TriggerPull,
+    /// Push changed local files, as the file watcher does with each batch
+    /// of events. The bulk check counts only these files.
+    PushFiles(Vec<PathBuf>),
+    /// Finish the command in progress and stop.
+    Shutdown,
+}
+
+/// Watch mode as a task: pulls on a timer, pushes files the watcher reports,
+/// and takes [`Command`]s sent with [`SyncService::send`]. Commands are handled
+/// one at a time, in the order they arrive, so a pull and a push never
+/// overlap.
+pub struct SyncService {
+    commands: mpsc::Sender<Command>,
+    task: JoinHandle<()>,
+    // Dropping the watcher stops it, so it lives as long as the service
+    _watcher: Option<Hotwatch>,
+}
+
+impl SyncService {
+    /// Starts the service on the current tokio runtime. The periodic pull
+    /// and the file watcher follow `--no-periodic-sync` and `--no-watch-files`.
+    pub fn start(config: Arc<Config>) -> Result<Self> {
+        let (commands, receiver) = mpsc::channel(QUEUE_SIZE);
+
+        let watcher = if config.watch_files {
+            let mut hotwatch = Hotwatch::new().context("Failed to initialize hotwatch")?;
+            // The watcher thread only hands paths to the relay, which never
+            // blocks, so events keep being read while a slow push holds up
+            // the queue
+            let (sender, events) = mpsc::unbounded_channel();
+            tokio::spawn(relay(events, commands.clone()));
+            // Recursive, so files in subdirectories are seen too. A file moved
+            // or renamed within the directory is pushed like an edit of its new
+            // path, which does nothing if its content still matches GitHub.
+            hotwatch
+                .watch(&config.issues_dir, move |event: Event| {
+                    match event {
+                        // Only fails once the service has stopped
+                        Event::Write(path) | Event::Create(path) | Event::Rename(_, path) => {
+                            let _ = sender.send(path);
+                        }
+                        // So that removing the pause file resumes right away,
+                        // rather than at the next scheduled pull
+                        Event::Remove(path) if path.file_name().is_some_and(|name| name == PAUSE_FILE) => {
+                            let _ = sender.send(path);
+                        }
+                        _ => {}
+                    }
+                })
+                .context("Failed to watch directory")?;
+            Some(hotwatch)
+        } else {
+            None
+        };
+
+        let task = tokio::spawn(run(config, receiver));
+        Ok(SyncService { commands, task, _watcher: watcher })
+    }
+
+    /// Queues a command. Fails once the service has shut down.
+    pub async fn send(&self, command: Command) -> Result<()> {
+        self.commands.send(command).await.map_err(|_| anyhow::anyhow!("Sync service is not running"))
+    }
+
+    /// Asks the service to shut down and waits until it has. A pull or push
+    /// in progress is finished first.
+    pub async fn stop(self) -> Result<()> {
+        // Fails only if the service already stopped, which is what we want
+        let _ = self.send(Command::Shutdown).await;
+        self.task.await.context("Sync service failed")
+    }
+}
+
+// Queues the paths the watcher reports as batches, in order. Paths that
+// arrive while the queue is full are collected into the next batch, and a
+// path changed several times meanwhile is queued once, since a push sends
+// the file as it is then. Stops when the watcher or the service does.
+async fn relay(mut events: mpsc::UnboundedReceiver<PathBuf>, commands: mpsc::Sender<Command>) {
+    while let Some(path) = events.recv().await {
+        let mut paths = vec![path];
+        while let Ok(path) = events.try_recv() {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        if commands.send(Command::PushFiles(paths)).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn run(config: Arc<Config>, mut commands: mpsc::Receiver<Command>) {
+    // The first scheduled pull is one interval after the initial sync
+    let mut interval = time::interval_at(Instant::now() + config.sync_interval, config.sync_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut paused = false;
+
+    loop {
+        let command = tokio::select! {
+            _ = interval.tick(), if config.periodic_sync => {
+                println!("Performing scheduled sync from GitHub to local...");
+                Command::TriggerPull
+            }
+            command = commands.recv() => command.unwrap_or(Command::Shutdown),
+        };
+
+        // Shutting down works while paused too
+        if command != Command::Shutdown && !resume_or_skip(&config, &mut paused, &command).await {
+            continue;
+        }
+
+        match command {
+            Command::TriggerPull => pull(&config).await,
+            Command::PushFiles(paths) => push_files(&config, &paths).await,
+            Command::Shutdown => break,
+        }
+    }
+}
+
+// Whether `command` should be handled, given the pause file. Skipped
+// commands are logged; on resuming, a catch-up pull is done first, which
+// also stands in for a scheduled pull.
+async fn resume_or_skip(config: &Config, paused: &mut bool, command: &Command) -> bool {
+    let pause_file = config.issues_dir.join(PAUSE_FILE);
+    if pause_file.exists() {
+        if !*paused {
+            println!("Paused: {} exists; not syncing until it is removed", pause_file.display());
+            *paused = true;
+        }
+        match command {
+            Command::TriggerPull => println!("Paused, skipping the scheduled sync"),
+            Command::PushFiles(paths) => {
+                for path in paths.iter().filter(|path| **path != pause_file) {
+                    println!("Paused, ignoring the change to {:?}", path);
+                }
+            }
+            _ => {}
+        }
+        return false;
+    }
+
+    if *paused {
+        println!("Resumed: {} was removed; catching up with GitHub...", pause_file.display());
+        *paused = false;
+        pull(config).await;
+        return *command != Command::TriggerPull;
+    }
+    true
+}
+
+async fn pull(config: &Config) {
+    // Pushes queued while GitHub was unreachable go first, so the pull
+    // doesn't see them as local edits still waiting
+    let pushed = cache::push_queued(config).await;
+    if !pushed.is_empty() {
+        let summary = SyncSummary { updated: pushed, ..Default::default() };
+        hook::run_post_sync_hook(config, hook::Direction::Push, &summary);
+        report::record(config, hook::Direction::Push, Ok(&summary));
+        notify::notify(config, hook::Direction::Push, Ok(&summary)).await;
+    }
+
+    match sync_github_to_local(config, None).await {
+        Ok(summary) => {
+            git::commit_pulled_changes(config, &summary);
+            hook::run_post_sync_hook(config, hook::Direction::Pull, &summary);
+            report::record(config, hook::Direction::Pull, Ok(&summary));
+            notify::notify(config, hook::Direction::Pull, Ok(&summary)).await;
+        }
+        Err(e) => {
+            eprintln!("{}", config.colors.error(&format!("Error syncing from GitHub: {:#}", e)));
+            report::record(config, hook::Direction::Pull, Err(&e));
+            notify::notify(config, hook::Direction::Pull, Err(&e)).await;
+        }
+    }
+}
+
+// Pushes each of `paths`, after one bulk check of the issue files among
+// them. If that fails, they are all left unpushed.
+async fn push_files(config: &Config, paths: &[PathBuf]) {
+    let issue_files: Vec<PathBuf> = match config.format.data_file_name() {
+        Some(_) => Vec::new(),
+        None => paths
+            .iter()
+            .filter(|path| !milestones::is_milestone_file(config, path))
+            .filter_map(|path| config.metadata.issue_file(path))
+            .collect(),
+    };
+    let refused = if issue_files.is_empty() {
+        false
+    } else if let Err(e) = bulk::check_files(config, &issue_files) {
+        report_push(config, Err(e)).await;
+        true
+    } else {
+        false
+    };
+
+    for path in paths {
+        if refused && config.metadata.issue_file(path).is_some_and(|path| issue_files.contains(&path)) {
+            continue;
+        }
+        push_file(config, path).await;
+    }
+}
+
+// Paths that aren't issue files (or the data file) are ignored
+async fn push_file(config: &Config, path: &Path) {
+    if milestones::is_milestone_file(config, path) {
+        println!("Local file changed: {:?}", path);
+        if let Err(e) = milestones::push(config).await {
+            eprintln!("{}", config.colors.error(&format!("Error syncing to GitHub: {:#}", e)));
+        }
+        return;
+    }
+    let result = if let Some(name) = config.format.data_file_name() {
+        if path.file_name().is_none_or(|file_name| file_name != name) {
+            return;
+        }
+        println!("Local file changed: {:?}", path);
+        format::push_data_file(config).await
+    } else if let Some(path) = config.metadata.issue_file(path) {
+        // A sidecar edit is pushed as an edit of its markdown file
+        println!("Local file changed: {:?}", path);
+        sync_local_to_github(config, &path).await.map(|number| number.into_iter().collect())
+    } else {
+        return;
+    };
+    report_push(config, result).await;
+}
+
+async fn report_push(config: &Config, result: Result<Vec<i64>>) {
+    match result {
+        Ok(updated) if !updated.is_empty() => {
+            let summary = SyncSummary { updated, ..Default::default() };
+            hook::run_post_sync_hook(config, hook::Direction::Push, &summary);
+            report::record(config, hook::Direction::Push, Ok(&summary));
+            notify::notify(config, hook::Direction::Push, Ok(&summary)).await;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("{}", config.colors.error(&format!("Error syncing to GitHub: {:#}", e)));
+            report::record(config, hook::Direction::Push, Err(&e));
+            notify::notify(config, hook::Direction::Push, Err(&e)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use std::fs;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_commands_are_handled_in_order_until_shutdown() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Issue", "open", &[], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Issue", "open", &[], "Edited")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.periodic_sync = false;
+        config.watch_files = false;
+
+        let service = SyncService::start(Arc::new(config)).unwrap();
+        service.send(Command::TriggerPull).await.unwrap();
+        service.send(Command::PushFiles(vec![dir.path().join("notes.txt")])).await.unwrap();
+        service.stop().await.unwrap();
+
+        let file_path = dir.path().join("issue-1.md");
+        let edited = fs::read_to_string(&file_path).unwrap().replace("Body", "Edited");
+        fs::write(&file_path, edited).unwrap();
+
+        let mut config = test_config(&server, dir.path());
+        config.periodic_sync = false;
+        config.watch_files = false;
+        let service = SyncService::start(Arc::new(config)).unwrap();
+        service.send(Command::PushFiles(vec![file_path])).await.unwrap();
+        service.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watcher_events_never_wait_for_the_queue() {
+        let (events, receiver) = mpsc::unbounded_channel();
+        let (commands, mut queued) = mpsc::channel(1);
+        // Sent from a plain thread, like the watcher's, while nothing takes
+        // commands off the queue
+        std::thread::spawn(move || {
+            for name in ["a.md", "b.md", "a.md", "a.md", "c.md"] {
+                events.send(PathBuf::from(name)).unwrap();
+            }
+        })
+        .join()
+        .unwrap();
+
+        tokio::spawn(relay(receiver, commands));
+        let mut pushed = Vec::new();
+        while let Some(Command::PushFiles(paths)) = queued.recv().await {
+            pushed.extend(paths);
+        }
+        assert_eq!(pushed, ["a.md", "b.md", "c.md"].map(PathBuf::from));
+    }
+
+    #[tokio::test]
+    async fn test_pause_file_skips_syncs_until_removed() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Issue", "open", &[], "Body"),
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH")).respond_with(ResponseTemplate::new(500)).expect(0).mount(&server).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("issue-1.md");
+        fs::write(&file_path, "---\nnumber: 1\ntitle: Edited while paused\n---\n\nBody").unwrap();
+        let pause_file = dir.path().join(PAUSE_FILE);
+        fs::write(&pause_file, "").unwrap();
+
+        let config = test_config(&server, dir.path());
+        let mut paused = false;
+        assert!(!resume_or_skip(&config, &mut paused, &Command::TriggerPull).await);
+        assert!(!resume_or_skip(&config, &mut paused, &Command::PushFiles(vec![file_path.clone()])).await);
+        assert!(paused);
+
+        // The next command after removing it starts with the catch-up pull,
+        // which replaces the scheduled one
+        fs::remove_file(&pause_file).unwrap();
+        assert!(!resume_or_skip(&config, &mut paused, &Command::TriggerPull).await);
+        assert!(!paused);
+        assert!(resume_or_skip(&config, &mut paused, &Command::PushFiles(vec![file_path])).await);
+    }
+
+    #[tokio::test]
+    async fn test_a_refused_bulk_batch_does_not_hold_up_later_edits() {
+        let server = MockServer::start().await;
+        let issues: Vec<serde_json::Value> = (1..=3).map(|n| issue_json(n, "Issue", "open", &[], "Body")).collect();
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issues))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Issue", "open", &[], "Fixed")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH")).respond_with(ResponseTemplate::new(500)).expect(0).mount(&server).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.periodic_sync = false;
+        config.watch_files = false;
+        config.bulk_max_files = 2;
+        sync_github_to_local(&config, None).await.unwrap();
+        let paths: Vec<PathBuf> = (1..=3).map(|n| dir.path().join(format!("issue-{}.md", n))).collect();
+        for path in &paths {
+            fs::write(path, fs::read_to_string(path).unwrap().replace("Body", "Mangled")).unwrap();
+        }
+
+        // The whole batch is refused; a later save of one file goes through
+        let service = SyncService::start(Arc::new(config)).unwrap();
+        service.send(Command::PushFiles(paths.clone())).await.unwrap();
+        fs::write(&paths[0], fs::read_to_string(&paths[0]).unwrap().replace("Mangled", "Fixed")).unwrap();
+        service.send(Command::PushFiles(vec![paths[0].clone()])).await.unwrap();
+        service.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::frontmatter::parse_bool;
+use crate::{format_list, format_scalar, parse_list, unquote_scalar};
+
+/// Where an issue's frontmatter is kept.
+///
+/// `Inline` is the usual frontmatter block at the top of `issue-N.md`.
+/// `Sidecar` leaves only the body in `issue-N.md` and moves the metadata
+/// to `issue-N.json` next to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Metadata {
+    Inline,
+    Sidecar,
+}
+
+// The order render_issue_file writes keys in; other keys follow sorted
+const KEY_ORDER: [&str; 16] = [
+    "number",
+    "title",
+    "state",
+    "state_reason",
+    "labels",
+    "assignees",
+    "html_url",
+    "node_id",
+    "locked",
+    "comments",
+    "participants",
+    "attachments",
+    "tasks",
+    "created_at",
+    "updated_at",
+    "schema_version",
+];
+const LIST_KEYS: [&str; 4] = ["labels", "assignees", "participants", "attachments"];
+const INTEGER_KEYS: [&str; 3] = ["number", "comments", "schema_version"];
+const BOOLEAN_KEYS: [&str; 1] = ["locked"];
+const MAP_KEYS: [&str; 1] = ["tasks"];
+
+impl Metadata {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "inline" => Ok(Metadata::Inline),
+            "sidecar" => Ok(Metadata::Sidecar),
+            _ => Err(anyhow::anyhow!("Invalid --metadata value: {} (expected inline or sidecar)", value)),
+        }
+    }
+
+    /// The issue file a changed path belongs to: the file itself for
+    /// markdown, the markdown file next to it for a sidecar.
+    pub fn issue_file(self, path: &Path) -> Option<PathBuf> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("md") => Some(path.to_path_buf()),
+            Some("json") if self == Metadata::Sidecar => {
+                let issue_file = path.with_extension("md");
+                issue_file.is_file().then_some(issue_file)
+            }
+            _ => None,
+        }
+    }
+}
+
+pub fn sidecar_path(issue_file: &Path) -> PathBuf {
+    issue_file.with_extension("json")
+}
+
+/// Splits a file with inline frontmatter into the body and the sidecar
+/// JSON. A file without frontmatter has no sidecar.
+pub fn split(content: &str) -> (String, Option<String>) {
+    let Some((frontmatter, body)) = content
+        .strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---\n"))
+    else {
+        return (content.to_string(), None);
+    };
+
+    let fields: Vec<String> = frontmatter
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| {
+            let (key, value) = (key.trim(), value.trim());
+            let value = if LIST_KEYS.contains(&key) {
+                parse_list(value).map(Value::from).unwrap_or_else(|_| Value::from(value))
+            } else if INTEGER_KEYS.contains(&key) {
+                value.parse::<i64>().map(Value::from).unwrap_or_else(|_| Value::from(value))
+            } else if BOOLEAN_KEYS.contains(&key) {
+                parse_bool(value).map(Value::from).unwrap_or_else(|| Value::from(value))
+            } else if MAP_KEYS.contains(&key) {
+                serde_yaml::from_str::<Value>(value).ok().filter(Value::is_object).unwrap_or_else(|| Value::from(value))
+            } else {
+                Value::from(unquote_scalar(value))
+            };
+            format!("  {}: {}", Value::from(key), value)
+        })
+        .collect();
+
+    // Written by hand to keep the keys in frontmatter order
+    let json = format!("{{\n{}\n}}\n", fields.join(",\n"));
+    (body.trim_start_matches('\n').to_string(), Some(json))
+}
+
+/// Joins a body and its sidecar JSON back into a file with inline
+/// frontmatter, the form everything else reads.
+pub fn join(body: &str, json: &str) -> Result<String> {
+    let fields: Map<String, Value> = serde_json::from_str(json).context("Sidecar is not a JSON object")?;
+
+    let mut keys: Vec<&String> = fields.keys().collect();
+    keys.sort_by_key(|key| (KEY_ORDER.iter().position(|k| k == key).unwrap_or(KEY_ORDER.len()), key.as_str()));
+
+    let mut content = String::from("---\n");
+    for key in keys {
+        let value = match &fields[key] {
+            Value::String(value) => format_scalar(value),
+            Value::Array(items) => format_list(
+                &items
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_string).unwrap_or_else(|| item.to_string()))
+                    .collect::<Vec<_>>(),
+            ),
+            // Flow style, as render_issue_file writes `tasks`
+            Value::Object(map) => {
+                let entries: Vec<String> = map.iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+            Value::Null => String::new(),
+            value => value.to_string(),
+        };
+        content.push_str(&format!("{}: {}\n", key, value));
+    }
+    content.push_str("---\n\n");
+    content.push_str(body);
+    Ok(content)
+}
+
+/// Reads an issue file, joining in its sidecar if there is one.
+pub fn read(path: &Path, body: String) -> io::Result<String> {
+    let json = match fs::read_to_string(sidecar_path(path)) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(body),
+        Err(e) => return Err(e),
+    };
+    join(&body, &json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {:#}", sidecar_path(path).display(), e)))
+}
+
+/// When an issue was last edited locally: the later of the file and its
+/// sidecar, since either may hold the edit.
+pub fn modified(metadata: Metadata, path: &Path) -> io::Result<DateTime<Utc>> {
+    let modified: DateTime<Utc> = fs::metadata(path)?.modified()?.into();
+    if metadata == Metadata::Inline {
+        return Ok(modified);
+    }
+    match fs::metadata(sidecar_path(path)).and_then(|sidecar| sidecar.modified()) {
+        Ok(sidecar_modified) => Ok(modified.max(sidecar_modified.into())),
+        Err(_) => Ok(modified),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_join_round_trip() {
+        let content = "---\nnumber: 3\ntitle: \" Padded\"\nstate: closed\nstate_reason: not_planned\nlabels: [bug, \"a, b\"]\nassignees: []\nhtml_url: https://github.com/owner/repo/issues/3\nschema_version: 1\n---\n\nBody\n\nMore";
+        let (body, json) = split(content);
+        let json = json.unwrap();
+        assert_eq!(body, "Body\n\nMore");
+
+        let fields: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(fields["number"], 3);
+        assert_eq!(fields["title"], " Padded");
+        assert_eq!(fields["labels"], serde_json::json!(["bug", "a, b"]));
+        assert!(json.starts_with("{\n  \"number\": 3,\n  \"title\""));
+
+        assert_eq!(join(&body, &json).unwrap(), content);
+        assert_eq!(split("Just a body"), ("Just a body".to_string(), None));
+        assert!(join("Body", "[1]").is_err());
+    }
+}
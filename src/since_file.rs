@@ -0,0 +1,74 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::write_atomically;
+
+/// The cursor kept in a `--since-file`: an RFC 3339 timestamp on one line.
+/// A missing or empty file has no cursor, so the pull fetches every issue.
+pub fn read(path: &Path) -> Result<Option<DateTime<Utc>>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context(format!("Failed to read --since-file {}", path.display())),
+    };
+    let value = content.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|since| Some(since.with_timezone(&Utc)))
+        .context(format!("Invalid timestamp in --since-file {}: {:?} (expected e.g. 2024-01-31T12:00:00Z)", path.display(), value))
+}
+
+/// Records the start of a successful pull as the new cursor.
+pub fn write(path: &Path, at: DateTime<Utc>) -> Result<()> {
+    let content = format!("{}\n", at.to_rfc3339_opts(SecondsFormat::Secs, true));
+    write_atomically(path, content.as_bytes()).context(format!("Failed to write --since-file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use crate::{startup_since, sync_github_to_local};
+    use chrono::SubsecRound;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_since_file_drives_the_cursor_instead_of_the_state_file() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([issue_json(1, "Issue", "open", &[], "Body")])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cursor_dir = tempfile::tempdir().unwrap();
+        let since_file = cursor_dir.path().join("cursor.txt");
+        let mut config = test_config(&server, dir.path());
+        config.since_file = Some(since_file.clone());
+
+        // No file yet: a full pull, after which the file holds its start
+        assert_eq!(startup_since(&config).unwrap(), None);
+        sync_github_to_local(&config, startup_since(&config).unwrap()).await.unwrap();
+        let since = read(&since_file).unwrap().expect("the pull should write the cursor");
+        assert_eq!(since, config.state.get().last_pull_at.unwrap().trunc_subsecs(0));
+
+        // The orchestrator's cursor wins over the state file's
+        fs::write(&since_file, "2024-01-31T12:00:00+01:00\n").unwrap();
+        let since = startup_since(&config).unwrap().unwrap();
+        assert_eq!(since.to_rfc3339(), "2024-01-31T11:00:00+00:00");
+        sync_github_to_local(&config, Some(since)).await.unwrap();
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests[1].url.query().unwrap().contains("since=2024-01-31T11"));
+
+        fs::write(&since_file, "yesterday").unwrap();
+        assert!(startup_since(&config).unwrap_err().to_string().contains("Invalid timestamp in --since-file"));
+    }
+}
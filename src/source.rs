@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::{format, write_atomically, Config};
+
+/// Describes where the issue files of a directory came from, so a copy
+/// handed to someone else says how to sync it again.
+pub const SOURCE_FILE: &str = ".retasks-source.json";
+
+/// The repository, API, filters and time of a pull, and the command that
+/// repeats it.
+pub fn describe(config: &Config, synced_at: DateTime<Utc>) -> serde_json::Value {
+    let repo = format!("{}/{}", config.repo_owner, config.repo_name);
+    let mut command = vec!["retasks".to_string(), "--repo".to_string(), repo.clone()];
+    if config.github.api_url() != octorust::FALLBACK_HOST {
+        command.extend(["--api-url".to_string(), config.github.api_url().to_string()]);
+    }
+    let format = match config.format {
+        format::Format::Markdown => None,
+        format::Format::Json => Some("json".to_string()),
+        format::Format::Yaml => Some("yaml".to_string()),
+    };
+    let flags = [
+        ("--format", format),
+        ("--only", config.only.map(|n| n.to_string())),
+        ("--assignee", config.assignee.clone()),
+        ("--creator", config.creator.clone()),
+        ("--mentioned", config.mentioned.clone()),
+        ("--number-min", config.number_min.map(|n| n.to_string())),
+        ("--number-max", config.number_max.map(|n| n.to_string())),
+    ];
+    for (flag, value) in flags {
+        if let Some(value) = value {
+            command.extend([flag.to_string(), value]);
+        }
+    }
+
+    serde_json::json!({
+        "repo": repo,
+        "owner": config.repo_owner,
+        "name": config.repo_name,
+        "api_url": config.github.api_url(),
+        "synced_at": synced_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        "tool": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "format": config.format,
+        "filters": {
+            "only": config.only,
+            "assignee": config.assignee,
+            "creator": config.creator,
+            "mentioned": config.mentioned,
+            "number_min": config.number_min,
+            "number_max": config.number_max,
+        },
+        "resync": command.join(" "),
+    })
+}
+
+/// Writes the source file into `dir`.
+pub fn write(dir: &Path, source: &serde_json::Value) -> Result<()> {
+    let path = dir.join(SOURCE_FILE);
+    let content = format!("{}\n", serde_json::to_string_pretty(source)?);
+    write_atomically(&path, content.as_bytes()).context(format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_github_to_local;
+    use crate::tests::{issue_json, test_config};
+    use std::fs;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_each_pull_records_where_the_issues_came_from() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([issue_json(1, "Issue", "open", &[], "Body")])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.creator = Some("octocat".to_string());
+        config.number_min = Some(1);
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let source: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path().join(SOURCE_FILE)).unwrap()).unwrap();
+        assert_eq!(source["repo"], "owner/repo");
+        assert_eq!(source["api_url"], server.uri());
+        assert_eq!(source["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(source["format"], "markdown");
+        assert_eq!(source["filters"]["creator"], "octocat");
+        assert_eq!(source["filters"]["assignee"], serde_json::Value::Null);
+        assert_eq!(
+            source["resync"],
+            format!("retasks --repo owner/repo --api-url {} --creator octocat --number-min 1", server.uri())
+        );
+        let synced_at: DateTime<Utc> = source["synced_at"].as_str().unwrap().parse().unwrap();
+        assert!(config.state.get().last_pull_at.unwrap() - synced_at < chrono::Duration::seconds(1));
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fingerprint, fingerprints_match, issue_from_file, issue_from_remote, parse_markdown_file, push_local_file, read_issue_text, render_issue_file,
+    write_file_with_retry, Config,
+};
+
+const MANIFEST_FILE: &str = "pending.json";
+
+/// A local edit waiting for `apply`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PendingChange {
+    path: PathBuf,
+    /// Fingerprint of the file when it was staged, so `apply` only pushes
+    /// what was reviewed.
+    fingerprint: String,
+    staged_at: DateTime<Utc>,
+}
+
+type Manifest = BTreeMap<i64, PendingChange>;
+
+/// Records a local edit in the stage directory instead of pushing it: an
+/// entry in `pending.json` and an `issue-N.diff` against the issue as it is
+/// on GitHub. Staging the same issue again replaces its entry.
+pub async fn stage_change(
+    config: &Config,
+    stage_dir: &Path,
+    number: i64,
+    path: &Path,
+    content: &str,
+    local_fingerprint: &str,
+) -> Result<()> {
+    let remote = config
+        .github
+        .with_rotation(|client| async move { client.issues().get(&config.repo_owner, &config.repo_name, number).await })
+        .await
+        .context(format!("Failed to fetch issue #{} from GitHub", number))?
+        .body;
+    let remote_content = render_issue_file(&issue_from_remote(config, remote));
+
+    fs::create_dir_all(stage_dir).context(format!("Failed to create {}", stage_dir.display()))?;
+    let diff_path = stage_dir.join(format!("issue-{}.diff", number));
+    let diff = format!(
+        "--- github #{}\n+++ {}\n{}",
+        number,
+        path.display(),
+        line_diff(&remote_content, content.trim_end())
+    );
+    write_file_with_retry(&diff_path, &diff, config.io_retries)
+        .context(format!("Failed to write {}", diff_path.display()))?;
+
+    let mut manifest = load_manifest(stage_dir)?;
+    manifest.insert(
+        number,
+        PendingChange {
+            path: path.to_path_buf(),
+            fingerprint: local_fingerprint.to_string(),
+            staged_at: Utc::now(),
+        },
+    );
+    save_manifest(config, stage_dir, &manifest)?;
+
+    println!("Staged issue #{} in {}, run `apply` to push it", number, diff_path.display());
+    Ok(())
+}
+
+/// Pushes every staged change, removing it from the stage directory once
+/// it's on GitHub. Files edited again since they were staged are left for
+/// the next staging round rather than pushed unreviewed.
+pub async fn apply(config: &Config, stage_dir: &Path) -> Result<Vec<i64>> {
+    let mut manifest = load_manifest(stage_dir)?;
+    if manifest.is_empty() {
+        println!("No staged changes in {}", stage_dir.display());
+        return Ok(Vec::new());
+    }
+
+    let mut applied = Vec::new();
+    let mut failures = Vec::new();
+    for (number, change) in manifest.clone() {
+        let current = match current_fingerprint(config, &change.path) {
+            Ok(current) => current,
+            Err(e) => {
+                eprintln!("{}", config.colors.error(&format!("Error applying issue #{}: {:#}", number, e)));
+                failures.push(number);
+                continue;
+            }
+        };
+        if !fingerprints_match(config, &current, &change.fingerprint) {
+            eprintln!(
+                "{}",
+                config.colors.warning(&format!(
+                    "Warning: {} changed since it was staged, not applying issue #{}. Save it again to re-stage it.",
+                    change.path.display(),
+                    number
+                ))
+            );
+            continue;
+        }
+
+        match push_local_file(config, &change.path, None).await {
+            Ok(_) => {
+                if !config.dry_run {
+                    manifest.remove(&number);
+                    let _ = fs::remove_file(stage_dir.join(format!("issue-{}.diff", number)));
+                    applied.push(number);
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", config.colors.error(&format!("Error applying issue #{}: {:#}", number, e)));
+                failures.push(number);
+            }
+        }
+    }
+
+    if !config.dry_run {
+        save_manifest(config, stage_dir, &manifest)?;
+    }
+    if !failures.is_empty() {
+        return Err(anyhow::anyhow!("Failed to apply {} staged change(s): {:?}", failures.len(), failures));
+    }
+    Ok(applied)
+}
+
+fn current_fingerprint(config: &Config, path: &Path) -> Result<String> {
+    let content = read_issue_text(config, path).context(format!("Failed to read {}", path.display()))?;
+    let (mut frontmatter, body) = parse_markdown_file(&content)?;
+    config.state_map.apply(&mut frontmatter)?;
+    let number = frontmatter
+        .get("number")
+        .and_then(|n| n.parse::<i64>().ok())
+        .ok_or_else(|| anyhow::anyhow!("Could not determine issue number in {}", path.display()))?;
+    Ok(fingerprint(config, &issue_from_file(number, &frontmatter, body)?))
+}
+
+fn load_manifest(stage_dir: &Path) -> Result<Manifest> {
+    let path = stage_dir.join(MANIFEST_FILE);
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).context(format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::new()),
+        Err(e) => Err(e).context(format!("Failed to read {}", path.display())),
+    }
+}
+
+fn save_manifest(config: &Config, stage_dir: &Path, manifest: &Manifest) -> Result<()> {
+    let path = stage_dir.join(MANIFEST_FILE);
+    write_file_with_retry(&path, &serde_json::to_string_pretty(manifest)?, config.io_retries)
+        .context(format!("Failed to write {}", path.display()))
+}
+
+// Whole-file line diff (issue files are small, so no hunks), with ` `, `-`
+// and `+` prefixes like a unified diff
+fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lengths[i][j] is the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push_str(&format!(" {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            diff.push_str(&format!("-{}\n", old[i]));
+            i += 1;
+        } else {
+            diff.push_str(&format!("+{}\n", new[j]));
+            j += 1;
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_local_to_github;
+    use crate::tests::{issue_json, test_config};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_staged_change_is_pushed_only_on_apply() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Old", "open", &[], "Body")))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "New", "open", &[], "Body")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let stage_dir = dir.path().join("staged");
+        let mut config = test_config(&server, dir.path());
+        config.stage_dir = Some(stage_dir.clone());
+
+        let file_path = dir.path().join("issue-1.md");
+        fs::write(&file_path, "---\nnumber: 1\ntitle: New\nstate: open\n---\n\nBody").unwrap();
+        assert_eq!(sync_local_to_github(&config, &file_path).await.unwrap(), None);
+
+        let diff = fs::read_to_string(stage_dir.join("issue-1.diff")).unwrap();
+        assert!(diff.contains("-title: Old\n+title: New\n"));
+
+        assert_eq!(apply(&config, &stage_dir).await.unwrap(), vec![1]);
+        assert!(load_manifest(&stage_dir).unwrap().is_empty());
+        assert!(!stage_dir.join("issue-1.diff").exists());
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const STATE_FILE: &str = ".retasks-state.json";
+
+/// Sync cursor persisted between runs, so a restarted watcher can catch up
+/// instead of doing a blind full resync.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SyncState {
+    /// Start time of the last successful pull, used as `since` for the
+    /// catch-up pull on the next start.
+    pub last_pull_at: Option<DateTime<Utc>>,
+    /// When local changes were last known to be in sync: the end of the last
+    /// pull or the last pushed file event. Files modified after this were
+    /// edited while retasks wasn't running.
+    pub last_event_at: Option<DateTime<Utc>>,
+    /// Fingerprint of each issue's pushable fields as last pulled or pushed.
+    #[serde(default)]
+    pub fingerprints: BTreeMap<i64, String>,
+    /// The same for each milestone file, with `--sync-milestones`.
+    #[serde(default)]
+    pub milestone_fingerprints: BTreeMap<i64, String>,
+    /// Issues `new` is creating, by the id embedded in their body, with
+    /// their title. An entry outlives a crash between creating the issue
+    /// and writing its file, so the retry adopts the issue instead of
+    /// creating it twice.
+    #[serde(default)]
+    pub pending_creates: BTreeMap<String, String>,
+    /// Files whose push failed because GitHub was unreachable, by issue
+    /// number, with `--offline-fallback`. Retried before the next pull.
+    #[serde(default)]
+    pub queued_pushes: BTreeMap<i64, PathBuf>,
+}
+
+/// The state file in the issues directory, guarded by a mutex so the
+/// periodic pull and the watcher don't interleave their read-modify-write.
+pub struct StateStore {
+    path: PathBuf,
+    state: Mutex<SyncState>,
+}
+
+impl StateStore {
+    pub fn load(issues_dir: &Path) -> Result<Self> {
+        let path = issues_dir.join(STATE_FILE);
+        let state = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .context(format!("Failed to parse state file: {}", path.display()))?,
+            Err(_) => SyncState::default(),
+        };
+
+        Ok(StateStore {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn get(&self) -> SyncState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Applies `change` and writes the result back to disk.
+    pub fn update(&self, change: impl FnOnce(&mut SyncState)) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        change(&mut state);
+
+        let content = serde_json::to_string_pretty(&*state)?;
+        crate::write_atomically(&self.path, content.as_bytes())
+            .context(format!("Failed to write state file: {}", self.path.display()))
+    }
+}
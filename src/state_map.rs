@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{format_list, parse_list, parse_state, unquote_scalar, Issue};
+
+/// Custom `state` values from `--state-map`, each standing for a GitHub
+/// state plus labels, e.g. `in-progress` for an open issue labelled
+/// `in-progress`.
+#[derive(Debug, Default, Serialize)]
+pub struct StateMap(Vec<Mapping>);
+
+#[derive(Debug, Serialize)]
+struct Mapping {
+    name: String,
+    state: String,
+    labels: Vec<String>,
+}
+
+impl StateMap {
+    /// Reads `NAME=STATE:LABEL[,LABEL...]` values, e.g.
+    /// `in-progress=open:in-progress`. Earlier mappings win on pull.
+    pub fn parse<'a>(values: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let mut mappings: Vec<Mapping> = Vec::new();
+        for value in values {
+            let invalid = || anyhow::anyhow!("Invalid --state-map value: {} (expected NAME=STATE:LABEL, e.g. in-progress=open:in-progress)", value);
+            let (name, target) = value.split_once('=').ok_or_else(invalid)?;
+            let (state, labels) = target.split_once(':').ok_or_else(invalid)?;
+            let name = name.trim().to_string();
+            let state = parse_state(state).map_err(|_| invalid())?.to_string();
+            let labels: Vec<String> =
+                labels.split(',').map(|label| label.trim().to_string()).filter(|label| !label.is_empty()).collect();
+            if name.is_empty() || labels.is_empty() {
+                return Err(invalid());
+            }
+            if parse_state(&name).is_ok() {
+                return Err(anyhow::anyhow!("--state-map can't redefine the {} state", name));
+            }
+            if mappings.iter().any(|mapping| mapping.name.eq_ignore_ascii_case(&name)) {
+                return Err(anyhow::anyhow!("--state-map maps {} more than once", name));
+            }
+            mappings.push(Mapping { name, state, labels });
+        }
+        Ok(StateMap(mappings))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn find(&self, state: &str) -> Option<&Mapping> {
+        self.0.iter().find(|mapping| mapping.name.eq_ignore_ascii_case(state.trim()))
+    }
+
+    /// For pulls: an issue in a mapping's GitHub state with all of its
+    /// labels gets the custom state instead, and loses those labels. When
+    /// several mappings fit, the first one given wins; the labels of the
+    /// others stay in `labels`.
+    pub fn localize(&self, issues: &mut [Issue]) {
+        for issue in issues.iter_mut() {
+            let has = |issue: &Issue, label: &str| issue.labels.iter().any(|l| l.eq_ignore_ascii_case(label));
+            let Some(mapping) = self.0.iter().find(|mapping| {
+                issue.state.eq_ignore_ascii_case(&mapping.state) && mapping.labels.iter().all(|label| has(issue, label))
+            }) else {
+                continue;
+            };
+            issue.labels.retain(|l| !mapping.labels.iter().any(|label| l.eq_ignore_ascii_case(label)));
+            issue.state = mapping.name.clone();
+        }
+    }
+
+    /// For pushes: rewrites a custom `state` in the frontmatter of an issue
+    /// file to its GitHub state, adding its labels to `labels`.
+    pub fn apply(&self, frontmatter: &mut HashMap<String, String>) -> Result<()> {
+        let Some(mapping) = frontmatter.get("state").and_then(|state| self.find(&unquote_scalar(state))) else {
+            return Ok(());
+        };
+        let mut labels = match frontmatter.get("labels") {
+            Some(labels) => parse_list(labels)?,
+            None => Vec::new(),
+        };
+        for label in &mapping.labels {
+            if !labels.iter().any(|l| l.eq_ignore_ascii_case(label)) {
+                labels.push(label.clone());
+            }
+        }
+        frontmatter.insert("state".to_string(), mapping.state.clone());
+        frontmatter.insert("labels".to_string(), format_list(&labels));
+        Ok(())
+    }
+
+    /// The issue as GitHub holds it, if its state is a custom one, so
+    /// fingerprints of pulled issues match those of the files pushed.
+    pub fn to_github(&self, issue: &Issue) -> Option<Issue> {
+        let mapping = self.find(&issue.state)?;
+        let mut issue = issue.clone();
+        for label in &mapping.labels {
+            if !issue.labels.iter().any(|l| l.eq_ignore_ascii_case(label)) {
+                issue.labels.push(label.clone());
+            }
+        }
+        issue.state = mapping.state.clone();
+        Some(issue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_local_to_github;
+    use crate::tests::{issue_json, test_config};
+    use crate::{push_all_files, sync_github_to_local};
+    use std::fs;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_custom_states_map_to_labels_and_back() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Started", "open", &["bug", "in-progress"], "Body"),
+                issue_json(2, "Both", "open", &["needs-review", "in-progress"], "Body"),
+                issue_json(3, "Closed", "closed", &["in-progress"], "Body"),
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Started", "open", &["bug", "in-progress"], "Body")))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/1"))
+            .and(body_partial_json(serde_json::json!({ "state": "open", "labels": ["bug", "needs-review"] })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(1, "Started", "open", &["bug", "needs-review"], "Body")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.state_map = StateMap::parse(["in-progress=open:in-progress", "review=open:needs-review"]).unwrap();
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let read = |number: i64| fs::read_to_string(dir.path().join(format!("issue-{}.md", number))).unwrap();
+        assert!(read(1).contains("state: in-progress\nlabels: [bug]\n"), "{}", read(1));
+        // The first mapping wins; only the closed issue keeps its label
+        assert!(read(2).contains("state: in-progress\nlabels: [needs-review]\n"), "{}", read(2));
+        assert!(read(3).contains("state: closed\nlabels: [in-progress]\n"), "{}", read(3));
+
+        // Untouched files match what was pulled, so nothing is pushed
+        assert!(push_all_files(&config).await.unwrap().is_empty());
+
+        let file = dir.path().join("issue-1.md");
+        fs::write(&file, read(1).replace("state: in-progress", "state: review")).unwrap();
+        assert_eq!(sync_local_to_github(&config, &file).await.unwrap(), Some(1));
+
+        assert!(StateMap::parse(["open=open:started"]).is_err());
+        assert!(StateMap::parse(["blocked=paused:blocked"]).is_err());
+        assert!(StateMap::parse(["blocked=open:"]).is_err());
+    }
+}
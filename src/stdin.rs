@@ -0,0 +1,99 @@
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::error::SyncError;
+use crate::{create_issue, frontmatter, parse_markdown_file, push_issue_text, template, Config, SyncSummary};
+
+/// What errors about the piped issue name as its file.
+const SOURCE: &str = "<stdin>";
+
+/// Pushes the one issue read from `input` (`push -`). With a `number` in its
+/// frontmatter the issue is updated, with the same checks as a pushed file;
+/// without one it is created, and the new number is printed on a line of its
+/// own as the last line of output.
+pub async fn push(config: &Config, mut input: impl Read) -> Result<SyncSummary> {
+    let mut content = String::new();
+    input.read_to_string(&mut content).context("Failed to read the issue from stdin")?;
+    if content.trim().is_empty() {
+        return Err(anyhow::anyhow!("Nothing to push: stdin is empty, expected an issue in markdown with frontmatter"));
+    }
+
+    let source = Path::new(SOURCE);
+    let (mut raw, body) = parse_markdown_file(&content).map_err(|e| SyncError::parse(source, e))?;
+    config.state_map.apply(&mut raw).map_err(|e| SyncError::parse(source, e))?;
+    let parsed = frontmatter::Frontmatter::parse(&raw).map_err(|e| SyncError::parse(source, e))?;
+
+    let Some(issue_number) = parsed.number else {
+        let title = parsed.title.filter(|title| !title.trim().is_empty()).ok_or_else(|| {
+            SyncError::parse(source, anyhow::anyhow!("no `number` key, and no `title` to create an issue with"))
+        })?;
+        let new_issue = template::IssueTemplate {
+            title,
+            labels: parsed.labels.unwrap_or_default(),
+            assignees: parsed.assignees.unwrap_or_default(),
+            body,
+            ..Default::default()
+        };
+        let created = create_issue(config, None, Some(&new_issue)).await?;
+        if let Some(number) = created {
+            println!("{}", number);
+        }
+        return Ok(SyncSummary { created: created.into_iter().collect(), ..Default::default() });
+    };
+
+    let _lock = config.issue_locks.lock(issue_number).await;
+    let updated = push_issue_text(config, source, &content, None).await?;
+    Ok(SyncSummary { updated: updated.into_iter().collect(), ..Default::default() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_issue_from_stdin_is_updated_or_created() {
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/owner/repo/issues/4"))
+            .and(body_partial_json(serde_json::json!({ "title": "Piped", "body": "From a script" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue_json(4, "Piped", "open", &[], "From a script")))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/owner/repo/issues"))
+            .and(body_partial_json(serde_json::json!({ "title": "New", "labels": ["bug"] })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(issue_json(9, "New", "open", &["bug"], "Body")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        let input = "---\nnumber: 4\ntitle: Piped\nstate: open\n---\n\nFrom a script";
+        let summary = push(&config, input.as_bytes()).await.unwrap();
+        assert_eq!(summary.updated, vec![4]);
+        // Recorded as synced, so the same text again sends nothing
+        assert!(push(&config, input.as_bytes()).await.unwrap().updated.is_empty());
+
+        let conflicted = "---\nnumber: 4\ntitle: Piped\n---\n\n<<<<<<< HEAD\nA\n=======\nB\n>>>>>>> branch";
+        assert!(push(&config, conflicted.as_bytes()).await.unwrap_err().to_string().contains("merge conflict markers"));
+        config.readonly_closed = true;
+        let closed = "---\nnumber: 4\ntitle: Closed\nstate: closed\n---\n\nFrom a script";
+        assert!(push(&config, closed.as_bytes()).await.unwrap().updated.is_empty());
+        config.readonly_closed = false;
+
+        let input = "---\ntitle: New\nlabels: [bug]\n---\n\nBody";
+        let summary = push(&config, input.as_bytes()).await.unwrap();
+        assert_eq!(summary.created, vec![9]);
+        assert!(dir.path().join("issue-9.md").exists());
+
+        let error = push(&config, "  \n".as_bytes()).await.unwrap_err();
+        assert!(error.to_string().contains("stdin is empty"));
+    }
+}
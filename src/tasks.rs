@@ -0,0 +1,97 @@
+use std::fmt;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::Issue;
+
+// `- [ ] item`, `* [x] item`, `1. [X] item`, indented or not
+const TASK_PATTERN: &str = r"^\s*(?:[-*+]|\d+[.)])\s+\[([ xX])\](?:\s|$)";
+
+/// How many items of the task lists in a body are checked, out of all of
+/// them. Written to the frontmatter as `tasks: {done: 3, total: 7}`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Tasks {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl fmt::Display for Tasks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{done: {}, total: {}}}", self.done, self.total)
+    }
+}
+
+/// Counts the task list items in `body`. Items inside fenced code blocks
+/// are examples, not tasks, so they are skipped.
+pub fn count(body: &str) -> Tasks {
+    let pattern = Regex::new(TASK_PATTERN).unwrap();
+    let mut tasks = Tasks::default();
+    let mut in_code = false;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        if let Some(captures) = pattern.captures(line) {
+            tasks.total += 1;
+            if &captures[1] != " " {
+                tasks.done += 1;
+            }
+        }
+    }
+    tasks
+}
+
+/// Sets `tasks` on each issue from its body.
+pub fn fill(issues: &mut [Issue]) {
+    for issue in issues.iter_mut() {
+        issue.tasks = Some(count(issue.body.as_deref().unwrap_or_default()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use crate::{parse_markdown_file, sync_github_to_local};
+    use std::fs;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_task_list_items_are_counted_outside_code() {
+        let body = "Plan:\n- [x] Design\n- [ ] Build\n  * [X] Nested\n1. [ ] Numbered\n- [link](url)\n- [ ]not a task\n\n```\n- [x] Example\n```";
+        assert_eq!(count(body), Tasks { done: 2, total: 4 });
+        assert_eq!(count("No tasks"), Tasks::default());
+    }
+
+    #[tokio::test]
+    async fn test_track_tasks_writes_the_summary_on_pull() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                issue_json(1, "Epic", "open", &[], "- [x] One\n- [ ] Two\n- [x] Three"),
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        config.track_tasks = true;
+        sync_github_to_local(&config, None).await.unwrap();
+
+        let (frontmatter, _) = parse_markdown_file(&fs::read_to_string(dir.path().join("issue-1.md")).unwrap()).unwrap();
+        assert_eq!(frontmatter["tasks"], "{done: 2, total: 3}");
+
+        // Sidecars keep it as an object and give it back the same way
+        let (body, json) = crate::sidecar::split("---\nnumber: 1\ntasks: {done: 2, total: 3}\n---\n\nBody");
+        assert!(json.as_deref().unwrap().contains("\"tasks\": {\"done\":2,\"total\":3}"));
+        assert_eq!(crate::sidecar::join(&body, &json.unwrap()).unwrap(), "---\nnumber: 1\ntasks: {done: 2, total: 3}\n---\n\nBody");
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use serde::Deserialize;
+
+use crate::{parse_markdown_file, Config};
+
+const TEMPLATE_DIR: &str = ".github/ISSUE_TEMPLATE";
+
+/// A markdown issue template from the repository's `.github/ISSUE_TEMPLATE`.
+#[derive(Debug, Default, PartialEq)]
+pub struct IssueTemplate {
+    /// The file name without `.md`, which is what `--template` matches.
+    pub file_stem: String,
+    pub name: String,
+    pub about: String,
+    pub title: String,
+    pub labels: Vec<String>,
+    pub assignees: Vec<String>,
+    pub body: String,
+}
+
+// GitHub accepts both `labels: [a, b]` and `labels: "a, b"` in templates
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NameList {
+    List(Vec<String>),
+    Text(String),
+}
+
+impl NameList {
+    fn into_vec(self) -> Vec<String> {
+        let names = match self {
+            NameList::List(names) => names,
+            NameList::Text(text) => text.split(',').map(str::to_string).collect(),
+        };
+        names.into_iter().map(|n| n.trim().to_string()).filter(|n| !n.is_empty()).collect()
+    }
+}
+
+/// Lists the markdown templates in the repository. Issue forms (`.yml`) and
+/// `config.yml` are skipped, since they don't map onto an issue file.
+pub async fn list_templates(config: &Config) -> Result<Vec<IssueTemplate>> {
+    let entries = config
+        .github
+        .with_rotation(|client| async move {
+            client.repos().get_content_vec_entries(&config.repo_owner, &config.repo_name, TEMPLATE_DIR, "").await
+        })
+        .await
+        .context(format!("Failed to list issue templates in {}", TEMPLATE_DIR))?
+        .body;
+
+    let mut templates = Vec::new();
+    for entry in entries.iter().filter(|e| e.type_ == "file" && e.name.ends_with(".md")) {
+        templates.push(fetch_template(config, &entry.name).await?);
+    }
+    Ok(templates)
+}
+
+/// Finds a template by file name (`bug_report`, with or without `.md`) or by
+/// its `name:` key, ignoring case.
+pub async fn find_template(config: &Config, name: &str) -> Result<IssueTemplate> {
+    let wanted = name.trim_end_matches(".md");
+    let templates = list_templates(config).await?;
+    let available: Vec<String> = templates.iter().map(|t| t.file_stem.clone()).collect();
+
+    templates
+        .into_iter()
+        .find(|t| t.file_stem.eq_ignore_ascii_case(wanted) || t.name.eq_ignore_ascii_case(wanted))
+        .ok_or_else(|| {
+            anyhow::anyhow!("No issue template named {} (available: {})", name, available.join(", "))
+        })
+}
+
+/// Prints the templates for `new --template` given without a name.
+pub fn print_templates(templates: &[IssueTemplate]) {
+    if templates.is_empty() {
+        println!("No markdown issue templates in {}", TEMPLATE_DIR);
+        return;
+    }
+    for template in templates {
+        if template.about.is_empty() {
+            println!("{}: {}", template.file_stem, template.name);
+        } else {
+            println!("{}: {} - {}", template.file_stem, template.name, template.about);
+        }
+    }
+}
+
+async fn fetch_template(config: &Config, file_name: &str) -> Result<IssueTemplate> {
+    let path = format!("{}/{}", TEMPLATE_DIR, file_name);
+    let file = config
+        .github
+        .with_rotation(|client| {
+            let path = path.clone();
+            async move { client.repos().get_content_file(&config.repo_owner, &config.repo_name, &path, "").await }
+        })
+        .await
+        .context(format!("Failed to fetch issue template {}", path))?
+        .body;
+
+    // The contents API returns base64 wrapped at 60 columns
+    let encoded: String = file.content.split_whitespace().collect();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .context(format!("Failed to decode issue template {}", path))?;
+    let content = String::from_utf8(bytes).context(format!("Issue template {} is not UTF-8", path))?;
+
+    parse_template(file_name.trim_end_matches(".md"), &content).context(format!("Invalid issue template {}", path))
+}
+
+fn parse_template(file_stem: &str, content: &str) -> Result<IssueTemplate> {
+    let (frontmatter, body) = parse_markdown_file(content)?;
+    let scalar = |key: &str| -> Result<String> {
+        match frontmatter.get(key) {
+            Some(value) => Ok(serde_yaml::from_str::<Option<String>>(value)?.unwrap_or_default()),
+            None => Ok(String::new()),
+        }
+    };
+    let names = |key: &str| -> Result<Vec<String>> {
+        match frontmatter.get(key) {
+            Some(value) => Ok(serde_yaml::from_str::<Option<NameList>>(value)?.map(NameList::into_vec).unwrap_or_default()),
+            None => Ok(Vec::new()),
+        }
+    };
+
+    let name = scalar("name")?;
+    Ok(IssueTemplate {
+        file_stem: file_stem.to_string(),
+        name: if name.is_empty() { file_stem.to_string() } else { name },
+        about: scalar("about")?,
+        title: scalar("title")?,
+        labels: names("labels")?,
+        assignees: names("assignees")?,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template_reads_both_label_styles() {
+        let content = "---\nname: Bug report\nabout: Something is broken\ntitle: \"[BUG] \"\nlabels: bug, triage\nassignees: [octocat]\n---\n\n**Describe the bug**\n";
+        let template = parse_template("bug_report", content).unwrap();
+
+        assert_eq!(template.name, "Bug report");
+        assert_eq!(template.title, "[BUG] ");
+        assert_eq!(template.labels, vec!["bug".to_string(), "triage".to_string()]);
+        assert_eq!(template.assignees, vec!["octocat".to_string()]);
+        assert_eq!(template.body, "**Describe the bug**");
+
+        let bare = parse_template("feature", "---\nlabels: ''\n---\nIdea").unwrap();
+        assert_eq!(bare.name, "feature");
+        assert!(bare.labels.is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::{Config, Issue};
+
+/// Starts the timeline appendix. Everything from here to the end of the
+/// body is written by a pull and dropped before a push.
+const MARKER: &str = "<!-- timeline -->";
+
+/// Appends each issue's event history to its body. An issue whose timeline
+/// can't be fetched is written without one, with a warning.
+pub async fn append(config: &Config, issues: &mut [Issue]) {
+    for issue in issues.iter_mut() {
+        let events = match config.github.timeline(&config.repo_owner, &config.repo_name, issue.number).await {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    config.colors.warning(&format!("Warning: failed to fetch the timeline of issue #{}: {:#}", issue.number, e))
+                );
+                continue;
+            }
+        };
+
+        let lines: Vec<String> = events.iter().filter_map(describe).collect();
+        if lines.is_empty() {
+            continue;
+        }
+        let section = format!("{}\n## Timeline\n\n{}", MARKER, lines.join("\n"));
+        issue.body = Some(match issue.body.as_deref() {
+            Some(body) if !body.is_empty() => format!("{}\n\n{}", body, section),
+            _ => section,
+        });
+    }
+}
+
+/// Removes the appendix written by [`append`], leaving the body as it is
+/// on GitHub.
+pub fn strip(body: &str) -> String {
+    match body.rfind(MARKER) {
+        Some(0) => String::new(),
+        Some(index) if body[..index].ends_with("\n\n") => body[..index - 2].to_string(),
+        _ => body.to_string(),
+    }
+}
+
+// One list item per event, or None for events without a time
+fn describe(event: &Value) -> Option<String> {
+    let kind = event["event"].as_str()?;
+    let at = event["created_at"]
+        .as_str()
+        .or_else(|| event["submitted_at"].as_str())
+        .and_then(|at| at.parse::<DateTime<Utc>>().ok())?;
+    let actor = event["actor"]["login"].as_str().or_else(|| event["user"]["login"].as_str()).unwrap_or("ghost");
+
+    let what = match kind {
+        "labeled" => format!("added the `{}` label", event["label"]["name"].as_str().unwrap_or_default()),
+        "unlabeled" => format!("removed the `{}` label", event["label"]["name"].as_str().unwrap_or_default()),
+        "assigned" => format!("assigned @{}", event["assignee"]["login"].as_str().unwrap_or_default()),
+        "unassigned" => format!("unassigned @{}", event["assignee"]["login"].as_str().unwrap_or_default()),
+        "milestoned" => format!("added this to the {} milestone", event["milestone"]["title"].as_str().unwrap_or_default()),
+        "demilestoned" => format!("removed this from the {} milestone", event["milestone"]["title"].as_str().unwrap_or_default()),
+        "renamed" => format!(
+            "changed the title from {:?} to {:?}",
+            event["rename"]["from"].as_str().unwrap_or_default(),
+            event["rename"]["to"].as_str().unwrap_or_default()
+        ),
+        "closed" => match event["state_reason"].as_str() {
+            Some("not_planned") => "closed this as not planned".to_string(),
+            _ => "closed this".to_string(),
+        },
+        "reopened" => "reopened this".to_string(),
+        "referenced" => match event["commit_id"].as_str() {
+            Some(commit) => format!("referenced this in commit {}", &commit[..commit.len().min(7)]),
+            None => "referenced this".to_string(),
+        },
+        "cross-referenced" => {
+            let source = &event["source"]["issue"];
+            match (source["repository"]["full_name"].as_str(), source["number"].as_i64()) {
+                (Some(repo), Some(number)) => format!("mentioned this in {}#{}", repo, number),
+                _ => "mentioned this".to_string(),
+            }
+        }
+        "commented" => "commented".to_string(),
+        other => other.replace('_', " "),
+    };
+    Some(format!("- {} @{} {}", at.format("%Y-%m-%d %H:%M UTC"), actor, what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{issue_json, test_config};
+    use crate::{issue_from_remote, types};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_timeline_is_appended_and_stripped() {
+        let server = MockServer::start().await;
+        let events = serde_json::json!([
+            { "event": "labeled", "actor": { "login": "alice" }, "created_at": "2024-01-02T10:00:00Z", "label": { "name": "bug" } },
+            { "event": "commented", "user": { "login": "bob" }, "created_at": "2024-01-03T11:30:00Z" },
+            { "event": "committed", "sha": "abc" },
+            { "event": "closed", "actor": { "login": "alice" }, "created_at": "2024-01-04T09:15:00Z", "state_reason": "not_planned" },
+        ]);
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/issues/3/timeline"))
+            .and(query_param("page", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(events))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&server, dir.path());
+        let remote: types::Issue = serde_json::from_value(issue_json(3, "Crash", "closed", &[], "Steps")).unwrap();
+        let mut issues = vec![issue_from_remote(&config, remote)];
+        append(&config, &mut issues).await;
+
+        let body = issues[0].body.clone().unwrap();
+        assert_eq!(
+            body,
+            "Steps\n\n<!-- timeline -->\n## Timeline\n\n- 2024-01-02 10:00 UTC @alice added the `bug` label\n- 2024-01-03 11:30 UTC @bob commented\n- 2024-01-04 09:15 UTC @alice closed this as not planned"
+        );
+        assert_eq!(strip(&body), "Steps");
+        assert_eq!(strip("No appendix"), "No appendix");
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt;
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local, Utc};
+
+/// The zone `--timezone` renders frontmatter timestamps in. Each timestamp
+/// keeps its offset, so it still names the same instant as GitHub's UTC one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timezone {
+    Utc,
+    /// The system's zone, which honors the `TZ` variable, e.g.
+    /// `TZ=Europe/Berlin`.
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl Timezone {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "UTC" | "utc" | "Z" => Ok(Timezone::Utc),
+            "local" => Ok(Timezone::Local),
+            _ => value.parse().map(Timezone::Fixed).map_err(|_| {
+                anyhow::anyhow!(
+                    "Invalid --timezone value: {} (expected UTC, local or an offset like +02:00; for a named zone, set TZ and pass local)",
+                    value
+                )
+            }),
+        }
+    }
+
+    /// `at` in this zone, with the offset that applied at that instant.
+    pub fn convert(self, at: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Timezone::Utc => at.fixed_offset(),
+            Timezone::Local => at.with_timezone(&Local).fixed_offset(),
+            Timezone::Fixed(offset) => at.with_timezone(&offset),
+        }
+    }
+}
+
+impl fmt::Display for Timezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timezone::Utc => write!(f, "UTC"),
+            Timezone::Local => write!(f, "local"),
+            Timezone::Fixed(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::{resolve_difference, Change};
+    use crate::state::SyncState;
+    use crate::tests::{issue_json, test_config};
+    use crate::{issue_from_file, issue_from_remote, parse_markdown_file, render_issue_file};
+    use chrono::Duration;
+    use wiremock::MockServer;
+
+    #[tokio::test]
+    async fn test_timestamps_render_in_the_zone_and_still_detect_conflicts() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&server, dir.path());
+        let remote: octorust::types::Issue = serde_json::from_value(issue_json(1, "Issue", "open", &[], "Body")).unwrap();
+        let updated = remote.updated_at.unwrap();
+
+        config.timezone = Timezone::parse("+05:30").unwrap();
+        let content = render_issue_file(&issue_from_remote(&config, remote.clone()));
+        assert!(content.contains("updated_at: 2020-01-01T05:30:00+05:30\n"), "{}", content);
+
+        // Read back, it is the same instant as GitHub's UTC timestamp, so
+        // the sync cursor compares against it as before
+        let (frontmatter, body) = parse_markdown_file(&content).unwrap();
+        let local = issue_from_file(1, &frontmatter, body).unwrap().updated_at;
+        assert_eq!(local, Some(updated.fixed_offset()));
+        let edited = updated + Duration::hours(2);
+        let state = SyncState {
+            last_pull_at: Some(updated - Duration::minutes(1)),
+            last_event_at: Some(updated + Duration::hours(1)),
+            ..SyncState::default()
+        };
+        assert_eq!(resolve_difference(&state, edited, local), Change::Conflict);
+        let state = SyncState { last_pull_at: Some(updated + Duration::minutes(1)), ..state };
+        assert_eq!(resolve_difference(&state, edited, local), Change::Push);
+
+        config.timezone = Timezone::Utc;
+        assert!(render_issue_file(&issue_from_remote(&config, remote)).contains("updated_at: 2020-01-01T00:00:00Z\n"));
+        // The forms the README lists
+        for value in ["UTC", "utc", "Z", "local", "+02:00", "-05:30", "+0200"] {
+            assert!(Timezone::parse(value).is_ok(), "{}", value);
+        }
+        assert!(Timezone::parse("Europe/Berlin").is_err());
+    }
+}
@@ -0,0 +1,220 @@
+/// Hard-wraps a body to `width` columns for the local file.
+///
+/// Only paragraphs and list items that are a single long line are wrapped:
+/// GitHub renders every newline in an issue as a line break, so blocks that
+/// already span several lines are the author's own line breaks and are kept.
+/// Code, tables, headings, quotes and HTML are never touched, and links and
+/// `inline code` are never split.
+pub fn wrap(body: &str, width: usize) -> String {
+    let lines: Vec<&str> = body.split('\n').collect();
+    let mut out = Vec::new();
+    let mut in_fence = false;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if is_fence(line) {
+            in_fence = !in_fence;
+        }
+        if in_fence || is_fence(line) {
+            out.push(line.to_string());
+            i += 1;
+            continue;
+        }
+
+        let block_end = block_end(&lines, i);
+        if block_end == i + 1 && line.chars().count() > width {
+            if let Some(wrapped) = wrap_line(line, width) {
+                out.extend(wrapped);
+                i += 1;
+                continue;
+            }
+        }
+        out.extend(lines[i..block_end.max(i + 1)].iter().map(|line| line.to_string()));
+        i = block_end.max(i + 1);
+    }
+    out.join("\n")
+}
+
+/// Undoes [`wrap`] before a push: a paragraph or list item is joined back
+/// into one line when its lines are exactly what `wrap` makes of that line,
+/// so that a round trip never changes the body on GitHub.
+pub fn unwrap(body: &str, width: usize) -> String {
+    let lines: Vec<&str> = body.split('\n').collect();
+    let mut out = Vec::new();
+    let mut in_fence = false;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if is_fence(line) {
+            in_fence = !in_fence;
+        }
+        if in_fence || is_fence(line) {
+            out.push(line.to_string());
+            i += 1;
+            continue;
+        }
+
+        let block_end = block_end(&lines, i);
+        if block_end > i + 1 {
+            let block = &lines[i..block_end];
+            let indent = " ".repeat(list_marker_width(line).unwrap_or(0));
+            let joined = block
+                .iter()
+                .enumerate()
+                .map(|(n, line)| if n == 0 { *line } else { line.strip_prefix(indent.as_str()).unwrap_or(line) })
+                .collect::<Vec<_>>()
+                .join(" ");
+            if wrap_line(&joined, width).is_some_and(|wrapped| wrapped == block) {
+                out.push(joined);
+                i = block_end;
+                continue;
+            }
+        }
+        out.extend(lines[i..block_end.max(i + 1)].iter().map(|line| line.to_string()));
+        i = block_end.max(i + 1);
+    }
+    out.join("\n")
+}
+
+// The end of the wrappable block starting at `start`: a run of paragraph
+// lines, or a list item with its indented continuation lines. Returns
+// `start` itself for lines that are never wrapped.
+fn block_end(lines: &[&str], start: usize) -> usize {
+    let line = lines[start];
+    if let Some(marker) = list_marker_width(line) {
+        let indent = " ".repeat(marker);
+        let mut end = start + 1;
+        while end < lines.len() {
+            let next = lines[end];
+            match next.strip_prefix(indent.as_str()) {
+                Some(rest) if is_paragraph_line(rest) && list_marker_width(rest).is_none() => end += 1,
+                _ => break,
+            }
+        }
+        return end;
+    }
+
+    if !is_paragraph_line(line) {
+        return start;
+    }
+    let mut end = start + 1;
+    while end < lines.len() && is_paragraph_line(lines[end]) {
+        end += 1;
+    }
+    // A paragraph followed by `---` or `===` is a heading
+    if end < lines.len() && is_setext_underline(lines[end]) {
+        return start;
+    }
+    end
+}
+
+fn is_paragraph_line(line: &str) -> bool {
+    let Some(first) = line.chars().next() else { return false };
+    !first.is_whitespace()
+        && !matches!(first, '#' | '>' | '<' | '|')
+        && !line.contains('|')
+        && !is_fence(line)
+        && !is_setext_underline(line)
+        && !is_thematic_break(line)
+        && !is_link_definition(line)
+        && list_marker_width(line).is_none()
+}
+
+// Width of `- `, `* `, `+ ` or `12. ` at the start of a list item
+fn list_marker_width(line: &str) -> Option<usize> {
+    if ["- ", "* ", "+ "].iter().any(|marker| line.starts_with(marker)) {
+        return Some(2);
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && line[digits..].starts_with(". ") {
+        return Some(digits + 2);
+    }
+    None
+}
+
+fn is_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+fn is_setext_underline(line: &str) -> bool {
+    let line = line.trim_end();
+    !line.is_empty() && (line.chars().all(|c| c == '=') || line.chars().all(|c| c == '-'))
+}
+
+fn is_thematic_break(line: &str) -> bool {
+    let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.len() >= 3 && ['-', '*', '_'].iter().any(|&mark| compact.chars().all(|c| c == mark))
+}
+
+fn is_link_definition(line: &str) -> bool {
+    line.starts_with('[') && line.contains("]:")
+}
+
+// Greedy wrap of one line, continuation lines indented under a list
+// marker. None if the line can't be rebuilt exactly by joining on single
+// spaces (doubled or trailing spaces), since unwrapping would change it.
+fn wrap_line(line: &str, width: usize) -> Option<Vec<String>> {
+    let words = words(line);
+    if words.is_empty() || words.join(" ") != line {
+        return None;
+    }
+
+    let indent = " ".repeat(list_marker_width(line).unwrap_or(0));
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in words {
+        if current.is_empty() {
+            current = if lines.is_empty() { word } else { format!("{}{}", indent, word) };
+        } else if current.chars().count() + 1 + word.chars().count() <= width {
+            current.push(' ');
+            current.push_str(&word);
+        } else {
+            lines.push(std::mem::take(&mut current));
+            current = format!("{}{}", indent, word);
+        }
+    }
+    lines.push(current);
+    Some(lines)
+}
+
+// Space-separated words, keeping `inline code` and [link text](url) whole
+fn words(line: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut open = false;
+    for token in line.split(' ') {
+        match words.last_mut() {
+            Some(last) if open => {
+                last.push(' ');
+                last.push_str(token);
+            }
+            _ => words.push(token.to_string()),
+        }
+        let word = words.last().unwrap();
+        open = word.matches('`').count() % 2 == 1 || word.matches('[').count() > word.matches(']').count() || {
+            let after_link = word.rfind("](").map(|index| &word[index..]).unwrap_or("");
+            after_link.matches('(').count() > after_link.matches(')').count()
+        };
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_round_trip_keeps_code_lists_and_links() {
+        let body = "A long paragraph that needs wrapping, with a [link to the docs](https://example.com/docs) and `some code here`.\n\n- A list item that is also long enough to wrap onto a second line\n- Short item\n\n```\na code line that is long enough to wrap but must not be touched at all\n```\n\n| a table row that is long enough to wrap but is left alone | b |\n\nLines the author\nbroke by hand stay as they are";
+
+        let wrapped = wrap(body, 30);
+        assert_eq!(
+            wrapped,
+            "A long paragraph that needs\nwrapping, with a\n[link to the docs](https://example.com/docs)\nand `some code here`.\n\n- A list item that is also\n  long enough to wrap onto a\n  second line\n- Short item\n\n```\na code line that is long enough to wrap but must not be touched at all\n```\n\n| a table row that is long enough to wrap but is left alone | b |\n\nLines the author\nbroke by hand stay as they are"
+        );
+        assert_eq!(unwrap(&wrapped, 30), body);
+        assert_eq!(wrap(&wrapped, 30), wrapped);
+    }
+}